@@ -0,0 +1,131 @@
+//! # Baseline
+//!
+//! Compares the results of a run against the JSON summary of an earlier one, allowing
+//! loaded to act as a gate against performance regressions in CI.
+
+use crate::sla::SlaResult;
+use crate::stats::{SummaryStats, SCHEMA_VERSION};
+use crate::util::format_duration;
+use anyhow::{bail, Context, Result};
+use bigdecimal::ToPrimitive;
+use std::path::Path;
+
+/// Reads the JSON summary of an earlier run to compare against
+pub fn load(path: &Path) -> Result<SummaryStats> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the baseline {}", path.display()))?;
+    let baseline: SummaryStats = serde_json::from_str(&json)
+        .with_context(|| format!("{} isn't the JSON summary of a run", path.display()))?;
+    if baseline.schema_version() != SCHEMA_VERSION {
+        bail!(
+            "The baseline {} is of schema version {}, rather than {SCHEMA_VERSION}",
+            path.display(),
+            baseline.schema_version()
+        );
+    }
+    Ok(baseline)
+}
+
+/// Compares the throughput, p99 time to last byte latency and error rate of the run
+/// against the baseline, each of them passing unless it regressed by more than
+/// `threshold` percent (percentage points for the error rate)
+///
+/// The latency is left out when either summary is missing its p99.
+pub fn compare(baseline: &SummaryStats, current: &SummaryStats, threshold: f64) -> Vec<SlaResult> {
+    let mut results = vec![];
+
+    let before = baseline.mean_reqs_per_second().to_f64().unwrap_or_default();
+    let after = current.mean_reqs_per_second().to_f64().unwrap_or_default();
+    let change = relative_change(before, after);
+    results.push(SlaResult {
+        name: "baseline-throughput",
+        threshold: format!("-{threshold}% of {before:.3} req/s"),
+        actual: format!("{after:.3} req/s ({})", fmt_change(change, "%")),
+        passed: change.map_or(true, |c| c >= -threshold),
+    });
+
+    let p99 = |s: &SummaryStats| s.time_to_last_byte_latency().percentile("p99");
+    if let (Some(before), Some(after)) = (p99(baseline), p99(current)) {
+        #[allow(clippy::cast_precision_loss)]
+        let change = relative_change(before as f64, after as f64);
+        results.push(SlaResult {
+            name: "baseline-p99",
+            threshold: format!("+{threshold}% of {}", format_duration(before.into())),
+            actual: format!(
+                "{} ({})",
+                format_duration(after.into()),
+                fmt_change(change, "%")
+            ),
+            passed: change.map_or(true, |c| c <= threshold),
+        });
+    }
+
+    let before = baseline.error_rate();
+    let after = current.error_rate();
+    let change = after - before;
+    results.push(SlaResult {
+        name: "baseline-error-rate",
+        threshold: format!("+{threshold} points over {before:.3}%"),
+        actual: format!("{after:.3}% ({})", fmt_change(Some(change), " points")),
+        passed: change <= threshold,
+    });
+
+    results
+}
+
+/// The change from `before` to `after` in percent, unless there was nothing before
+fn relative_change(before: f64, after: f64) -> Option<f64> {
+    (before > 0.0).then(|| (after - before) / before * 100.0)
+}
+
+fn fmt_change(change: Option<f64>, unit: &str) -> String {
+    match change {
+        Some(change) => format!("{change:+.2}{unit}"),
+        None => "no baseline".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::baseline::compare;
+    use crate::stats::{RunStats, SummaryStats};
+    use bigdecimal::BigDecimal;
+
+    /// A second long run of `requests` requests with a constant latency
+    fn summary(requests: u64, latency: u64, errors: usize) -> SummaryStats {
+        let mut stats = RunStats::new(3);
+        stats.ttlb_latency_hist.record(latency).unwrap();
+        if errors > 0 {
+            stats.errors.insert(500, errors);
+        }
+        SummaryStats::new(
+            BigDecimal::from(1_000_000_000),
+            BigDecimal::from(0),
+            BigDecimal::from(0),
+            BigDecimal::from(requests),
+            &stats,
+            &[50.0, 99.0],
+        )
+    }
+
+    #[test]
+    fn fails_what_regressed_beyond_the_threshold() {
+        let baseline = summary(1000, 10_000_000, 0);
+
+        let results = compare(&baseline, &summary(950, 10_500_000, 5), 10.0);
+        let names = results.iter().map(|r| r.name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["baseline-throughput", "baseline-p99", "baseline-error-rate"]
+        );
+        assert!(results.iter().all(|r| r.passed), "{results:?}");
+        assert_eq!(results[0].actual, "950.000 req/s (-5.00%)");
+
+        let results = compare(&baseline, &summary(800, 12_000_000, 200), 10.0);
+        assert!(results.iter().all(|r| !r.passed), "{results:?}");
+
+        // Improving is never a regression
+        let results = compare(&baseline, &summary(2000, 1_000_000, 0), 10.0);
+        assert!(results.iter().all(|r| r.passed), "{results:?}");
+    }
+}
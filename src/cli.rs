@@ -1,7 +1,17 @@
+use crate::connection::latency::LatencyDistribution;
+use crate::engine::s3::size::{parse_size, SizeDistribution};
+use crate::engine::s3::uri::KeyList;
 use crate::stream::checksum::Checksum;
+use crate::stream::payload_dir::{PayloadDir, PayloadOrder};
+use crate::stream::perpetual_stream::DEFAULT_CACHED_CHECKSUMS;
+use crate::util;
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Method, Uri};
 use std::error::Error;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
@@ -23,13 +33,32 @@ long_about = None)]
 pub struct Loaded {
     #[command(subcommand)]
     pub loaded: LoadedCmd,
+
+    /// Format to write the logs in, whose level is set through `RUST_LOG`
+    ///
+    /// JSON logs have a record per line, those logged on behalf of a connection being
+    /// tagged with its `worker_id`, `connection_id` and `url`, and with the
+    /// `request_uri` of the request it was issuing, if any.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    /// A JSON object per line, for log aggregators
+    Json,
 }
 
 #[derive(Subcommand, Debug)]
 #[command(infer_subcommands = true)]
 pub enum LoadedCmd {
     /// Run an engine to generate http traffic to a server
-    Run(RunCmd),
+    Run(Box<RunCmd>),
+    /// Merge the latency histograms written by several runs with `--hdr-output`
+    Merge(MergeCmd),
+    /// List the engines a run can make use of, or describe the arguments of one of them
+    Engines(EnginesCmd),
     /// Generate shell completions
     GenCompletions {
         /// Set the shell for generating completions
@@ -42,19 +71,108 @@ pub enum LoadedCmd {
     },
 }
 
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, Args)]
+pub struct EnginesCmd {
+    /// The engine to describe, listing every engine when omitted
+    #[arg(value_name = "ENGINE")]
+    pub name: Option<String>,
+
+    /// Format to output the engines in
+    #[arg(short, long, value_enum, default_value_t = FormatType::Pretty)]
+    pub format: FormatType,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct MergeCmd {
+    /// The HDR histogram files to merge, e.g. one per load generator
+    #[arg(required = true, value_name = "FILES")]
+    pub files: Vec<PathBuf>,
+
+    /// Format to output the merged latency statistics
+    #[arg(short, long, value_enum, default_value_t = FormatType::Pretty)]
+    pub format: FormatType,
+
+    /// The latency percentiles to report, as a ',' separated list (e.g. `50,90,99,99.9`)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_percentage,
+        default_values_t = [50.0, 95.0, 99.0, 99.9, 99.99]
+    )]
+    pub percentiles: Vec<f64>,
+
+    /// Writes the merged latency distribution to the given path as CSV rows of
+    /// `(latency_ns, quantile, count)`, suitable for plotting a CDF
+    #[arg(long, value_name = "PATH")]
+    pub cdf_output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct RunCmd {
-    /// URL to generate load on
+    /// URL to generate load on, can be repeated to spread the connections across several
+    /// endpoints (see --connections-per-host)
     ///
     /// For example:
     /// `"http://localhost:9000/endpoint"`
-    #[arg(short, long)]
-    pub url: String,
+    #[arg(short, long, required = true)]
+    pub url: Vec<String>,
 
     /// Format to output results
     #[arg(short, long, value_enum, default_value_t = FormatType::Pretty)]
     pub format: FormatType,
 
+    /// Suppresses the live per-second stats and progress bar, only reporting the summary
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// The latency percentiles to report, as a ',' separated list (e.g. `50,90,99,99.9`)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_percentage,
+        default_values_t = [50.0, 95.0, 99.0, 99.9, 99.99]
+    )]
+    pub percentiles: Vec<f64>,
+
+    /// The number of significant figures the latency and size histograms are recorded with
+    ///
+    /// More figures resolve the tail latencies more finely at the cost of memory, fewer
+    /// lighten the overhead on constrained load generators.
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(1..=5))]
+    pub histogram_sigfig: u8,
+
+    /// Records the latencies and sizes of only this percentage of the requests, picked
+    /// at random, to lighten the overhead of recording every request under extreme rates
+    ///
+    /// The counts, throughput and errors still account for every request, but the
+    /// percentiles are estimated from the sample, the tail ones becoming less reliable
+    /// the smaller it is. The picks are derived from the seed.
+    #[arg(long, value_name = "PCT", value_parser = parse_percentage)]
+    pub sample_rate: Option<f64>,
+
+    /// How often the live stats are reported, e.g. `500ms` or `10s`
+    ///
+    /// Also sets the interval at which statsd metrics are pushed, the interval log is
+    /// appended to and `--target-throughput` adjusts the active connections.
+    #[arg(long, default_value = "1s", value_parser = parse_report_interval)]
+    pub report_interval: Duration,
+
+    /// How long an interrupted run waits for the requests in flight to finish before
+    /// summarizing without them, the summary being flagged as partial when some didn't.
+    /// A second Ctrl-C exits immediately
+    #[arg(long, default_value = "10s", value_parser = humantime::parse_duration)]
+    pub shutdown_grace: Duration,
+
+    /// Lists the N slowest requests by round trip time at the end of the run, along
+    /// with their method, URI and status
+    #[arg(long, value_name = "N")]
+    pub trace_slowest: Option<usize>,
+
+    /// Includes a breakdown of each worker's stats in the summary
+    #[arg(long)]
+    pub per_worker_stats: bool,
+
     /// Number of threads to use to generate load (defaults to number of physical cores)
     #[arg(short, long, default_value_t = num_cpus::get_physical())]
     pub threads: usize,
@@ -63,18 +181,337 @@ pub struct RunCmd {
     #[arg(short, long, default_value_t = 1)]
     pub connections: usize,
 
+    /// The number of connections to open to each --url, either a single count for all of
+    /// them or a ',' separated list of one count per --url (e.g. `4,2,2`) to load an
+    /// asymmetric fleet
+    ///
+    /// The counts must add up to --connections. Without it, the connections are spread
+    /// evenly across the urls. Either way, each worker gets its share of every url.
+    #[arg(long, value_name = "N", value_delimiter = ',')]
+    pub connections_per_host: Vec<usize>,
+
+    /// Whether connections are reused across requests or re-established for each request
+    #[arg(long, value_enum, default_value_t = ConnectionMode::Reuse)]
+    pub conn_mode: ConnectionMode,
+
+    /// Closes and re-establishes each connection after it has issued this many requests
+    ///
+    /// A value of 1 behaves the same as `--conn-mode per-request`
+    #[arg(long, value_name = "N")]
+    pub max_requests_per_connection: Option<NonZeroUsize>,
+
+    /// Issues requests as HTTP/1.0, which closes the connection after every response
+    #[arg(long)]
+    pub http10: bool,
+
+    /// Sends `Connection: close` with every request, establishing a connection per request
+    #[arg(long)]
+    pub connection_close: bool,
+
+    /// Sends the full url (`http://host/path`) as the target of the request line rather
+    /// than only its path, the way clients address a forward proxy
+    ///
+    /// Servers must accept both forms, but gateways often handle the absolute one
+    /// differently. The url is the one the engine requests, e.g. naming the bucket's
+    /// host with the S3 engine's virtual-hosted addressing.
+    #[arg(long)]
+    pub absolute_uri: bool,
+
+    /// Establishes every connection before the run starts, so that their handshakes
+    /// don't land in the measured window
+    #[arg(long)]
+    pub warm_connections: bool,
+
+    /// Issues a no-op request (`HEAD`) over each connection as it's warmed up, the
+    /// response of which doesn't count towards the stats of the run
+    #[arg(long, requires = "warm_connections")]
+    pub warm_request: bool,
+
+    /// Staggers the start of the connections evenly over this window (e.g. `30s`), so the
+    /// server sees the concurrency build up gradually rather than a herd of handshakes
+    ///
+    /// The window is part of the duration of the run. When full concurrency was reached
+    /// is logged and noted in the summary.
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = humantime::parse_duration,
+        conflicts_with = "warm_connections"
+    )]
+    pub connection_ramp: Option<Duration>,
+
+    /// How long to back off after failing to replace a dropped connection, doubling on every
+    /// further failure
+    #[arg(long, default_value = "100ms", value_parser = humantime::parse_duration)]
+    pub reconnect_backoff: Duration,
+
+    /// How the backoff between attempts to reconnect is randomized, to keep connections
+    /// that were dropped together from all reconnecting at the same time
+    ///
+    /// The jitter is derived from the seed.
+    #[arg(long, value_enum, default_value_t = BackoffJitter::None)]
+    pub backoff_jitter: BackoffJitter,
+
+    /// Randomly drops the connection after the given percentage of responses, forcing
+    /// it to reconnect, to measure the cost of recovering from dropped connections
+    ///
+    /// The drops are derived from the seed so they can be reproduced.
+    #[arg(long, value_name = "PCT", value_parser = parse_percentage)]
+    pub fault_drop_rate: Option<f64>,
+
+    /// Delays every request by a latency drawn from the distribution before issuing it,
+    /// either a fixed delay (`10ms`), a uniform range (`5ms..50ms`) or an exponential
+    /// distribution by its mean (`exp:20ms`)
+    ///
+    /// For checking the reported percentiles against a known distribution, or modelling
+    /// slow clients. The delays are derived from the seed so they can be reproduced.
+    #[arg(long, value_name = "DIST", conflicts_with = "rate")]
+    pub inject_latency: Option<LatencyDistribution>,
+
+    /// Stops reading every response body after the given number of bytes, dropping the
+    /// connection rather than transferring the rest of it
+    ///
+    /// For measuring how fast the server starts responding without paying for full
+    /// transfers, such as the time to first byte of large GETs.
+    #[arg(long, value_name = "BYTES")]
+    pub read_limit: Option<usize>,
+
+    /// Asks for compressed responses and decompresses gzip and deflate bodies to count
+    /// the bytes they come down to, alongside those transferred
+    ///
+    /// The summary reports the compression ratio of the responses. The bodies are only
+    /// decompressed to be counted, the engines still see them as they were sent.
+    #[arg(long)]
+    pub decompress: bool,
+
+    /// How the async runtime that drives the connections is laid out across threads
+    #[arg(long, value_enum, default_value_t = RuntimeFlavor::PerThread)]
+    pub runtime: RuntimeFlavor,
+
+    /// Disables Nagle's algorithm on the connections by setting `TCP_NODELAY`
+    #[arg(long)]
+    pub tcp_nodelay: bool,
+
+    /// Sets `SO_REUSEPORT` on the connections
+    #[arg(long)]
+    pub reuse_port: bool,
+
+    /// The size of the socket send buffer (`SO_SNDBUF`) in bytes
+    #[arg(long)]
+    pub send_buffer: Option<usize>,
+
+    /// The size of the socket receive buffer (`SO_RCVBUF`) in bytes
+    #[arg(long)]
+    pub recv_buffer: Option<usize>,
+
+    /// How long the addresses the host resolves to are cached for, connections of a
+    /// worker share the cache and are spread across its addresses in round robin order
+    ///
+    /// A TTL of 0s resolves the host for every new connection
+    #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+    pub dns_cache_ttl: Duration,
+
+    /// Which IP version to connect over when the host resolves to both IPv4 and IPv6 addresses
+    #[arg(long, value_enum, default_value_t = IpVersion::Auto)]
+    pub ip_version: IpVersion,
+
+    /// Binds the connections to the given local address before connecting, to send the
+    /// traffic out of a specific interface
+    ///
+    /// Can be repeated to spread the connections across several addresses in round robin
+    /// order, multiplying the source ports available to avoid exhausting them at very high
+    /// connection counts. Connections only bind to the addresses of the IP version of the
+    /// server they connect to.
+    #[arg(long, value_name = "IP")]
+    pub bind_address: Vec<IpAddr>,
+
+    /// Tunnels the connections through the HTTP proxy at the given url with `CONNECT`
+    #[arg(long, value_name = "URL", value_parser = parse_proxy)]
+    pub proxy: Option<Uri>,
+
+    /// Credentials to authenticate with the proxy using HTTP Basic auth, in the form of `user:pass`
+    #[arg(long, requires = "proxy", value_parser = parse_basic_auth)]
+    pub proxy_auth: Option<String>,
+
+    /// Adapts the number of active connections to reach the target throughput in requests per second
+    ///
+    /// Starts with a single active connection, adding connections while under the target and
+    /// backing off when overshooting it or when latency climbs. `--connections` caps the
+    /// number of connections that can be active at once.
+    #[arg(long)]
+    pub target_throughput: Option<f64>,
+
     /// Limits the number of requests per second
     #[arg(short, long)]
     pub rate_limit: Option<u32>,
 
+    /// How requests are issued over each connection
+    ///
+    /// The open models launch requests on a schedule of `--rate` requests per second, without
+    /// waiting for the earlier requests to complete. Slow responses can't throttle the offered
+    /// load, each connection opening further connections to the server whenever all of its own
+    /// are busy. Latency is measured from when each request was scheduled.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Arrival::Closed,
+        requires_ifs([("fixed", "rate"), ("poisson", "rate")])
+    )]
+    pub arrival: Arrival,
+
+    /// The number of requests per second across the whole run for the open models of
+    /// `--arrival`
+    #[arg(long, conflicts_with_all = ["rate_limit", "target_throughput", "fault_drop_rate"])]
+    pub rate: Option<f64>,
+
     /// Completes the run once the specified amount of time in seconds has elapsed
     #[arg(short, long, group = "completion", value_parser = parse_duration)]
     pub duration: Option<Duration>,
 
+    /// Stops recording the stats this long before the end of a --duration run, so the
+    /// requests cut short by its end don't skew the summary (e.g. `5s`)
+    #[arg(long, requires = "duration", value_parser = humantime::parse_duration)]
+    pub cooldown: Option<Duration>,
+
     /// Completes the run once the specified number of requests have been completed
     #[arg(short, long, group = "completion")]
     pub num_requests: Option<usize>,
 
+    /// Completes the run once the specified number of bytes have been transferred, written
+    /// and read alike (e.g. `1TiB`)
+    ///
+    /// Failed requests count towards it too, and the last requests may take the total
+    /// transferred past it.
+    #[arg(long, value_name = "SIZE", group = "completion", value_parser = parse_size)]
+    pub total_bytes: Option<usize>,
+
+    /// Credentials to authenticate with using HTTP Basic auth, in the form of `user:pass`
+    #[arg(long, group = "auth", value_parser = parse_basic_auth)]
+    pub basic_auth: Option<String>,
+
+    /// A token to authenticate with using HTTP Bearer auth
+    #[arg(long, group = "auth")]
+    pub bearer_token: Option<String>,
+
+    /// Aborts the run once the percentage of requests that errored exceeds the given threshold
+    ///
+    /// The error rate is only checked once a minimum number of requests have completed
+    /// to avoid aborting on a handful of early errors.
+    #[arg(long, value_parser = parse_percentage)]
+    pub abort_on_error_rate: Option<f64>,
+
+    /// Stops the run once its throughput has plateaued, i.e. the coefficient of variation
+    /// of the requests per second over the last --stable-window report intervals stays
+    /// below the given percentage for --stable-intervals intervals in a row
+    ///
+    /// Finds the steady state of capacity tests, reporting the throughput it settled at
+    /// and how long it took to reach it. A --duration or --num-requests still ends the
+    /// run if it never stabilizes.
+    #[arg(long, value_name = "PCT", value_parser = parse_percentage)]
+    pub stop_when_stable: Option<f64>,
+
+    /// The number of report intervals the variation of the throughput is measured over
+    #[arg(
+        long,
+        value_name = "INTERVALS",
+        default_value_t = 10,
+        requires = "stop_when_stable"
+    )]
+    pub stable_window: usize,
+
+    /// The number of intervals in a row the throughput must stay stable for
+    #[arg(
+        long,
+        value_name = "INTERVALS",
+        default_value_t = 3,
+        requires = "stop_when_stable"
+    )]
+    pub stable_intervals: usize,
+
+    /// Fails the run if the p99 time to last byte latency exceeds the given duration (e.g. `250ms`)
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pub max_p99: Option<Duration>,
+
+    /// Fails the run if the percentage of requests that errored exceeds the given threshold
+    #[arg(long, value_parser = parse_percentage)]
+    pub max_error_rate: Option<f64>,
+
+    /// Fails the run if the mean throughput in requests per second falls below the given rate
+    #[arg(long)]
+    pub min_throughput: Option<f64>,
+
+    /// Compares the results against those of an earlier run, read from the JSON summary
+    /// it printed, failing the run if its throughput, p99 time to last byte latency or
+    /// error rate regressed
+    ///
+    /// When running a scenario, every stage is compared against the baseline.
+    #[arg(long, value_name = "PATH")]
+    pub baseline: Option<PathBuf>,
+
+    /// How far, in percent, the throughput may drop and the p99 latency rise from the
+    /// --baseline before they're regressions, the error rate may rise by as many
+    /// percentage points
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 10.0,
+        value_parser = parse_percentage,
+        requires = "baseline"
+    )]
+    pub regression_threshold: f64,
+
+    /// Writes the SLA results as a `JUnit` XML report to the given path
+    #[arg(long, value_name = "PATH")]
+    pub junit: Option<PathBuf>,
+
+    /// Writes the full round trip time latency distribution to the given path as
+    /// CSV rows of `(latency_ns, quantile, count)`, suitable for plotting a CDF
+    ///
+    /// When running a scenario, the file holds the distribution of the last stage
+    #[arg(long, value_name = "PATH")]
+    pub cdf_output: Option<PathBuf>,
+
+    /// Writes the round trip time latency histogram to the given path in the compressed
+    /// HDR histogram format, which `loaded merge` combines across load generators
+    ///
+    /// When running a scenario, the file holds the histogram of the last stage
+    #[arg(long, value_name = "PATH")]
+    pub hdr_output: Option<PathBuf>,
+
+    /// Appends the throughput of every interval to the given path as CSV rows of
+    /// `(timestamp, requests_per_second, bytes_written_per_second, bytes_read_per_second)`
+    ///
+    /// Timestamps are in UTC, the header is only written when the file is new.
+    #[arg(long, value_name = "PATH")]
+    pub interval_log: Option<PathBuf>,
+
+    /// Pushes metrics of every interval to the statsd (or DogStatsD) server at `host:port`
+    ///
+    /// Requests, errors and bytes are sent as counters, the latencies as timers
+    /// sampled from the requests completed during the interval
+    #[arg(long, value_name = "HOST:PORT")]
+    pub statsd_addr: Option<String>,
+
+    /// POSTs the JSON summary to the given `http://` URL once the run completes
+    ///
+    /// Failing to deliver it is only warned about, it doesn't change the exit code. When
+    /// running a scenario, the summary of every stage is delivered.
+    #[arg(long, value_name = "URL", value_parser = parse_webhook)]
+    pub webhook: Option<Uri>,
+
+    /// Runs a multi-stage scenario described by the provided JSON file
+    ///
+    /// Each stage runs for a duration in seconds and can override the rate limit
+    /// and the number of connections, for example:
+    ///
+    ///   [{"duration": 60, "rate_limit": 1000}, {"duration": 120, "rate_limit": 5000, "connections": 64}]
+    #[arg(long, conflicts_with = "completion")]
+    pub scenario: Option<PathBuf>,
+
+    /// Prints the first N requests of every connection instead of sending them, then exits
+    #[arg(long, value_name = "N", conflicts_with = "scenario")]
+    pub dry_run: Option<usize>,
+
     /// A seed to inject some randomness per run (defaults to generated UUIDv4).
     ///
     /// It is up to the engine to make use of this and it may or may not
@@ -91,6 +528,70 @@ pub struct RunCmd {
 pub enum FormatType {
     Pretty,
     Json,
+    /// GitHub-flavored Markdown tables
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConnectionMode {
+    /// Keep a connection alive and reuse it for every request
+    Reuse,
+    /// Tear down and re-establish the connection for every request
+    PerRequest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BackoffJitter {
+    /// Backs off for exactly the doubled delay
+    None,
+    /// Backs off for anywhere between nothing and the doubled delay
+    Full,
+    /// Backs off for at least half the doubled delay, the rest of it being random
+    Equal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Arrival {
+    /// Closed model, a request is issued as soon as the previous one completes
+    Closed,
+    /// Open model, requests are launched at a fixed interval of `--rate` regardless of how
+    /// quickly the server responds
+    Fixed,
+    /// Open model, requests are launched following a Poisson process of `--rate` regardless
+    /// of how quickly the server responds
+    Poisson,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum RuntimeFlavor {
+    /// Each worker thread runs its own single-threaded runtime and its connections stay on it
+    PerThread,
+    /// A single multi-threaded runtime with a thread per worker is shared by all the
+    /// connections, letting tasks be stolen across threads
+    Multi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChecksumMode {
+    /// Calculate the checksum up front and send it as a header
+    Header,
+    /// Calculate the checksum as the body is streamed and send it in an `aws-chunked`
+    /// trailer, the `Content-Length` of the PUT being that of the encoded body. MD5
+    /// can't be sent in a trailer, nor can it be combined with --chunked
+    Trailer,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum IpVersion {
+    /// Connects to the first address the host resolves to
+    #[default]
+    Auto,
+    /// Only connects to IPv4 addresses
+    #[value(name = "4")]
+    V4,
+    /// Only connects to IPv6 addresses
+    #[value(name = "6")]
+    V6,
 }
 
 fn parse_duration(arg: &str) -> Result<Duration, std::num::ParseIntError> {
@@ -98,12 +599,118 @@ fn parse_duration(arg: &str) -> Result<Duration, std::num::ParseIntError> {
     Ok(Duration::from_secs(seconds))
 }
 
+fn parse_percentage(arg: &str) -> Result<f64, String> {
+    let pct: f64 = arg.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=100.0).contains(&pct) {
+        Ok(pct)
+    } else {
+        Err(format!("percentage `{arg}` is not between 0 and 100"))
+    }
+}
+
+fn parse_size_range(arg: &str) -> Result<SizeDistribution, String> {
+    match arg.parse()? {
+        range @ SizeDistribution::Uniform { .. } => Ok(range),
+        _ => Err(format!(
+            "`{arg}` isn't a range of sizes such as `1KiB..64KiB`"
+        )),
+    }
+}
+
+fn parse_report_interval(arg: &str) -> Result<Duration, String> {
+    let interval = humantime::parse_duration(arg).map_err(|e| format!("{e}"))?;
+    if interval.is_zero() {
+        return Err("report interval must be greater than 0".into());
+    }
+    Ok(interval)
+}
+
+fn parse_fraction(arg: &str) -> Result<f64, String> {
+    let fraction: f64 = arg.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&fraction) {
+        Ok(fraction)
+    } else {
+        Err(format!("fraction `{arg}` is not between 0 and 1"))
+    }
+}
+
+fn parse_zipf_exponent(arg: &str) -> Result<f64, String> {
+    let exponent: f64 = arg.parse().map_err(|e| format!("{e}"))?;
+    if exponent > 0.0 && exponent.is_finite() {
+        Ok(exponent)
+    } else {
+        Err(format!("exponent `{arg}` is not positive"))
+    }
+}
+
+fn parse_proxy(arg: &str) -> Result<Uri, String> {
+    let uri: Uri = arg.parse().map_err(|e| format!("{e}"))?;
+    if uri.host().is_none() {
+        return Err(format!("proxy `{arg}` has no host"));
+    }
+    Ok(uri)
+}
+
+fn parse_webhook(arg: &str) -> Result<Uri, String> {
+    let url = arg.parse::<Uri>().map_err(|e| format!("{e}"))?;
+    match url.scheme_str() {
+        Some("http") if url.host().is_some() => Ok(url),
+        Some("http") => Err(format!("`{arg}` has no host")),
+        _ => Err(format!("`{arg}` isn't an http:// URL")),
+    }
+}
+
+fn parse_basic_auth(arg: &str) -> Result<String, String> {
+    if arg.contains(':') {
+        Ok(arg.to_string())
+    } else {
+        Err(format!("invalid user:pass: no `:` found in `{arg}`"))
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct BenchArgs {
+    /// Serves the requests from a built-in loopback server listening on the address of
+    /// the url, which reports the number of requests it received once the run completes
+    #[arg(long)]
+    pub serve: bool,
+
+    /// The size in bytes of the responses of the built-in server
+    #[arg(long, default_value_t = 0, requires = "serve")]
+    pub response_size: usize,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ScriptArgs {
+    /// The file with the requests to issue in order, each line being of the form
+    /// `METHOD PATH [NAME=VALUE ...]`, e.g.:
+    ///
+    ///   POST /items Content-Type=application/json
+    ///   GET /items/1 Accept=application/json
+    ///   DELETE /items/1
+    ///
+    /// Blank lines and those starting with `#` are skipped. Header values can be read
+    /// from the environment as with `--headers`, and the summary breaks down the
+    /// requests and latencies of each line.
+    #[arg(long, value_name = "PATH")]
+    pub request_script: PathBuf,
+
+    /// Goes through the script over and over until the run completes, the default
+    #[arg(long = "loop", conflicts_with = "once")]
+    pub repeat: bool,
+
+    /// Stops each connection after a single pass through the script, the run
+    /// completing once all of them did
+    #[arg(long)]
+    pub once: bool,
+}
+
 #[derive(Debug, Clone, Args)]
 #[command(args_conflicts_with_subcommands = true)]
 pub struct SimpleArgs {
     /// The HTTP method for the request
-    #[arg(short, long)]
-    pub method: String,
+    #[arg(short, long, default_value = "GET", value_parser = util::parse_method)]
+    pub method: Method,
 
     /// The HTTP headers for the request
     ///
@@ -114,8 +721,14 @@ pub struct SimpleArgs {
     /// or as a series of args:
     ///
     ///   loaded run --url <URL> simple --method <METHOD> -X"ContentType=application/json" -X"Content-Length=500"
-    #[arg(short = 'X', long, value_parser = parse_key_val::< String, String >)]
-    pub headers: Vec<(String, String)>,
+    ///
+    /// A value of `env:<VAR>` is read from the environment variable at startup, keeping
+    /// secrets out of the command line and shell history (e.g. `-X"Authorization=env:API_TOKEN"`).
+    ///
+    /// The first value of a header replaces any loaded sets itself (e.g. `Host`), repeating
+    /// the header sends each further value as another field line of it.
+    #[arg(short = 'X', long, value_parser = parse_header)]
+    pub headers: Vec<(HeaderName, HeaderValue)>,
 
     /// The body of the http request
     #[arg(long, group = "b")]
@@ -124,6 +737,39 @@ pub struct SimpleArgs {
     /// The body of the http request, read in from the provided file
     #[arg(long, group = "b")]
     pub body_from_file: Option<PathBuf>,
+
+    /// Generates a body of a different size for every request, drawn uniformly from the
+    /// range of sizes (e.g. `1KiB..64KiB`) and filled using the seed
+    #[arg(long, value_name = "MIN..MAX", group = "b", value_parser = parse_size_range)]
+    pub body_size_range: Option<SizeDistribution>,
+
+    /// The percentage of each generated body that's random, the rest being zeros, from
+    /// incompressible (100) down to highly compressible bodies
+    #[arg(long, value_name = "PCT", default_value_t = 100.0, requires = "body_size_range", value_parser = parse_percentage)]
+    pub body_entropy: f64,
+
+    /// Issues a weighted mix of requests described by the provided JSON file
+    /// instead of a single request, picking one per request using the seed
+    ///
+    /// The file holds an array of request templates, e.g.:
+    ///
+    ///   [{"name": "items", "weight": 70, "method": "GET", "path": "/items"},
+    ///    {"weight": 30, "method": "POST", "path": "/orders", "headers": {"Content-Type": "application/json"}, "body": "{}"}]
+    ///
+    /// The summary breaks down the requests and latencies of each template.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["method", "headers", "b"])]
+    pub mix: Option<PathBuf>,
+}
+
+/// Parses a `name=value` header, see [`util::parse_header_value`] for the value
+fn parse_header(s: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{s}`"))?;
+    let name = name
+        .parse()
+        .map_err(|e| format!("invalid header name `{name}`: {e}"))?;
+    Ok((name, util::parse_header_value(value)?))
 }
 
 /// Parse a single key-value pair
@@ -144,18 +790,179 @@ where
 
 #[derive(Debug, Clone, Args)]
 #[command(args_conflicts_with_subcommands = true)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct S3Args {
     /// The bucket to operate on for our S3 requests
     #[arg(long, short)]
     pub bucket: String,
 
     /// The size in bytes of the object for a PUT/GET operation
-    #[arg(long, short)]
-    pub object_size: usize,
+    #[arg(long, short, required_unless_present_any = ["object_size_dist", "payload_dir"])]
+    pub object_size: Option<usize>,
+
+    /// The distribution the sizes of the objects are drawn from for every PUT, in place
+    /// of a single object size
+    ///
+    /// Either a fixed size (`1MiB`), a uniform range of sizes (`1KiB..10MiB`) or the path
+    /// to a histogram file with a `size,weight` bucket on each line. The sizes are drawn
+    /// using the seed.
+    #[arg(long, value_name = "DIST", conflicts_with = "object_size")]
+    pub object_size_dist: Option<SizeDistribution>,
+
+    /// Uploads the files of the directory as they are, each with its own size and
+    /// checksum, in place of generated objects
+    ///
+    /// The files are loaded in memory up front. Each connection starts off at a
+    /// different file derived from the seed.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["object_size", "object_size_dist", "unique_payloads"]
+    )]
+    pub payload_dir: Option<PayloadDir>,
+
+    /// The order the files of the --payload-dir are uploaded in
+    #[arg(long, value_enum, default_value_t = PayloadOrder::RoundRobin, requires = "payload_dir")]
+    pub payload_order: PayloadOrder,
 
     #[arg(long, short, value_enum, default_value_t = TrafficPattern::Put)]
     pub traffic_pattern: TrafficPattern,
 
+    /// How the keys of the GETs are picked from the keyspace
+    ///
+    /// Only applies to GETs that don't read back the object just PUT, i.e. the `get`,
+    /// `read-modify-write`, `conditional-get` and `split` patterns. The keys are drawn using the seed.
+    #[arg(long, value_enum, default_value_t = KeyDistribution::Sequential)]
+    pub key_distribution: KeyDistribution,
+
+    /// How the keyspace is divided up between the connections
+    #[arg(long, value_enum, default_value_t = KeySharding::Contiguous)]
+    pub key_sharding: KeySharding,
+
+    /// The exponent of the `zipf` key distribution, the higher the more skewed
+    /// towards the hottest keys
+    #[arg(long, default_value_t = 1.1, value_parser = parse_zipf_exponent)]
+    pub zipf_exponent: f64,
+
+    /// The fraction of the connections of each worker that only GET objects with the
+    /// `split` traffic pattern, the others only PUT
+    #[arg(long, value_name = "FRACTION", default_value_t = 0.5, value_parser = parse_fraction)]
+    pub reader_fraction: f64,
+
+    /// How the bucket is addressed in the request urls
+    #[arg(long, value_enum, default_value_t = Addressing::Path)]
+    pub addressing: Addressing,
+
+    /// Sends `Expect: 100-continue` with every PUT
+    ///
+    /// Note: the http client doesn't surface interim responses, so the body is still
+    /// streamed straight after the headers rather than once the server sends `100 Continue`.
+    #[arg(long)]
+    pub expect_continue: bool,
+
+    /// Streams the PUT bodies with `Transfer-Encoding: chunked` rather than sending
+    /// a `Content-Length` up front
+    ///
+    /// Can't be combined with trailing checksums, whose `aws-chunked` bodies S3 only
+    /// accepts with a `Content-Length`.
+    #[arg(long)]
+    pub chunked: bool,
+
+    /// Verifies the integrity of the objects being transferred
+    ///
+    /// For PUTs with an MD5 checksum, the returned `ETag` is compared against the digest
+    /// that was sent. Mismatches are counted and reported in the summary.
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Recomputes the checksum of every object read by a GET with the
+    /// `--checksum-algorithm`, comparing it against the one the server returns in the
+    /// checksum header of the response (e.g. `x-amz-checksum-sha256`)
+    ///
+    /// The server is asked for it with `x-amz-checksum-mode: ENABLED`. The checksums
+    /// verified and mismatched are counted in the summary.
+    #[arg(long, requires = "checksum_algorithm")]
+    pub verify_checksum: bool,
+
+    /// Has GETs read ranges of this size at random offsets within the objects rather than
+    /// the whole objects, e.g. `4KiB`, modelling random I/O within large objects
+    ///
+    /// Only applies to the `get` and `split` traffic patterns. The offsets are drawn
+    /// using the seed, and ranges read of another length are logged.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "object_size")]
+    pub random_read_size: Option<usize>,
+
+    /// The number of random ranges read from each object before moving on to the next key
+    #[arg(
+        long,
+        value_name = "COUNT",
+        default_value = "1",
+        requires = "random_read_size"
+    )]
+    pub random_read_count: NonZeroUsize,
+
+    /// Reads back each object PUT by the `both` traffic pattern only once this long has
+    /// passed since it was written, PUTting further objects in the meantime, to test the
+    /// read-after-write consistency of the server
+    ///
+    /// Objects read back missing (404) or stale, i.e. of another size or `ETag` than was
+    /// written, are logged and counted as inconsistent reads in the summary.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub read_after_write_delay: Option<Duration>,
+
+    /// Counts GETs of objects that don't exist (404) as misses rather than errors, for
+    /// reading from a keyspace that wasn't fully written
+    ///
+    /// The summary reports the hit/miss ratio of the GETs.
+    #[arg(long)]
+    pub allow_misses: bool,
+
+    /// Sends `If-None-Match: *` with every PUT so existing objects aren't overwritten
+    ///
+    /// PUTs rejected as the object already exists (412) aren't counted as errors, they're
+    /// reported separately in the summary.
+    #[arg(long)]
+    pub if_none_match: bool,
+
+    /// Deletes the objects that were PUT once the run completes
+    ///
+    /// Only the keys of successful PUTs are deleted, each connection keeping track
+    /// of those it wrote until then.
+    #[arg(long)]
+    pub cleanup: bool,
+
+    /// Requests the objects being PUT are encrypted server side
+    #[arg(long, value_enum)]
+    pub sse: Option<ServerSideEncryption>,
+
+    /// The KMS key to encrypt the objects being PUT with, requires `--sse aws:kms`
+    #[arg(long, value_name = "KEY_ID", requires = "sse")]
+    pub sse_kms_key_id: Option<String>,
+
+    /// The `Content-Type` declared for the objects being PUT (e.g. `image/jpeg`), which
+    /// GETs also send as their `Accept`
+    #[arg(long, value_name = "MIME", default_value = "application/octet-stream")]
+    pub content_type: HeaderValue,
+
+    /// The storage class of the objects being PUT (e.g. `STANDARD_IA`)
+    #[arg(long)]
+    pub storage_class: Option<String>,
+
+    /// Tags the objects being PUT, can be repeated to attach several tags
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_val::< String, String >)]
+    pub tagging: Vec<(String, String)>,
+
+    /// Cycles through the object keys listed in the given file, one per line, instead of
+    /// generating them, e.g. to replay an inventory exported from an existing bucket
+    ///
+    /// Each connection starts from a different key derived from the seed.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["prefix_folder_depth", "num_objs_per_prefix_folder", "num_branches_per_folder_depth"]
+    )]
+    pub key_file: Option<KeyList>,
+
     /// Specifies the folder depth that will be used to generate prefixes
     ///
     /// To illustrate, let's say we have an object with the name 'foo':
@@ -207,26 +1014,123 @@ pub struct S3Args {
     /// The checksum algorithm to calculate and use for the S3 request
     #[arg(long, short)]
     pub checksum_algorithm: Option<Checksum>,
+
+    /// How the checksum is sent along with the PUTs
+    #[arg(long, value_enum, default_value_t = ChecksumMode::Header)]
+    pub checksum_mode: ChecksumMode,
+
+    /// The most checksums of the objects to precalculate and cache, past which they're
+    /// calculated as the objects are uploaded
+    ///
+    /// Lower it to shorten the startup when precalculating every checksum of a large
+    /// buffer with a slow algorithm would stall it.
+    #[arg(long, value_name = "ENTRIES", default_value_t = DEFAULT_CACHED_CHECKSUMS)]
+    pub checksum_cache_size: usize,
+
+    /// Generates distinct pseudo-random bytes for every PUT rather than reusing a
+    /// shared buffer, defeating deduplication and compression on the server
+    ///
+    /// The checksum, if any, is always sent in a trailer as it can't be calculated up
+    /// front, whatever the --checksum-mode.
+    #[arg(long)]
+    pub unique_payloads: bool,
 }
 
 #[derive(Debug, Clone, Subcommand)]
 #[command(subcommand_help_heading = "Engines", subcommand_value_name = "ENGINE")]
 pub enum Engine {
-    /// An engine for load testing a single request variant ad nauseam
+    /// An engine for load testing a single request variant, or a weighted mix of them, ad nauseam
     ///
-    /// Note: does not make use of the `seed` argument.
-    #[command(arg_required_else_help = true)]
+    /// Note: makes use of the `seed` argument when issuing a `--mix` of requests.
     Simple(SimpleArgs),
     /// An engine for load testing an S3 server
     ///
     /// Note: makes use of the `seed` argument.
     #[command(arg_required_else_help = true)]
     S3(S3Args),
+    /// An engine issuing minimal GETs, for measuring the overhead of loaded itself
+    /// against a server that does next to nothing, such as the built-in one
+    Bench(BenchArgs),
+    /// An engine replaying a script of requests in order, each connection going
+    /// through it from the top
+    #[command(arg_required_else_help = true)]
+    Script(ScriptArgs),
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Addressing {
+    /// The bucket is the first segment of the path (e.g. `host/bucket/key`)
+    Path,
+    /// The bucket is part of the hostname (e.g. `bucket.host/key`)
+    Virtual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ServerSideEncryption {
+    /// Encrypts with keys managed by the server
+    #[value(name = "aes256")]
+    Aes256,
+    /// Encrypts with keys managed by KMS
+    #[value(name = "aws:kms")]
+    AwsKms,
+}
+
+impl ServerSideEncryption {
+    /// The value of the `x-amz-server-side-encryption` header
+    #[must_use]
+    pub fn header_value(self) -> &'static str {
+        match self {
+            ServerSideEncryption::Aes256 => "AES256",
+            ServerSideEncryption::AwsKms => "aws:kms",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeyDistribution {
+    /// Cycles through the keys in order
+    Sequential,
+    /// Picks keys uniformly at random
+    Uniform,
+    /// Picks keys following a Zipfian distribution, a few hot keys taking most of the GETs
+    Zipf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeySharding {
+    /// Each connection walks a keyspace of its own, named after the connection
+    Contiguous,
+    /// The connections share a single keyspace, connection i of N taking its keys i,
+    /// i+N, i+2N and so on, spreading the load evenly across backends that shard by
+    /// key prefix
+    Interleaved,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum TrafficPattern {
+    /// Only PUTs objects
     Put,
+    /// Only GETs objects
     Get,
+    /// PUTs each object, then GETs it back
     Both,
+    /// HEADs each object to capture its `ETag`, then GETs it with `If-None-Match` on that
+    /// `ETag`, expecting it to be unchanged (304)
+    ///
+    /// Exercises the conditional read path of caches and CDNs in front of the server.
+    /// The objects are expected to exist already, as with `read-modify-write`. Responses
+    /// that weren't modified are counted separately in the summary rather than as errors
+    ConditionalGet,
+    /// GETs each object, then PUTs it back with new contents
+    ///
+    /// The objects are expected to exist already, e.g. from an earlier `put` run with the
+    /// same seed, or --allow-misses can be used to tolerate those that don't
+    #[value(alias = "rmw")]
+    ReadModifyWrite,
+    /// Splits the connections into readers that only GET and writers that only PUT,
+    /// so reads and writes happen concurrently
+    ///
+    /// See --reader-fraction. Readers replay the keys of the writers (of the same worker),
+    /// so they can get ahead of them unless --allow-misses is used
+    Split,
 }
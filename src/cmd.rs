@@ -1,2 +1,4 @@
+pub mod engines;
 pub mod gen_completions;
+pub mod merge;
 pub mod run;
@@ -0,0 +1,271 @@
+use crate::cli::{EnginesCmd, FormatType, Loaded};
+use anyhow::{anyhow, Result};
+use clap::{Arg, Command, CommandFactory};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// An engine a run can make use of
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+struct EngineSummary {
+    name: String,
+    about: String,
+}
+
+/// An engine along with the arguments specific to it
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+struct EngineDescription {
+    name: String,
+    about: String,
+    arguments: Vec<ArgDescription>,
+    example: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+struct ArgDescription {
+    long: Option<String>,
+    short: Option<char>,
+    /// The name of the value the argument takes, if it takes any
+    value_name: Option<String>,
+    help: String,
+    required: bool,
+    default: Option<String>,
+    possible_values: Vec<String>,
+}
+
+/// Lists the engines a run can make use of, or describes one of them, from the
+/// metadata of the command line
+pub fn engines(args: &EnginesCmd) -> Result<()> {
+    let cli = Loaded::command();
+    let engines = engine_commands(&cli);
+    let Some(name) = &args.name else {
+        let summaries = engines.iter().copied().map(summarize).collect::<Vec<_>>();
+        match args.format {
+            FormatType::Pretty => {
+                let width = summaries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+                println!("Engines:");
+                for engine in &summaries {
+                    println!("  {:width$}  {}", engine.name, engine.about);
+                }
+                println!("\nDescribe the arguments of one with `loaded engines <ENGINE>`");
+            }
+            FormatType::Json => println!("{}", serde_json::to_string_pretty(&summaries)?),
+            FormatType::Markdown => {
+                println!("| Engine | Description |\n|---|---|");
+                for engine in &summaries {
+                    println!("| `{}` | {} |", engine.name, engine.about);
+                }
+            }
+        }
+        return Ok(());
+    };
+
+    let engine = engines
+        .iter()
+        .find(|e| e.get_name() == name)
+        .ok_or_else(|| {
+            let names = engines.iter().map(|e| e.get_name()).collect::<Vec<_>>();
+            anyhow!("No engine `{name}`, the engines are: {}", names.join(", "))
+        })?;
+    let description = describe(engine);
+    match args.format {
+        FormatType::Pretty => print!("{}", description.to_pretty()),
+        FormatType::Json => println!("{}", serde_json::to_string_pretty(&description)?),
+        FormatType::Markdown => print!("{}", description.to_markdown()),
+    }
+    Ok(())
+}
+
+/// The engine subcommands of `run`
+fn engine_commands(cli: &Command) -> Vec<&Command> {
+    cli.find_subcommand("run")
+        .map(|run| run.get_subcommands().filter(|c| !c.is_hide_set()).collect())
+        .unwrap_or_default()
+}
+
+fn summarize(engine: &Command) -> EngineSummary {
+    EngineSummary {
+        name: engine.get_name().to_string(),
+        about: engine
+            .get_about()
+            .map(ToString::to_string)
+            .unwrap_or_default(),
+    }
+}
+
+fn describe(engine: &Command) -> EngineDescription {
+    EngineDescription {
+        name: engine.get_name().to_string(),
+        about: engine
+            .get_long_about()
+            .or(engine.get_about())
+            .map(ToString::to_string)
+            .unwrap_or_default(),
+        arguments: engine
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .map(ArgDescription::new)
+            .collect(),
+        example: example(engine.get_name()),
+    }
+}
+
+/// An invocation of the engine to get started from
+fn example(engine: &str) -> Option<&'static str> {
+    match engine {
+        "simple" => Some("loaded run -u http://localhost:8080 -c 16 -d 30 simple -m GET"),
+        "s3" => {
+            Some("loaded run -u http://localhost:9000 -c 16 -d 30 s3 -b bucket -o 1048576 -t both")
+        }
+        "bench" => Some("loaded run -u http://127.0.0.1:8080 -n 100000 bench --serve"),
+        "script" => {
+            Some("loaded run -u http://localhost:8080 -c 4 script --request-script crud.txt --once")
+        }
+        _ => None,
+    }
+}
+
+impl ArgDescription {
+    fn new(arg: &Arg) -> Self {
+        let value_name = arg.get_action().takes_values().then(|| {
+            arg.get_value_names().and_then(<[_]>::first).map_or_else(
+                || arg.get_id().to_string().to_uppercase(),
+                ToString::to_string,
+            )
+        });
+        ArgDescription {
+            long: arg.get_long().map(ToString::to_string),
+            short: arg.get_short(),
+            value_name,
+            help: arg.get_help().map(ToString::to_string).unwrap_or_default(),
+            required: arg.is_required_set(),
+            default: (!arg.get_default_values().is_empty()).then(|| {
+                arg.get_default_values()
+                    .iter()
+                    .map(|v| v.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+            possible_values: arg
+                .get_possible_values()
+                .iter()
+                .filter(|v| !v.is_hide_set())
+                .map(|v| v.get_name().to_string())
+                .collect(),
+        }
+    }
+
+    /// How the argument is written on the command line, e.g. `-b, --bucket <BUCKET>`
+    fn usage(&self) -> String {
+        let mut usage = match (self.short, &self.long) {
+            (Some(short), Some(long)) => format!("-{short}, --{long}"),
+            (Some(short), None) => format!("-{short}"),
+            (None, Some(long)) => format!("    --{long}"),
+            (None, None) => String::new(),
+        };
+        if let Some(value_name) = &self.value_name {
+            if !usage.is_empty() {
+                usage.push(' ');
+            }
+            let _ = write!(usage, "<{value_name}>");
+        }
+        usage
+    }
+
+    /// The required, default and possible values of the argument, if any
+    fn notes(&self) -> String {
+        let mut notes = String::new();
+        if self.required {
+            notes.push_str(" [required]");
+        }
+        if let Some(default) = &self.default {
+            let _ = write!(notes, " [default: {default}]");
+        }
+        if !self.possible_values.is_empty() {
+            let _ = write!(
+                notes,
+                " [possible values: {}]",
+                self.possible_values.join(", ")
+            );
+        }
+        notes
+    }
+}
+
+impl EngineDescription {
+    fn to_pretty(&self) -> String {
+        let mut s = format!("{}: {}\n\nArguments:\n", self.name, self.about);
+        for arg in &self.arguments {
+            let _ = writeln!(
+                s,
+                "  {}\n          {}{}",
+                arg.usage(),
+                arg.help,
+                arg.notes()
+            );
+        }
+        if let Some(example) = self.example {
+            let _ = write!(s, "\nExample:\n  {example}\n");
+        }
+        s
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut s = format!("### `{}`\n\n{}\n\n", self.name, self.about);
+        s.push_str("| Argument | Description |\n|---|---|\n");
+        for arg in &self.arguments {
+            let _ = writeln!(
+                s,
+                "| `{}` | {}{} |",
+                arg.usage().trim(),
+                arg.help,
+                arg.notes()
+            );
+        }
+        if let Some(example) = self.example {
+            let _ = write!(s, "\n```sh\n{example}\n```\n");
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::Loaded;
+    use crate::cmd::engines::{describe, engine_commands, example};
+    use clap::{CommandFactory, Parser};
+
+    #[test]
+    fn describes_every_engine() {
+        let cli = Loaded::command();
+        let engines = engine_commands(&cli);
+        let names = engines.iter().map(|e| e.get_name()).collect::<Vec<_>>();
+        assert_eq!(names, ["simple", "s3", "bench", "script"]);
+        // A new engine should come with an example of its own, which parses
+        for name in &names {
+            let example = example(name).unwrap();
+            assert!(
+                Loaded::try_parse_from(example.split_whitespace()).is_ok(),
+                "{example}"
+            );
+        }
+
+        let s3 = describe(engines[1]);
+        let bucket = s3
+            .arguments
+            .iter()
+            .find(|a| a.long.as_deref() == Some("bucket"))
+            .unwrap();
+        assert!(bucket.required);
+        assert_eq!(bucket.usage(), "-b, --bucket <BUCKET>");
+        let pattern = s3
+            .arguments
+            .iter()
+            .find(|a| a.long.as_deref() == Some("traffic-pattern"))
+            .unwrap();
+        assert_eq!(pattern.default.as_deref(), Some("put"));
+        assert!(pattern.possible_values.contains(&"both".to_string()));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::cli::{FormatType, MergeCmd};
+use crate::stats::{hdr_decode, latency_cdf, LatencyStats};
+use anyhow::{Context, Result};
+use hdrhistogram::Histogram;
+
+/// Merges the latency histograms written by several runs into a single distribution
+/// and reports its statistics
+pub fn merge(args: &MergeCmd) -> Result<()> {
+    let histograms = args
+        .files
+        .iter()
+        .map(|path| {
+            let bytes = std::fs::read(path).with_context(|| format!("Failed to read {path:?}"))?;
+            hdr_decode(&bytes).with_context(|| format!("{path:?} isn't an HDR histogram"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let merged = merge_histograms(&histograms)?;
+
+    let latency = LatencyStats::new(&merged, &args.percentiles);
+    match args.format {
+        FormatType::Pretty => {
+            println!(
+                "Merged {} histograms, Total Requests: {}",
+                histograms.len(),
+                merged.len()
+            );
+            println!("Round Trip Time (RTT) Latency Statistics:\n{latency}");
+        }
+        FormatType::Json => println!("{}", serde_json::to_string_pretty(&latency)?),
+        FormatType::Markdown => println!(
+            "### Round Trip Time (RTT) Latency\n\n{}",
+            latency.to_markdown()
+        ),
+    }
+
+    if let Some(path) = &args.cdf_output {
+        std::fs::write(path, latency_cdf(&merged))?;
+    }
+    Ok(())
+}
+
+fn merge_histograms(histograms: &[Histogram<u64>]) -> Result<Histogram<u64>> {
+    // Start from the first so the merged histogram keeps its precision, growing
+    // its range to fit the values of the others
+    let mut merged = Histogram::new_from(&histograms[0]);
+    merged.auto(true);
+    for hist in histograms {
+        merged.add(hist)?;
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::merge::merge_histograms;
+    use crate::stats::{hdr_decode, hdr_encode};
+    use hdrhistogram::Histogram;
+
+    #[test]
+    fn merges_encoded_histograms() {
+        let mut a = Histogram::<u64>::new(3).unwrap();
+        a.record_n(1_000, 3).unwrap();
+        let mut b = Histogram::<u64>::new(3).unwrap();
+        b.record_n(5_000, 1).unwrap();
+
+        let decoded = [a, b]
+            .iter()
+            .map(|h| hdr_decode(&hdr_encode(h).unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        let merged = merge_histograms(&decoded).unwrap();
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged.value_at_quantile(0.5), 1_000);
+        assert_eq!(merged.max(), 5_003);
+    }
+}
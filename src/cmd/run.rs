@@ -1,37 +1,336 @@
-use crate::cli::{FormatType, RunCmd};
-use crate::stats::{InstantStats, RunStats, SummaryStats, WorkerStats};
+use crate::cli::{
+    Arrival, ChecksumMode, Engine, FormatType, RunCmd, RuntimeFlavor, ServerSideEncryption,
+    TrafficPattern,
+};
+use crate::connection::arrival::Schedule;
+use crate::connection::concurrency::ConcurrencyController;
+use crate::connection::proxy::Proxy;
+use crate::connection::ramp::RampSchedule;
+use crate::connection::{ConnectionOptions, SocketOptions, Warmup};
+use crate::engine::bench::server::LoopbackServer;
+use crate::run_config::RunConfig;
+use crate::stability::StabilityDetector;
+use crate::stats::{
+    hdr_encode, latency_cdf, InstantStats, RunStats, StableThroughput, SummaryStats,
+    ThroughputRange, WorkerStats, WorkerSummary,
+};
+use crate::stream::checksum::Checksum;
 use crate::worker::{CompletionCondition, Worker, WorkerInfo};
-use anyhow::{anyhow, Result};
-use bigdecimal::BigDecimal;
+use anyhow::{anyhow, bail, Context, Result};
+use bigdecimal::{BigDecimal, ToPrimitive};
 use bytesize::ByteSize;
 use governor::clock::DefaultClock;
 use governor::state::{InMemoryState, NotKeyed};
 use governor::{Quota, RateLimiter};
-use log::{error, info};
+use hdrhistogram::Histogram;
+use hyper::Uri;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{error, info, warn};
 use num_bigint::BigInt;
 
-use crate::util;
+use crate::interval_log::IntervalLog;
+use crate::junit::Suite;
+use crate::scenario::Scenario;
+use crate::sla::SlaResult;
+use crate::statsd::StatsdReporter;
+use crate::{baseline, junit, sla, util, webhook};
 use itertools::izip;
+use std::io::IsTerminal;
 use std::iter::zip;
-use std::num::NonZeroU32;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::process::exit;
-use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::Arc;
 use std::thread::{sleep, JoinHandle};
 use std::time::Duration;
 use std::{iter, thread};
+use tokio::runtime::{Handle, Runtime};
 use tokio::sync::RwLock;
 use tokio::time::Instant;
 
+/// How a run (or a single stage of a scenario) concluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Passed,
+    Aborted,
+    SlaFailed,
+    /// Regressed against the baseline
+    Regressed,
+}
+
 pub fn run(args: &RunCmd) -> Result<()> {
-    let run_flag = Arc::new(AtomicBool::new(true));
-    let run_flag_c = run_flag.clone();
+    validate(args)?;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let running_c = running.clone();
     ctrlc::set_handler(move || {
-        run_flag_c.store(false, Relaxed);
+        if running_c.swap(false, Relaxed) {
+            eprintln!("Finishing the requests in flight, press Ctrl-C again to exit immediately");
+        } else {
+            exit(130);
+        }
     })
     .expect("Error setting Ctrl-C handler");
 
+    if args.dry_run.is_some() {
+        return dry_run(args, &running);
+    }
+
+    // Serves the requests of the bench engine for as long as loaded runs
+    let loopbacks = match &args.engine {
+        Engine::Bench(bench_args) if bench_args.serve => args
+            .url
+            .iter()
+            .map(|url| {
+                let url = url.parse::<Uri>()?;
+                let addr = (url.host().unwrap(), url.port_u16().unwrap_or(80));
+                LoopbackServer::start(addr, bench_args.response_size)
+                    .with_context(|| format!("Failed to serve on {}:{}", addr.0, addr.1))
+            })
+            .collect::<Result<Vec<_>>>()?,
+        _ => vec![],
+    };
+
+    let baseline = args.baseline.as_deref().map(baseline::load).transpose()?;
+    let mut suites = vec![];
+    let outcome = match &args.scenario {
+        None => {
+            let (outcome, results) = run_stage(args, &running, baseline.as_ref())?;
+            suites.push(Suite {
+                name: "loaded".to_string(),
+                results,
+            });
+            outcome
+        }
+        Some(path) => {
+            let scenario = Scenario::from_file(path, args.threads)?;
+            let mut outcome = Outcome::Passed;
+            for (i, stage) in scenario.stages.iter().enumerate() {
+                if !running.load(Relaxed) {
+                    break;
+                }
+                println!("Stage {}/{}: {stage}", i + 1, scenario.stages.len());
+                let (stage_outcome, results) =
+                    run_stage(&stage.apply(args), &running, baseline.as_ref())?;
+                suites.push(Suite {
+                    name: format!("stage {}", i + 1),
+                    results,
+                });
+                match stage_outcome {
+                    Outcome::Passed => {}
+                    Outcome::Aborted => {
+                        outcome = Outcome::Aborted;
+                        break;
+                    }
+                    failed @ (Outcome::SlaFailed | Outcome::Regressed) => outcome = failed,
+                }
+            }
+            outcome
+        }
+    };
+
+    for server in &loopbacks {
+        eprintln!(
+            "Loopback server on {} received {} requests",
+            server.addr(),
+            server.requests()
+        );
+    }
+
+    if let Some(path) = &args.junit {
+        std::fs::write(path, junit::render(&suites))?;
+    }
+
+    match outcome {
+        Outcome::Passed => Ok(()),
+        Outcome::Aborted => exit(1),
+        Outcome::SlaFailed => exit(2),
+        Outcome::Regressed => exit(3),
+    }
+}
+
+/// Checks the arguments up front so mistakes are reported before any worker is
+/// spawned, rather than surfacing from deep within every connection
+fn validate(args: &RunCmd) -> Result<()> {
+    if args.connections < args.threads {
+        bail!(
+            "Connections ({}) cannot be less than the number of threads ({}).",
+            args.connections,
+            args.threads
+        )
+    }
+
+    for url in &args.url {
+        validate_url(url)?;
+    }
+    worker_urls(args)?;
+
+    if args.sample_rate == Some(0.0) {
+        bail!("--sample-rate must be greater than 0.")
+    }
+
+    if let Some(rate) = args.rate {
+        if args.arrival == Arrival::Closed {
+            bail!("--rate requires --arrival fixed or poisson.")
+        }
+        if rate <= 0.0 || !rate.is_finite() {
+            bail!("--rate must be greater than 0.")
+        }
+    }
+
+    if args.stable_window < 2 {
+        bail!("--stable-window must be at least 2 intervals.")
+    }
+    if args.stable_intervals == 0 {
+        bail!("--stable-intervals must be greater than 0.")
+    }
+
+    if let (Some(duration), Some(cooldown)) = (args.duration, args.cooldown) {
+        if cooldown >= duration {
+            bail!("--cooldown ({cooldown:?}) must be shorter than the --duration ({duration:?}).")
+        }
+    }
+
+    if let Engine::S3(s3_args) = &args.engine {
+        if s3_args.bucket.is_empty() || s3_args.bucket.contains('/') {
+            bail!("Invalid --bucket `{}`.", s3_args.bucket)
+        }
+        if s3_args.object_size == Some(0) {
+            bail!("--object-size must be greater than 0.")
+        }
+        if s3_args.read_after_write_delay.is_some()
+            && !matches!(s3_args.traffic_pattern, TrafficPattern::Both)
+        {
+            bail!("--read-after-write-delay requires --traffic-pattern both.")
+        }
+        if s3_args.random_read_size.is_some()
+            && !matches!(
+                s3_args.traffic_pattern,
+                TrafficPattern::Get | TrafficPattern::Split
+            )
+        {
+            bail!("--random-read-size requires --traffic-pattern get or split.")
+        }
+        if s3_args.random_read_size == Some(0) {
+            bail!("--random-read-size must be greater than 0.")
+        }
+        if s3_args.sse_kms_key_id.is_some() && s3_args.sse != Some(ServerSideEncryption::AwsKms) {
+            bail!("--sse-kms-key-id requires --sse aws:kms.")
+        }
+        let trailer = s3_args.checksum_mode == ChecksumMode::Trailer || s3_args.unique_payloads;
+        match s3_args.checksum_algorithm {
+            Some(Checksum::Md5) if trailer => {
+                bail!("MD5 checksums can't be sent in a trailer, use another --checksum-algorithm.")
+            }
+            Some(Checksum::Md5) if s3_args.verify_checksum => {
+                bail!("S3 returns no MD5 checksum header on GET for --verify-checksum to compare against, use another --checksum-algorithm.")
+            }
+            None if s3_args.checksum_mode == ChecksumMode::Trailer => {
+                bail!("--checksum-mode trailer requires a --checksum-algorithm.")
+            }
+            _ => {}
+        }
+        if s3_args.chunked && trailer {
+            bail!(
+                "--chunked can't be combined with trailing checksums, S3 rejects `aws-chunked` bodies sent with `Transfer-Encoding: chunked`."
+            )
+        }
+        if s3_args.verify && trailer {
+            bail!(
+                "--verify compares the ETag with the MD5 digest sent up front, it can't be combined with trailing checksums."
+            )
+        }
+    }
+    Ok(())
+}
+
+fn validate_url(url: &str) -> Result<()> {
+    let uri = url
+        .parse::<Uri>()
+        .with_context(|| format!("Invalid --url `{url}`"))?;
+    match uri.scheme_str() {
+        Some("http") => {}
+        Some(scheme) => bail!("Unsupported scheme `{scheme}` in --url, only http is supported."),
+        None => bail!("--url `{url}` has no scheme, e.g. `http://`."),
+    }
+    if uri.host().map_or(true, str::is_empty) {
+        bail!("--url `{url}` has no host.")
+    }
+    Ok(())
+}
+
+/// The urls the connections of each worker are opened to, the connections being spread
+/// across the urls by --connections-per-host and interleaved so that every worker gets
+/// its share of each url
+fn worker_urls(args: &RunCmd) -> Result<Vec<Vec<String>>> {
+    let hosts = args.url.len();
+    let per_host = match args.connections_per_host.as_slice() {
+        [] if args.connections < hosts => bail!(
+            "Connections ({}) cannot be less than the number of --url ({hosts}).",
+            args.connections
+        ),
+        [] => util::divvy(args.connections, hosts).collect(),
+        [count] => vec![*count; hosts],
+        counts if counts.len() == hosts => counts.to_vec(),
+        counts => bail!(
+            "--connections-per-host has {} counts for {hosts} --url, expected one or one per url.",
+            counts.len()
+        ),
+    };
+    let total = per_host.iter().sum::<usize>();
+    if total != args.connections {
+        bail!(
+            "--connections-per-host adds up to {total} connections rather than the {} of --connections.",
+            args.connections
+        )
+    }
+
+    let mut urls = util::interleave(&per_host)
+        .into_iter()
+        .map(|host| args.url[host].clone());
+    Ok(util::divvy(args.connections, args.threads)
+        .map(|connections| urls.by_ref().take(connections).collect())
+        .collect())
+}
+
+/// Has every connection print the requests it would send, without sending them
+fn dry_run(args: &RunCmd, running: &Arc<AtomicBool>) -> Result<()> {
+    let shared = SharedState {
+        run_flag: running.clone(),
+        rate_limit: None,
+        concurrency_limit: None,
+        connection_ramp: None,
+        runtime: None,
+    };
+
+    let handles = zip(0..args.threads, worker_urls(args)?)
+        .map(|(i, urls)| {
+            let worker_stats = Arc::new(RwLock::new(WorkerStats::new(args.histogram_sigfig)));
+            start_worker(args, urls, &shared, &None, i, &worker_stats)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for res in handles.into_iter().flat_map(JoinHandle::join) {
+        if let Err(e) = res {
+            error!("Worker encountered an error: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the workload to completion, reporting the summary of the run and
+/// returning the results of its SLA checks
+///
+/// `running` is the global flag flipped when the user interrupts loaded, each
+/// stage keeps its own run flag so completion conditions don't bleed into the next stage.
+fn run_stage(
+    args: &RunCmd,
+    running: &Arc<AtomicBool>,
+    baseline: Option<&SummaryStats>,
+) -> Result<(Outcome, Vec<SlaResult>)> {
+    let run_flag = Arc::new(AtomicBool::new(running.load(Relaxed)));
+
     let mut handles = vec![];
     let mut stats = vec![];
 
@@ -43,124 +342,366 @@ pub fn run(args: &RunCmd) -> Result<()> {
         )))
     });
 
-    // Build the completions conditions that correspond to our workers
-    let completion_conditions: Vec<Option<CompletionCondition>> = if args.num_requests.is_some() {
-        // Divvy up the requests across the workers so they're distributed evenly
-        util::divvy(args.num_requests.unwrap(), args.threads)
-            .map(|num_requests| Some(CompletionCondition::NumRequests(num_requests)))
-            .collect()
-    } else if args.duration.is_some() {
-        iter::repeat(Some(CompletionCondition::Duration(args.duration.unwrap())))
-            .take(args.threads)
-            .collect()
-    } else {
-        iter::repeat(None).take(args.threads).collect()
+    let mut controller = args
+        .target_throughput
+        .map(|target| ConcurrencyController::new(target, args.connections));
+    let mut sinks = IntervalSinks::open(args)?;
+
+    // Keep the shared runtime alive until all the workers have been joined
+    let runtime = build_shared_runtime(args)?;
+    let shared = SharedState {
+        run_flag: run_flag.clone(),
+        rate_limit: lim,
+        concurrency_limit: controller.as_ref().map(ConcurrencyController::active),
+        connection_ramp: args
+            .connection_ramp
+            .map(|window| Arc::new(RampSchedule::new(window, args.connections))),
+        runtime: runtime.as_ref().map(|rt| rt.handle().clone()),
     };
 
-    for (i, num_connections, completion_condition) in izip!(
+    for (i, urls, completion_condition) in izip!(
         0..args.threads,
-        util::divvy(args.connections, args.threads),
-        completion_conditions,
+        worker_urls(args)?,
+        completion_conditions(args),
     ) {
-        let worker_stats = Arc::new(RwLock::new(WorkerStats::default()));
-        let handle = start_worker(
-            &args,
-            num_connections,
-            &run_flag,
-            &lim,
-            &completion_condition,
-            i,
-            &worker_stats,
-        )?;
+        let worker_stats = Arc::new(RwLock::new(
+            WorkerStats::new(args.histogram_sigfig).with_slowest(args.trace_slowest.unwrap_or(0)),
+        ));
+        let handle = start_worker(args, urls, &shared, &completion_condition, i, &worker_stats)?;
 
         handles.push(handle);
         stats.push(worker_stats);
     }
 
-    let (requests_issued, bytes_written, bytes_read) =
-        wait_for_completion(&args, &run_flag, &handles, &mut stats);
+    let (requests_issued, bytes_written, bytes_read, aborted, stable, range) = wait_for_completion(
+        args,
+        running,
+        &run_flag,
+        &handles,
+        &mut stats,
+        controller.as_mut(),
+        &mut sinks,
+    );
 
-    let infos = handles
-        .into_iter()
-        .flat_map(JoinHandle::join)
-        .filter_map(|res| match res {
-            Ok(o) => Some(o),
-            Err(e) => {
-                error!("Worker encountered an error: {}", e);
-                None
-            }
-        })
-        .collect::<Vec<_>>();
+    let (infos, unfinished) = join_workers(args, running, handles);
 
     if infos.is_empty() {
         exit(1)
     }
 
+    let connection_requests = get_connection_requests(&infos);
     let total_runtime = get_total_runtime(infos);
+    // Nothing's recorded past the start of the cooldown
+    let total_runtime = match (args.duration, args.cooldown) {
+        (Some(duration), Some(cooldown)) => {
+            total_runtime.min(duration.saturating_sub(cooldown).as_nanos())
+        }
+        _ => total_runtime,
+    };
+    let run_stats = summarize_worker_stats(&stats, args.histogram_sigfig)?;
     let summary_stats = SummaryStats::new(
         BigDecimal::from(total_runtime),
         bytes_written.into(),
         bytes_read.into(),
         requests_issued.into(),
-        summarize_worker_stats(&stats)?,
+        &run_stats,
+        &args.percentiles,
+    )
+    .with_connection_requests(&connection_requests)
+    .with_sample_rate(args.sample_rate)
+    .with_unfinished_workers(unfinished)
+    .with_stable_throughput(stable)
+    .with_throughput_range(range)
+    .with_full_concurrency(
+        shared
+            .connection_ramp
+            .as_ref()
+            .and_then(|ramp| ramp.reached_after()),
+    )
+    .with_config(RunConfig::new(args));
+    let summary_stats = if args.per_worker_stats {
+        summary_stats.with_workers(
+            stats
+                .iter()
+                .enumerate()
+                .map(|(i, s)| WorkerSummary::new(i, &s.blocking_read(), &args.percentiles))
+                .collect(),
+        )
+    } else {
+        summary_stats
+    };
+    let sla_results = sla::evaluate(args, &run_stats, &summary_stats);
+
+    report(args, &summary_stats, &run_stats)?;
+    let comparisons = compare_to_baseline(args, baseline, &summary_stats);
+
+    if let Some(c) = controller {
+        println!("Settled on {} active connections", c.active().load(Relaxed));
+    }
+
+    let outcome = outcome(args, aborted, &sla_results, &comparisons);
+    Ok((
+        outcome,
+        sla_results.into_iter().chain(comparisons).collect(),
+    ))
+}
+
+/// Compares the results against the baseline, if there's one, printing how they
+/// changed alongside the summary (or to stderr past a JSON one)
+fn compare_to_baseline(
+    args: &RunCmd,
+    baseline: Option<&SummaryStats>,
+    summary_stats: &SummaryStats,
+) -> Vec<SlaResult> {
+    let Some(baseline) = baseline else {
+        return vec![];
+    };
+    let comparisons = baseline::compare(baseline, summary_stats, args.regression_threshold);
+    let mut report = format!(
+        "Compared to the baseline {}:\n",
+        args.baseline.as_ref().unwrap().display()
     );
+    for comparison in &comparisons {
+        report.push_str(&format!("\t{comparison}\n"));
+    }
+    match args.format {
+        FormatType::Json => eprint!("{report}"),
+        FormatType::Pretty | FormatType::Markdown => print!("{report}"),
+    }
+    comparisons
+}
 
+/// Prints the summary of the run in the requested format, writes the latency
+/// distribution and delivers the summary to the webhook if requested
+fn report(args: &RunCmd, summary_stats: &SummaryStats, run_stats: &RunStats) -> Result<()> {
     match args.format {
         FormatType::Pretty => println!("{summary_stats}"),
-        FormatType::Json => println!("{}", serde_json::to_string_pretty(&summary_stats)?),
+        FormatType::Json => println!("{}", serde_json::to_string_pretty(summary_stats)?),
+        FormatType::Markdown => println!("{}", summary_stats.to_markdown()),
+    }
+
+    if let Some(path) = &args.cdf_output {
+        std::fs::write(path, latency_cdf(&run_stats.rtt_latency_hist))?;
+    }
+
+    if let Some(path) = &args.hdr_output {
+        std::fs::write(path, hdr_encode(&run_stats.rtt_latency_hist)?)?;
+    }
+
+    if let Some(url) = &args.webhook {
+        if let Err(e) = webhook::post(url, summary_stats) {
+            warn!("Failed to deliver the summary to webhook {url}: {e}");
+        }
     }
 
     Ok(())
 }
 
+/// Reports why the run didn't pass, if it didn't
+fn outcome(
+    args: &RunCmd,
+    aborted: bool,
+    sla_results: &[SlaResult],
+    comparisons: &[SlaResult],
+) -> Outcome {
+    if aborted {
+        eprintln!(
+            "Run aborted: error rate exceeded {}%",
+            args.abort_on_error_rate.unwrap()
+        );
+        return Outcome::Aborted;
+    }
+
+    let failed_slas = sla_results.iter().filter(|r| !r.passed).collect::<Vec<_>>();
+    if !failed_slas.is_empty() {
+        eprintln!("SLA check failed:");
+        for result in failed_slas {
+            eprintln!("\t{result}");
+        }
+        return Outcome::SlaFailed;
+    }
+
+    if comparisons.iter().any(|c| !c.passed) {
+        eprintln!("Regressed against the baseline");
+        return Outcome::Regressed;
+    }
+
+    Outcome::Passed
+}
+
+/// State shared by all the workers of a run
+struct SharedState {
+    run_flag: Arc<AtomicBool>,
+    rate_limit: Option<Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>,
+    concurrency_limit: Option<Arc<AtomicUsize>>,
+    connection_ramp: Option<Arc<RampSchedule>>,
+    /// The runtime the connections of all workers are spawned onto, when not
+    /// running a runtime per worker thread
+    runtime: Option<Handle>,
+}
+
+/// Builds the multi-threaded runtime shared by all the workers, if requested
+fn build_shared_runtime(args: &RunCmd) -> Result<Option<Runtime>> {
+    Ok(match args.runtime {
+        RuntimeFlavor::PerThread => None,
+        RuntimeFlavor::Multi => Some(
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(args.threads)
+                .enable_all()
+                .build()?,
+        ),
+    })
+}
+
 fn start_worker(
     args: &RunCmd,
-    connections: usize,
-    run_flag: &Arc<AtomicBool>,
-    lim: &Option<Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>,
+    urls: Vec<String>,
+    shared: &SharedState,
     completion_condition: &Option<CompletionCondition>,
     worker_id: usize,
     worker_stats: &Arc<RwLock<WorkerStats>>,
 ) -> Result<JoinHandle<Result<WorkerInfo>>> {
-    let url = args.url.clone();
     info!("Starting worker {}", worker_id);
 
     let mut worker = Worker {
         worker_id,
         stats: worker_stats.clone(),
-        run_flag: run_flag.clone(),
-        rate_limit: lim.clone(),
+        run_flag: shared.run_flag.clone(),
+        rate_limit: shared.rate_limit.clone(),
+        connection_options: ConnectionOptions {
+            mode: args.conn_mode,
+            max_requests: args.max_requests_per_connection.map(NonZeroUsize::get),
+            schedule: schedule(args),
+            http10: args.http10,
+            connection_close: args.connection_close,
+            absolute_uri: args.absolute_uri,
+            authorization: authorization(args),
+            socket: SocketOptions {
+                nodelay: args.tcp_nodelay,
+                reuse_port: args.reuse_port,
+                send_buffer: args.send_buffer,
+                recv_buffer: args.recv_buffer,
+                ip_version: args.ip_version,
+                bind_addresses: args.bind_address.clone(),
+            },
+            dry_run: args.dry_run,
+            reconnect_backoff: args.reconnect_backoff,
+            backoff_jitter: args.backoff_jitter,
+            fault_drop_rate: args.fault_drop_rate,
+            inject_latency: args.inject_latency.clone(),
+            read_limit: args.read_limit,
+            decompress: args.decompress,
+            sample_rate: args.sample_rate,
+            proxy: proxy(args),
+            dns_cache_ttl: args.dns_cache_ttl,
+            warmup: match (args.warm_connections, args.warm_request) {
+                (_, true) => Some(Warmup::Request),
+                (true, false) => Some(Warmup::Connect),
+                (false, false) => None,
+            },
+        },
+        num_workers: args.threads,
+        num_connections: args.connections,
+        concurrency_limit: shared.concurrency_limit.clone(),
+        connection_ramp: shared.connection_ramp.clone(),
     };
     let engine = args.engine.clone();
     let completion_condition = completion_condition.clone();
     let seed = args.seed.clone();
+    let shared_runtime = shared.runtime.clone();
     let handle = thread::Builder::new()
         .name(format!("Worker {worker_id}"))
         .spawn(move || {
-            let rt = tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .expect("build runtime");
-
-            let local = tokio::task::LocalSet::new();
-            local.block_on(&rt, async move {
-                worker
-                    .run(engine, url, connections, seed, completion_condition)
-                    .await
-            })
+            let run = worker.run(engine, urls, seed, completion_condition);
+            match shared_runtime {
+                // The worker's connections are spawned onto the shared runtime, this
+                // thread only waits on them
+                Some(handle) => handle.block_on(run),
+                None => tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("build runtime")
+                    .block_on(run),
+            }
         })
         .map_err(|e| anyhow!("{:?}", e))?;
 
     Ok(handle)
 }
 
+/// Builds the `Authorization` header value from the auth args, if any were provided
+fn authorization(args: &RunCmd) -> Option<String> {
+    if let Some(credentials) = &args.basic_auth {
+        Some(format!("Basic {}", base64::encode(credentials)))
+    } else {
+        args.bearer_token
+            .as_ref()
+            .map(|token| format!("Bearer {token}"))
+    }
+}
+
+fn proxy(args: &RunCmd) -> Option<Proxy> {
+    args.proxy.as_ref().map(|uri| Proxy {
+        authority: format!("{}:{}", uri.host().unwrap(), uri.port_u16().unwrap_or(80)),
+        authorization: args
+            .proxy_auth
+            .as_ref()
+            .map(|credentials| format!("Basic {}", base64::encode(credentials))),
+    })
+}
+
+/// The schedule each connection launches requests on for the open models, dividing the
+/// rate evenly across the connections
+#[allow(clippy::cast_precision_loss)]
+fn schedule(args: &RunCmd) -> Option<Schedule> {
+    let rate = args.rate? / args.connections as f64;
+    match args.arrival {
+        Arrival::Closed => None,
+        Arrival::Fixed => Some(Schedule::Fixed(Duration::from_secs_f64(1.0 / rate))),
+        Arrival::Poisson => Some(Schedule::Poisson(rate)),
+    }
+}
+
+/// Builds the completions conditions that correspond to our workers
+fn completion_conditions(args: &RunCmd) -> Vec<Option<CompletionCondition>> {
+    if let Some(num_requests) = args.num_requests {
+        // Divvy up the requests across the workers so they're distributed evenly
+        util::divvy(num_requests, args.threads)
+            .map(|num_requests| Some(CompletionCondition::NumRequests(num_requests)))
+            .collect()
+    } else if let Some(total_bytes) = args.total_bytes {
+        util::divvy(total_bytes, args.threads)
+            .map(|bytes| Some(CompletionCondition::TotalBytes(bytes)))
+            .collect()
+    } else if let Some(duration) = args.duration {
+        iter::repeat(Some(CompletionCondition::Duration {
+            duration,
+            cooldown: args.cooldown,
+        }))
+        .take(args.threads)
+        .collect()
+    } else {
+        iter::repeat(None).take(args.threads).collect()
+    }
+}
+
 fn wait_for_completion(
     args: &RunCmd,
+    running: &Arc<AtomicBool>,
     run_flag: &Arc<AtomicBool>,
     worker_handles: &[JoinHandle<Result<WorkerInfo>>],
     current_stats: &mut Vec<Arc<RwLock<WorkerStats>>>,
-) -> (BigInt, BigInt, BigInt) {
-    let dur = Duration::from_millis(1000);
+    mut controller: Option<&mut ConcurrencyController>,
+    sinks: &mut IntervalSinks,
+) -> (
+    BigInt,
+    BigInt,
+    BigInt,
+    bool,
+    Option<StableThroughput>,
+    Option<ThroughputRange>,
+) {
+    let dur = args.report_interval;
     let mut previous_stats: Vec<InstantStats> = vec![];
     for _ in 0..args.threads {
         previous_stats.push(InstantStats::default());
@@ -168,8 +709,20 @@ fn wait_for_completion(
     let mut total_reqs: BigInt = BigInt::default();
     let mut total_bytes_written: BigInt = BigInt::default();
     let mut total_bytes_read: BigInt = BigInt::default();
+    let mut aborted = false;
+    let mut stability = args.stop_when_stable.map(|threshold| {
+        StabilityDetector::new(threshold, args.stable_window, args.stable_intervals)
+    });
+    let mut stable = None;
+    let mut range: Option<ThroughputRange> = None;
+    let mut latency_totals = LatencyTotals::default();
+    let start = Instant::now();
+    let progress = progress_bar(args);
 
     loop {
+        if !running.load(Relaxed) {
+            run_flag.store(false, Relaxed);
+        }
         if !run_flag.load(Relaxed) || worker_handles.iter().all(JoinHandle::is_finished) {
             break;
         }
@@ -181,14 +734,248 @@ fn wait_for_completion(
         total_bytes_written += stats.bytes_written;
         total_bytes_read += stats.bytes_read;
 
-        println!(
-            "{} Req/s, Write/s: {}, Read/s: {}",
-            stats.requests_issued,
-            ByteSize::b(stats.bytes_written as u64).to_string_as(true),
-            ByteSize::b(stats.bytes_read as u64).to_string_as(true)
+        let rates = stats.per_second(dur);
+        // Leaving out the interval the run ended in, which would understate the trough
+        if run_flag.load(Relaxed) && !worker_handles.iter().all(JoinHandle::is_finished) {
+            range
+                .get_or_insert_with(|| ThroughputRange::new(&rates))
+                .observe(&rates);
+        }
+        let mut line = progress_line(&rates, current_stats, args.histogram_sigfig);
+        if let Some(c) = controller.as_mut() {
+            let latency = latency_totals.interval_mean(current_stats);
+            #[allow(clippy::cast_precision_loss)]
+            let active = c.adjust(rates.requests_issued as f64, latency);
+            line.push_str(&format!(", Active Connections: {active}"));
+        }
+        if let Some(reporter) = &mut sinks.statsd {
+            if let Err(e) = reporter.push(&stats, current_stats) {
+                error!("Failed to push metrics to statsd: {e}");
+            }
+        }
+        if let Some(log) = &mut sinks.log {
+            if let Err(e) = log.append(&rates) {
+                error!("Failed to append to the interval log: {e}");
+            }
+        }
+        match &progress {
+            Some(bar) => {
+                bar.set_position(match (args.num_requests, args.total_bytes) {
+                    (Some(_), _) => total_reqs.to_u64().unwrap_or_default(),
+                    (None, Some(_)) => (&total_bytes_written + &total_bytes_read)
+                        .to_u64()
+                        .unwrap_or_default(),
+                    (None, None) => start.elapsed().as_secs(),
+                });
+                bar.println(line);
+            }
+            None if !args.quiet => println!("{line}"),
+            None => {}
+        }
+
+        if let Some(threshold) = args.abort_on_error_rate {
+            if exceeds_error_rate(&total_reqs, sum_errors(current_stats), threshold) {
+                error!("Error rate exceeded {threshold}%, aborting run");
+                run_flag.store(false, Relaxed);
+                aborted = true;
+            }
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        if let Some(rate) = stability
+            .as_mut()
+            .and_then(|s| s.observe(rates.requests_issued as f64))
+        {
+            info!("Throughput stabilized at {rate:.2} Req/s, stopping the run");
+            stable = Some(StableThroughput::new(rate, start.elapsed()));
+            stability = None;
+            run_flag.store(false, Relaxed);
+        }
+    }
+    if let Some(bar) = progress {
+        bar.finish_and_clear();
+    }
+    (
+        total_reqs,
+        total_bytes_written,
+        total_bytes_read,
+        aborted,
+        stable,
+        range,
+    )
+}
+
+/// Joins the workers once they're done, leaving out those that errored
+///
+/// Also returns the number of workers of an interrupted run that were given up on for
+/// not finishing within the grace period.
+fn join_workers(
+    args: &RunCmd,
+    running: &Arc<AtomicBool>,
+    handles: Vec<JoinHandle<Result<WorkerInfo>>>,
+) -> (Vec<WorkerInfo>, usize) {
+    let (handles, unfinished) = if running.load(Relaxed) {
+        (handles, 0)
+    } else {
+        drain(handles, args.shutdown_grace)
+    };
+
+    let infos = handles
+        .into_iter()
+        .flat_map(JoinHandle::join)
+        .filter_map(|res| match res {
+            Ok(o) => Some(o),
+            Err(e) => {
+                error!("Worker encountered an error: {}", e);
+                None
+            }
+        })
+        .collect();
+    (infos, unfinished)
+}
+
+/// Gives the workers of an interrupted run up to `grace` to finish the requests they
+/// have in flight, returning those that did so they can be joined without blocking,
+/// along with the number of those that didn't
+fn drain(
+    handles: Vec<JoinHandle<Result<WorkerInfo>>>,
+    grace: Duration,
+) -> (Vec<JoinHandle<Result<WorkerInfo>>>, usize) {
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline && !handles.iter().all(JoinHandle::is_finished) {
+        sleep(Duration::from_millis(10));
+    }
+
+    let (finished, unfinished): (Vec<_>, Vec<_>) =
+        handles.into_iter().partition(JoinHandle::is_finished);
+    if !unfinished.is_empty() {
+        warn!(
+            "{} workers didn't finish within {grace:?}, the summary is partial",
+            unfinished.len()
         );
     }
-    (total_reqs, total_bytes_written, total_bytes_read)
+    (finished, unfinished.len())
+}
+
+/// Where the stats of every interval are sent besides the console
+struct IntervalSinks {
+    statsd: Option<StatsdReporter>,
+    log: Option<IntervalLog>,
+}
+
+impl IntervalSinks {
+    fn open(args: &RunCmd) -> Result<Self> {
+        Ok(IntervalSinks {
+            statsd: args
+                .statsd_addr
+                .as_deref()
+                .map(|addr| StatsdReporter::connect(addr, args.histogram_sigfig))
+                .transpose()?,
+            log: args
+                .interval_log
+                .as_deref()
+                .map(IntervalLog::open)
+                .transpose()?,
+        })
+    }
+}
+
+/// Builds a progress bar tracking the run towards its completion condition, if
+/// there is one and the output is an interactive terminal
+fn progress_bar(args: &RunCmd) -> Option<ProgressBar> {
+    if args.quiet || !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let (len, template) = if let Some(num_requests) = args.num_requests {
+        (
+            num_requests as u64,
+            "[{bar:40}] {pos}/{len} requests ({percent}%, ETA {eta})",
+        )
+    } else if let Some(total_bytes) = args.total_bytes {
+        (
+            total_bytes as u64,
+            "[{bar:40}] {bytes}/{total_bytes} ({percent}%, ETA {eta})",
+        )
+    } else if let Some(duration) = args.duration {
+        (
+            duration.as_secs(),
+            "[{bar:40}] {pos}/{len}s ({percent}%, ETA {eta})",
+        )
+    } else {
+        return None;
+    };
+    let style = ProgressStyle::with_template(template)
+        .expect("valid progress template")
+        .progress_chars("=> ");
+    Some(ProgressBar::new(len).with_style(style))
+}
+
+/// Running totals of the time to last byte latency histograms, used to derive
+/// the mean latency of each interval from the cumulative histograms
+#[derive(Default)]
+struct LatencyTotals {
+    count: u64,
+    sum: f64,
+}
+
+impl LatencyTotals {
+    #[allow(clippy::cast_precision_loss)]
+    fn interval_mean(&mut self, th: &[Arc<RwLock<WorkerStats>>]) -> Option<f64> {
+        let (count, sum) = th.iter().fold((0, 0.0), |(count, sum), s| {
+            let hist = &s.blocking_read().run_stats.ttlb_latency_hist;
+            (count + hist.len(), sum + hist.mean() * hist.len() as f64)
+        });
+        let interval_count = count - self.count;
+        let interval_sum = sum - self.sum;
+        self.count = count;
+        self.sum = sum;
+        (interval_count > 0).then(|| interval_sum / interval_count as f64)
+    }
+}
+
+/// The throughput of the report interval, along with its round trip times
+fn progress_line(rates: &InstantStats, th: &[Arc<RwLock<WorkerStats>>], sigfig: u8) -> String {
+    let mut line = format!(
+        "{} Req/s, Write/s: {}, Read/s: {}",
+        rates.requests_issued,
+        ByteSize::b(rates.bytes_written as u64).to_string_as(true),
+        ByteSize::b(rates.bytes_read as u64).to_string_as(true)
+    );
+    if let Some((p50, p99)) = interval_rtt_percentiles(th, sigfig) {
+        line.push_str(&format!(
+            ", p50: {}, p99: {}",
+            util::format_duration(p50.into()),
+            util::format_duration(p99.into())
+        ));
+    }
+    line
+}
+
+/// The median and 99th percentile round trip times of the report interval, unless no
+/// request completed during it
+fn interval_rtt_percentiles(th: &[Arc<RwLock<WorkerStats>>], sigfig: u8) -> Option<(u64, u64)> {
+    let mut hist = Histogram::<u64>::new(sigfig).unwrap();
+    for s in th {
+        hist.add(s.blocking_write().take_interval_rtt()).unwrap();
+    }
+    (!hist.is_empty()).then(|| (hist.value_at_quantile(0.5), hist.value_at_quantile(0.99)))
+}
+
+/// The minimum number of completed requests before the error rate is considered
+const MIN_ERROR_RATE_SAMPLE: usize = 100;
+
+fn sum_errors(th: &[Arc<RwLock<WorkerStats>>]) -> usize {
+    th.iter()
+        .map(|s| s.blocking_read().run_stats.errors.values().sum::<usize>())
+        .sum()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn exceeds_error_rate(successes: &BigInt, errors: usize, threshold: f64) -> bool {
+    let successes = successes.to_f64().unwrap_or(f64::MAX);
+    let errors = errors as f64;
+    let total = successes + errors;
+    total >= MIN_ERROR_RATE_SAMPLE as f64 && errors / total * 100.0 > threshold
 }
 
 fn sum_instant_stats(
@@ -197,19 +984,11 @@ fn sum_instant_stats(
 ) -> InstantStats {
     let mut stats = vec![];
     for (a, b) in zip(th, curr) {
-        let guard = a.blocking_read();
-        let changed = guard.instant_stats.changed(b);
-        b.requests_issued = guard.instant_stats.requests_issued;
-        b.bytes_written = guard.instant_stats.bytes_written;
-        b.bytes_read = guard.instant_stats.bytes_read;
-
-        drop(guard);
-
-        stats.push(InstantStats {
-            requests_issued: changed.requests_issued,
-            bytes_written: changed.bytes_written,
-            bytes_read: changed.bytes_read,
-        });
+        // Snapshot the counters under a single read so they're consistent with each
+        // other, as they're updated together under the write lock
+        let snapshot = a.blocking_read().instant_stats;
+        stats.push(snapshot.changed(b));
+        *b = snapshot;
     }
 
     stats.iter().fold(InstantStats::default(), |mut acc, curr| {
@@ -220,23 +999,21 @@ fn sum_instant_stats(
     })
 }
 
-fn summarize_worker_stats(th: &[Arc<RwLock<WorkerStats>>]) -> Result<RunStats> {
-    th.iter().try_fold(RunStats::default(), |mut acc, curr| {
-        let guard = curr.blocking_read();
-        acc.rtt_latency_hist
-            .add(&guard.run_stats.rtt_latency_hist)?;
-        acc.ttfb_latency_hist
-            .add(&guard.run_stats.ttfb_latency_hist)?;
-        guard.run_stats.errors.iter().for_each(|(k, v)| {
-            acc.errors
-                .entry(*k)
-                .and_modify(|val| *val += *v)
-                .or_insert(*v);
-        });
+fn summarize_worker_stats(th: &[Arc<RwLock<WorkerStats>>], sigfig: u8) -> Result<RunStats> {
+    th.iter().try_fold(RunStats::new(sigfig), |mut acc, curr| {
+        acc.merge(&curr.blocking_read().run_stats)?;
         Ok(acc)
     })
 }
 
+/// The number of requests each connection of every worker completed
+fn get_connection_requests(infos: &[WorkerInfo]) -> Vec<usize> {
+    infos
+        .iter()
+        .flat_map(|info| info.run_infos.iter().map(|run| run.requests))
+        .collect()
+}
+
 fn get_total_runtime(infos: Vec<WorkerInfo>) -> u128 {
     let mut earliest_start = Instant::now();
     let mut latest_end = Instant::now();
@@ -260,3 +1037,48 @@ fn get_total_runtime(infos: Vec<WorkerInfo>) -> u128 {
     let i1 = latest_end.duration_since(earliest_start).as_nanos();
     i1
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{Loaded, LoadedCmd};
+    use crate::cmd::run::validate;
+    use clap::Parser;
+
+    fn validate_s3(s3_args: &[&str]) -> anyhow::Result<()> {
+        let run_args = [
+            "loaded",
+            "run",
+            "-u",
+            "http://localhost:9000",
+            "-c",
+            "1",
+            "-t",
+            "1",
+        ];
+        let s3 = ["s3", "-b", "bucket", "--object-size", "1024"];
+        let args = run_args.iter().chain(&s3).chain(s3_args);
+        let LoadedCmd::Run(args) = Loaded::try_parse_from(args).unwrap().loaded else {
+            panic!("not a run");
+        };
+        validate(&args)
+    }
+
+    #[test]
+    fn rejects_chunked_trailing_checksums() {
+        let trailer = [
+            "--checksum-mode",
+            "trailer",
+            "--checksum-algorithm",
+            "crc32",
+        ];
+        validate_s3(&["--chunked"]).unwrap();
+        validate_s3(&trailer).unwrap();
+        for trailer in [&trailer[..], &["--unique-payloads"]] {
+            let e = validate_s3(&[&["--chunked"], trailer].concat()).unwrap_err();
+            assert!(
+                e.to_string().starts_with("--chunked can't be combined"),
+                "{e}"
+            );
+        }
+    }
+}
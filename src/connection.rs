@@ -5,26 +5,55 @@
 //! formed by calling engine methods that define what requests are sent and what is to
 //! be done with the response.
 
+use crate::cli::{BackoffJitter, ConnectionMode, IpVersion};
+use crate::connection::arrival::Schedule;
+use crate::connection::backoff::Backoff;
+use crate::connection::body::{RequestBody, RequestSent, ResponseBody};
+use crate::connection::dns::DnsCache;
+use crate::connection::fault::FaultInjector;
+use crate::connection::latency::LatencyDistribution;
 use crate::connection::lifecycle::{ConnectionHttpLifecycle, ConnectionLifecycle};
+use crate::connection::proxy::Proxy;
 use crate::engine::Engine;
+use crate::logging;
+use crate::stats::ErrorSample;
+use crate::util;
 use anyhow::{anyhow, Result};
+use http_body_util::BodyExt;
 use hyper::body::Body;
-use hyper::{Request, Uri};
+use hyper::body::Incoming;
+use hyper::client::conn::http1::SendRequest;
+use hyper::http::request;
+use hyper::{Request, Response, Uri, Version};
 use hyper_util::rt::TokioIo;
-use log::{error, info, trace};
+use log::{debug, error, info, trace, warn};
+use socket2::{Domain, Protocol, Socket, Type};
 use std::error::Error;
-use std::rc::Rc;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::TcpStream;
+use tokio::net::{TcpSocket, TcpStream};
 use tokio::sync::Barrier;
 use tokio::time::Instant;
 
+pub mod arrival;
+pub mod backoff;
+pub mod body;
 pub mod completion;
+pub mod concurrency;
+pub mod decompress;
+pub mod dns;
+pub mod fault;
+pub mod latency;
 pub mod lifecycle;
+pub mod open_loop;
+pub mod proxy;
+pub mod ramp;
 pub mod rate_limit;
+pub mod sampling;
 pub mod stats;
 
 pub struct Connection {
@@ -33,18 +62,27 @@ pub struct Connection {
     pub run_flag: RunFlag,
     pub setup_barrier: Arc<Barrier>,
     pub lifecycle_listeners: Vec<ConnectionHttpLifecycle>,
+    pub options: ConnectionOptions,
+    /// Seed of the randomness of the connection, e.g. the faults it injects
+    pub seed: String,
+    /// The backoff between failed attempts to connect, see [`ConnectionOptions::backoff`]
+    pub backoff: Backoff,
+    /// Addresses the server resolved to, shared with the other connections of the worker
+    pub dns_cache: Arc<DnsCache>,
 }
 
 pub struct ConnectionRunInfo {
     pub start_time: Instant,
     pub end_time: Instant,
+    /// Number of requests the connection completed, whatever their status
+    pub requests: usize,
 }
 
 impl Connection {
     pub async fn run<E, Req>(&mut self, engine: &mut E, url: &Uri) -> Result<ConnectionRunInfo>
     where
-        E: Engine<Req>,
-        Req: Body + Send + 'static,
+        E: Engine<Req> + Send,
+        Req: Body + Send + Sync + 'static,
         Req::Data: Send,
         Req::Error: Into<Box<dyn Error + Send + Sync>>,
     {
@@ -54,34 +92,65 @@ impl Connection {
             self.parent_worker_id,
             self.id
         );
-        engine.setup().await?;
+        let warm_sender = self.set_up(engine, url).await?;
 
-        self.setup_barrier.wait().await;
+        if let Some(iterations) = self.options.dry_run {
+            return self.dry_run(engine, url, iterations).await;
+        }
 
-        for l in &mut self.lifecycle_listeners {
-            l.after_setup().await;
+        if let Some(schedule) = self.options.schedule {
+            return self.run_open_loop(engine, url, schedule, warm_sender).await;
         }
 
-        let host = url.host().expect("uri has no host");
-        let port = url.port_u16().unwrap_or(80);
-        let address = format!("{host}:{port}");
+        let mut sender = match warm_sender {
+            Some(sender) => Some(sender),
+            None => self.connect_backing_off(url, false).await?,
+        };
+        let start_time = Instant::now();
+        let result = self.closed_loop(engine, url, &mut sender).await;
+        let end_time = Instant::now();
 
-        let stream = TcpStream::connect(address).await?;
-        let io = TokioIo::new(stream);
+        // However the loop came to an end, what the connection recorded up to then is
+        // part of the run
+        for l in &mut self.lifecycle_listeners {
+            l.after_run().await;
+        }
+        let requests = result?;
 
-        let (mut sender, conn) = hyper::client::conn::http1::handshake(io).await.unwrap();
+        info!("Cleaning up {} engine ({})", engine.name(), self.id);
+        self.issue_cleanup_requests(engine, url, sender).await?;
+        engine.cleanup().await?;
 
-        tokio::task::spawn_local(async move {
-            if let Err(err) = conn.await {
-                error!("Connection failed: {:?}", err);
-            }
-        });
+        Ok(ConnectionRunInfo {
+            start_time,
+            end_time,
+            requests,
+        })
+    }
 
-        let start_time = Instant::now();
-        let authority = url.authority().unwrap().clone();
+    /// Issues requests one after the other until the run comes to an end, returning the
+    /// number that completed
+    async fn closed_loop<E, Req>(
+        &mut self,
+        engine: &mut E,
+        url: &Uri,
+        sender: &mut Option<SendRequest<RequestBody<Req>>>,
+    ) -> Result<usize>
+    where
+        E: Engine<Req> + Send,
+        Req: Body + Send + Sync + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let mut requests_on_connection = 0;
+        let mut requests = 0;
+        let mut faults = self
+            .options
+            .fault_drop_rate
+            .map(|rate| FaultInjector::new(rate, util::seeded_rng(&self.seed)));
 
         'run: loop {
-            if !self.run_flag.should_run() {
+            if !self.run_flag.should_run() || engine.finished() {
                 break;
             }
 
@@ -91,63 +160,703 @@ impl Connection {
                 }
             }
 
-            // Create an HTTP request with an empty body and a HOST header
-            let builder = Request::builder()
-                .uri(url)
-                .header(hyper::header::HOST, authority.as_str());
+            if sender.as_ref().is_some_and(SendRequest::is_closed) {
+                // The server closed the keep-alive connection
+                *sender = self.reconnect(url).await;
+                requests_on_connection = 0;
+            }
+            let request_sender = match sender {
+                Some(s) => s,
+                None => match self.connect_backing_off(url, false).await? {
+                    Some(s) => sender.insert(s),
+                    // Reconnecting or backing off was cut short by the end of the run
+                    None => continue,
+                },
+            };
+
+            // Taken once connected, connecting isn't part of the latency of the request
+            let start = Instant::now();
 
-            let (req, req_len) = engine.request(builder).await?;
+            let (req, req_len) = engine.request(self.request_builder(url)).await?;
 
             for l in &mut self.lifecycle_listeners {
-                l.before_request(&req, req_len).await;
+                l.before_request(&req, req_len, start).await;
             }
 
             trace!("Sending request {} - {} ", req.method(), req.uri());
             let method = req.method().clone();
             let uri = req.uri().clone();
-            let timeout_duration = Duration::from_secs(30);
-            let mut resp = tokio::time::timeout(timeout_duration, sender.send_request(req))
-                .await
-                .map_err(|_| {
-                    anyhow!(
-                        "{} request to {} timed out after {}",
-                        method,
-                        uri,
-                        crate::util::format_duration(timeout_duration.as_nanos())
-                    )
-                })??;
+            let sent = send_with_timeout(request_sender, req, self.options.absolute_uri);
+            let resp = match logging::request(&uri, sent).await? {
+                Ok(resp) => resp,
+                Err(e) => {
+                    // The connection can't be used after a failed exchange, e.g. the server
+                    // closing it mid request, so replace it and carry on with the run
+                    warn!("{method} request to {uri} failed, reconnecting: {e}");
+                    *sender = self.reconnect(url).await;
+                    requests_on_connection = 0;
+                    continue;
+                }
+            };
 
-            for l in &mut self.lifecycle_listeners {
-                l.after_request().await;
-            }
+            let reusable = logging::request(&uri, self.handle_response(engine, resp)).await?;
+            requests += 1;
 
-            let len = engine.response(&mut resp).await?;
+            if faults.as_mut().is_some_and(FaultInjector::should_drop) {
+                debug!("Injecting fault, dropping the connection to {uri}");
+                sender.take();
+                *sender = self.reconnect(url).await;
+                requests_on_connection = 0;
+                continue;
+            }
 
-            for l in &mut self.lifecycle_listeners {
-                l.after_response(&resp, len).await;
+            requests_on_connection += 1;
+            if !reusable
+                || self
+                    .options
+                    .max_requests_per_connection()
+                    .is_some_and(|max| requests_on_connection >= max)
+            {
+                // Drop the sender to tear down the connection, the next request will
+                // establish a fresh one
+                sender.take();
+                requests_on_connection = 0;
             }
         }
 
+        Ok(requests)
+    }
+
+    /// Creates an HTTP request builder with a HOST header and the configured authorization
+    fn request_builder(&self, url: &Uri) -> request::Builder {
+        let authority = url.authority().unwrap();
+        let mut builder = Request::builder()
+            .uri(url)
+            .header(hyper::header::HOST, authority.as_str());
+        if self.options.http10 {
+            builder = builder.version(Version::HTTP_10);
+        }
+        if self.options.connection_close {
+            builder = builder.header(hyper::header::CONNECTION, "close");
+        }
+        if self.options.decompress {
+            builder = builder.header(hyper::header::ACCEPT_ENCODING, "gzip, deflate");
+        }
+        match &self.options.authorization {
+            Some(auth) => builder.header(hyper::header::AUTHORIZATION, auth.as_str()),
+            None => builder,
+        }
+    }
+
+    /// Prints the requests the engine builds for the given number of iterations
+    /// without ever connecting to the server
+    async fn dry_run<E, Req>(
+        &mut self,
+        engine: &mut E,
+        url: &Uri,
+        iterations: usize,
+    ) -> Result<ConnectionRunInfo>
+    where
+        E: Engine<Req> + Send,
+        Req: Body + Send + Sync + 'static,
+    {
+        let start_time = Instant::now();
+        for i in 0..iterations {
+            if engine.finished() {
+                break;
+            }
+            let (req, req_len) = engine.request(self.request_builder(url)).await?;
+            let req = with_request_target(req, self.options.absolute_uri);
+            let mut out = format!(
+                "Request {i} (worker {}, connection {}): {} {} {:?} ({req_len} bytes)\n",
+                self.parent_worker_id,
+                self.id,
+                req.method(),
+                req.uri(),
+                req.version()
+            );
+            for (name, value) in req.headers() {
+                let value = match value.to_str() {
+                    _ if value.is_sensitive() => "<sensitive>",
+                    Ok(value) => value,
+                    Err(_) => "<binary>",
+                };
+                out.push_str(&format!("\t{name}: {value}\n"));
+            }
+            print!("{out}");
+        }
         let end_time = Instant::now();
 
-        info!("Cleaning up {} engine ({})", engine.name(), self.id);
         engine.cleanup().await?;
 
         Ok(ConnectionRunInfo {
             start_time,
             end_time,
+            requests: 0,
         })
     }
+
+    /// Hands the response over to the engine, notifying the lifecycle listeners as
+    /// it is received
+    ///
+    /// Returns whether the connection can be reused, which it can't once the body
+    /// was cut short by the read limit
+    async fn handle_response<E, Req>(
+        &mut self,
+        engine: &mut E,
+        resp: Response<Incoming>,
+    ) -> Result<bool>
+    where
+        E: Engine<Req> + Send,
+        Req: Body,
+    {
+        if let Some(RequestSent(sent)) = resp.extensions().get::<RequestSent>().copied() {
+            for l in &mut self.lifecycle_listeners {
+                l.after_send(sent).await;
+            }
+        }
+        for l in &mut self.lifecycle_listeners {
+            l.after_request().await;
+        }
+
+        let mut resp = ResponseBody::wrap(resp, self.options.read_limit, self.options.decompress);
+        resp.body_mut().first_frame().await;
+
+        for l in &mut self.lifecycle_listeners {
+            l.after_first_byte().await;
+        }
+
+        respond(engine, &mut resp, &mut self.lifecycle_listeners).await?;
+        // The rest of a truncated body is left unread on the connection
+        Ok(!resp.body().is_truncated())
+    }
+
+    /// Sets up the engine and waits for every other connection to be set up too,
+    /// returning the warmed up connection if there's any
+    async fn set_up<E, Req>(
+        &mut self,
+        engine: &mut E,
+        url: &Uri,
+    ) -> Result<Option<SendRequest<RequestBody<Req>>>>
+    where
+        E: Engine<Req> + Send,
+        Req: Body + Send + Sync + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        engine.setup().await?;
+
+        // Warming up ahead of the barrier keeps the handshakes of every connection out
+        // of the measured window
+        let warm_sender = match self.options.warmup {
+            Some(warmup) if self.options.dry_run.is_none() => {
+                self.warm_up(engine, url, warmup).await?
+            }
+            _ => None,
+        };
+
+        self.setup_barrier.wait().await;
+
+        for l in &mut self.lifecycle_listeners {
+            l.after_setup().await;
+        }
+        Ok(warm_sender)
+    }
+
+    /// Establishes the connection ahead of the run, also issuing the warmup request of
+    /// the engine over it for [`Warmup::Request`], whose response is discarded
+    ///
+    /// Returns `None` if the run ends before the connection could be established.
+    async fn warm_up<E, Req>(
+        &mut self,
+        engine: &mut E,
+        url: &Uri,
+        warmup: Warmup,
+    ) -> Result<Option<SendRequest<RequestBody<Req>>>>
+    where
+        E: Engine<Req> + Send,
+        Req: Body + Send + Sync + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let Some(mut sender) = self.connect_backing_off(url, false).await? else {
+            return Ok(None);
+        };
+        if warmup == Warmup::Connect {
+            return Ok(Some(sender));
+        }
+        if let Some(req) = engine.warmup_request(self.request_builder(url)).await? {
+            // The warmup request is only there to exercise the connection, so failing it
+            // merely means replacing the connection rather than failing the run
+            match send_with_timeout(&mut sender, req, self.options.absolute_uri).await? {
+                Ok(resp) => {
+                    trace!("Warmup request responded with {}", resp.status());
+                    if let Err(e) = resp.into_body().collect().await {
+                        warn!("Failed to read the response to the warmup request: {e}");
+                    }
+                }
+                Err(e) => warn!("Warmup request failed: {e}"),
+            }
+            if sender.is_closed() {
+                return self.connect_backing_off(url, false).await;
+            }
+        }
+        Ok(Some(sender))
+    }
+
+    /// Replaces a dropped connection, backing off between failed attempts
+    ///
+    /// Keeps trying for as long as the run goes on, returning `None` if it ends first
+    async fn reconnect<Req>(&mut self, url: &Uri) -> Option<SendRequest<RequestBody<Req>>>
+    where
+        Req: Body + Send + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        self.backoff.reset();
+        while self.run_flag.should_run() {
+            match self.connect(url, true).await {
+                Ok(sender) => return Some(sender),
+                Err(e) if is_port_exhaustion(&e) => {
+                    self.back_off_port_exhaustion(&e).await;
+                }
+                Err(e) => {
+                    let delay = self.backoff.next();
+                    warn!("Failed to reconnect, retrying in {delay:?}: {e}");
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+        None
+    }
+
+    /// Establishes a new connection, backing off and trying again for as long as the run
+    /// goes on whenever the local ports ran out, which passes once those of the connections
+    /// that were torn down are freed (e.g. connecting per request)
+    ///
+    /// `reconnect` marks the connection as replacing one that was dropped. Returns `None`
+    /// if the run ends first.
+    async fn connect_backing_off<Req>(
+        &mut self,
+        url: &Uri,
+        reconnect: bool,
+    ) -> Result<Option<SendRequest<RequestBody<Req>>>>
+    where
+        Req: Body + Send + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        self.backoff.reset();
+        while self.run_flag.should_run() {
+            match self.connect(url, reconnect).await {
+                Ok(sender) => return Ok(Some(sender)),
+                Err(e) if is_port_exhaustion(&e) => {
+                    self.back_off_port_exhaustion(&e).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Notes the local ports ran out before waiting for some of them to be freed
+    async fn back_off_port_exhaustion(&mut self, e: &anyhow::Error) {
+        let delay = self.backoff.next();
+        for l in &mut self.lifecycle_listeners {
+            l.after_port_exhaustion().await;
+        }
+        warn!("Ran out of local ports to connect from, retrying in {delay:?}: {e}");
+        tokio::time::sleep(delay).await;
+    }
+
+    /// Establishes a new tcp connection to the server and performs the http handshake
+    /// over it, returning the sender to issue requests with
+    ///
+    /// `reconnect` marks the connection as replacing one that was dropped
+    async fn connect<Req>(
+        &mut self,
+        url: &Uri,
+        reconnect: bool,
+    ) -> Result<SendRequest<RequestBody<Req>>>
+    where
+        Req: Body + Send + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        for l in &mut self.lifecycle_listeners {
+            l.before_connect().await;
+        }
+
+        let (address, resolution_time) = self.resolve(url).await?;
+        if let Some(resolution_time) = resolution_time {
+            for l in &mut self.lifecycle_listeners {
+                l.after_resolve(resolution_time).await;
+            }
+        }
+
+        let sender = self.open(url, address).await?;
+
+        for l in &mut self.lifecycle_listeners {
+            l.after_connect(&address, reconnect).await;
+        }
+
+        Ok(sender)
+    }
+
+    /// Resolves the address to connect to through the DNS cache of the worker
+    ///
+    /// Returns how long the lookup took when the address wasn't cached
+    async fn resolve(&self, url: &Uri) -> Result<(SocketAddr, Option<Duration>)> {
+        // When tunneling through a proxy, it's the proxy we connect to
+        let authority = match &self.options.proxy {
+            Some(proxy) => proxy.authority.clone(),
+            None => target(url),
+        };
+        self.dns_cache.resolve(&authority).await
+    }
+
+    /// Opens a connection to the server at `address` without notifying the lifecycle
+    /// listeners, returning the sender to issue requests with
+    async fn open<Req>(
+        &self,
+        url: &Uri,
+        address: SocketAddr,
+    ) -> Result<SendRequest<RequestBody<Req>>>
+    where
+        Req: Body + Send + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        // Offset by the worker so single connection workers don't all bind the same address
+        let mut stream = self
+            .options
+            .socket
+            .connect(address, self.parent_worker_id + self.id)
+            .await?;
+        if let Some(proxy) = &self.options.proxy {
+            proxy.tunnel(&mut stream, &target(url)).await?;
+        }
+        let io = TokioIo::new(stream);
+
+        let (sender, conn) = hyper::client::conn::http1::handshake(io).await?;
+
+        tokio::spawn(logging::inherit(async move {
+            if let Err(err) = conn.await {
+                error!("Connection failed: {:?}", err);
+            }
+        }));
+
+        Ok(sender)
+    }
+
+    /// Issues the requests the engine needs to clean up after the run, these aren't
+    /// part of the workload so they bypass the lifecycle listeners and its stats
+    async fn issue_cleanup_requests<E, Req>(
+        &self,
+        engine: &mut E,
+        url: &Uri,
+        mut sender: Option<SendRequest<RequestBody<Req>>>,
+    ) -> Result<()>
+    where
+        E: Engine<Req> + Send,
+        Req: Body + Send + Sync + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        while let Some(req) = engine.cleanup_request(self.request_builder(url)).await? {
+            let method = req.method().clone();
+            let uri = req.uri().clone();
+            if sender.as_ref().map_or(true, SendRequest::is_closed) {
+                let (address, _) = self.resolve(url).await?;
+                sender = Some(self.open(url, address).await?);
+            }
+            let request_sender = sender.as_mut().unwrap();
+            match send_with_timeout(request_sender, req, self.options.absolute_uri).await? {
+                Ok(resp) => {
+                    let status = resp.status();
+                    resp.into_body().collect().await?;
+                    if !status.is_success() {
+                        warn!("Cleanup {method} request to {uri} failed: {status}");
+                    }
+                }
+                Err(e) => {
+                    warn!("Cleanup {method} request to {uri} failed: {e}");
+                    sender = None;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether connecting failed as the local ephemeral ports ran out, either to connect
+/// from or to bind to
+fn is_port_exhaustion(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<io::Error>().is_some_and(|e| {
+        matches!(
+            e.kind(),
+            io::ErrorKind::AddrNotAvailable | io::ErrorKind::AddrInUse
+        )
+    })
+}
+
+/// The `host:port` of the server the url points at
+fn target(url: &Uri) -> String {
+    let host = url.host().expect("uri has no host");
+    let port = url.port_u16().unwrap_or(80);
+    format!("{host}:{port}")
+}
+
+/// Sets the target of the request line, which hyper sends as the uri of the request is
+///
+/// Requests are built with the full url, only its path and query (the origin form) are
+/// sent unless the absolute form is asked for, the host being in the `Host` header.
+fn with_request_target<Req>(mut req: Request<Req>, absolute_uri: bool) -> Request<Req> {
+    if absolute_uri {
+        return req;
+    }
+    // The path of a url without one is `/` rather than empty
+    let origin = match req.uri().query() {
+        Some(query) => format!("{}?{query}", req.uri().path()),
+        None => req.uri().path().to_string(),
+    };
+    if let Ok(origin) = Uri::try_from(origin) {
+        *req.uri_mut() = origin;
+    }
+    req
+}
+
+/// Hands the response over to the engine and then to the listeners, returning the
+/// length the engine read
+///
+/// The listeners record the response even when the engine fails the run on it, the
+/// start of the body the server sent being kept with the error too.
+async fn respond<E, Req>(
+    engine: &mut E,
+    resp: &mut Response<ResponseBody>,
+    listeners: &mut [ConnectionHttpLifecycle],
+) -> Result<usize>
+where
+    E: Engine<Req> + Send,
+    Req: Body,
+{
+    let len = engine.response(resp).await;
+    if let Some(decompressed) = resp.body_mut().finish_decompression() {
+        resp.extensions_mut().insert(decompressed);
+    }
+    let sample = resp.body_mut().error_sample();
+    if let Some(sample) = &sample {
+        resp.extensions_mut().insert(sample.clone());
+    }
+
+    let len = match len {
+        Ok(len) => len,
+        Err(e) => {
+            for l in listeners {
+                l.after_response(resp, 0).await;
+            }
+            return Err(match sample {
+                Some(ErrorSample(sample)) => anyhow!("{e:#}: {sample}"),
+                None => e,
+            });
+        }
+    };
+    for l in listeners {
+        l.after_response(resp, len).await;
+    }
+    Ok(len)
+}
+
+/// Sends the request, failing the run if the server doesn't respond in time
+///
+/// The response carries when the request had been sent in full as a [`RequestSent`].
+async fn send_with_timeout<Req>(
+    sender: &mut SendRequest<RequestBody<Req>>,
+    req: Request<Req>,
+    absolute_uri: bool,
+) -> Result<hyper::Result<Response<Incoming>>>
+where
+    Req: Body + 'static,
+{
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let (req, sent) = RequestBody::wrap(with_request_target(req, absolute_uri));
+    let timeout_duration = Duration::from_secs(30);
+    let resp = tokio::time::timeout(timeout_duration, sender.send_request(req))
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "{} request to {} timed out after {}",
+                method,
+                uri,
+                util::format_duration(timeout_duration.as_nanos())
+            )
+        })?;
+    Ok(resp.map(|mut resp| {
+        if let Some(sent) = sent.get() {
+            resp.extensions_mut().insert(RequestSent(*sent));
+        }
+        resp
+    }))
+}
+
+/// Options that control how a [Connection] manages its underlying tcp connection
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ConnectionOptions {
+    pub mode: ConnectionMode,
+    /// Replaces reused connections after this many requests
+    pub max_requests: Option<usize>,
+    /// Launches requests on this schedule without waiting for the previous ones to
+    /// complete, running an open loop over as many connections as it takes
+    pub schedule: Option<Schedule>,
+    /// Issues requests as HTTP/1.0
+    pub http10: bool,
+    /// Sends `Connection: close` with every request
+    pub connection_close: bool,
+    /// Sends the full url as the target of the request line rather than only its path
+    pub absolute_uri: bool,
+    /// Value of the `Authorization` header to attach to every request
+    pub authorization: Option<String>,
+    pub socket: SocketOptions,
+    /// Prints this many requests rather than sending them to the server
+    pub dry_run: Option<usize>,
+    /// How long to wait after the first failed attempt to replace a dropped connection,
+    /// doubling with every subsequent failure
+    pub reconnect_backoff: Duration,
+    /// How the backoff between attempts to reconnect is randomized
+    pub backoff_jitter: BackoffJitter,
+    /// Drops the connection after this percentage of responses to exercise reconnecting
+    pub fault_drop_rate: Option<f64>,
+    /// Delays every request by a latency drawn from the distribution
+    pub inject_latency: Option<LatencyDistribution>,
+    /// Stops reading response bodies after this many bytes, dropping the connection
+    pub read_limit: Option<usize>,
+    /// Decompresses gzip and deflate response bodies to count the bytes they come down to
+    pub decompress: bool,
+    /// Records the latencies and sizes of only this percentage of the requests
+    pub sample_rate: Option<f64>,
+    /// Tunnels connections to the server through this HTTP proxy
+    pub proxy: Option<Proxy>,
+    /// How long resolved addresses are cached for
+    pub dns_cache_ttl: Duration,
+    /// Warms up the connections before the run starts
+    pub warmup: Option<Warmup>,
+}
+
+/// How connections are warmed up before the run starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warmup {
+    /// Establishes the connection
+    Connect,
+    /// Establishes the connection and issues a no-op request over it
+    Request,
+}
+
+impl ConnectionOptions {
+    /// The backoff between failed attempts to connect of the connection with the given
+    /// seed, its jitter drawn apart from the other randomness of the connection
+    pub fn backoff(&self, seed: &str) -> Backoff {
+        Backoff::new(
+            self.reconnect_backoff,
+            MAX_RECONNECT_BACKOFF,
+            self.backoff_jitter,
+            util::seeded_rng(&format!("{seed}-backoff")),
+        )
+    }
+
+    /// The number of requests to issue over a connection before replacing it, if any
+    fn max_requests_per_connection(&self) -> Option<usize> {
+        if self.http10 || self.connection_close {
+            // The server closes the connection after every response
+            return Some(1);
+        }
+        match self.mode {
+            ConnectionMode::PerRequest => Some(1),
+            ConnectionMode::Reuse => self.max_requests,
+        }
+    }
+}
+
+/// The longest to wait in between attempts to replace a dropped connection
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Options applied to the underlying socket before it is connected
+#[derive(Debug, Clone, Default)]
+pub struct SocketOptions {
+    pub nodelay: bool,
+    pub reuse_port: bool,
+    pub send_buffer: Option<usize>,
+    pub recv_buffer: Option<usize>,
+    /// Restricts the addresses the host resolves to, to those of the IP version
+    pub ip_version: IpVersion,
+    /// Local addresses the connections are bound to, in round robin order
+    pub bind_addresses: Vec<IpAddr>,
+}
+
+impl SocketOptions {
+    /// Connects to `address`, the `connection`th connection being bound to its share
+    /// of the local addresses, if there are any
+    async fn connect(&self, address: SocketAddr, connection: usize) -> Result<TcpStream> {
+        let socket = Socket::new(
+            Domain::for_address(address),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        socket.set_nonblocking(true)?;
+        socket.set_nodelay(self.nodelay)?;
+        if self.reuse_port {
+            socket.set_reuse_port(true)?;
+        }
+        if let Some(size) = self.send_buffer {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(local) = self.bind_address(address, connection)? {
+            match socket.bind(&SocketAddr::new(local, 0).into()) {
+                // Unlike the local ports running out, which binding reports as the address
+                // being in use, retrying won't make the address a local one
+                Err(e) if e.kind() == io::ErrorKind::AddrNotAvailable => {
+                    return Err(anyhow!("Failed to bind to {local}: {e}"));
+                }
+                result => result?,
+            }
+        }
+
+        let socket = TcpSocket::from_std_stream(socket.into());
+        Ok(socket.connect(address).await?)
+    }
+
+    /// The local address the `connection`th connection to `address` is bound to, taking
+    /// turns among those of the same IP version
+    fn bind_address(&self, address: SocketAddr, connection: usize) -> Result<Option<IpAddr>> {
+        if self.bind_addresses.is_empty() {
+            return Ok(None);
+        }
+        let candidates = self
+            .bind_addresses
+            .iter()
+            .filter(|local| local.is_ipv4() == address.is_ipv4())
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Err(anyhow!(
+                "None of the bind addresses are of the IP version of {address}"
+            ));
+        }
+        Ok(Some(*candidates[connection % candidates.len()]))
+    }
 }
 
 #[derive(Debug)]
 pub struct RunFlag {
     global_run: Arc<AtomicBool>,
-    local_run: Rc<AtomicBool>,
+    local_run: Arc<AtomicBool>,
 }
 
 impl RunFlag {
-    pub fn new(global_run: Arc<AtomicBool>, local_run: Rc<AtomicBool>) -> Self {
+    pub fn new(global_run: Arc<AtomicBool>, local_run: Arc<AtomicBool>) -> Self {
         RunFlag {
             global_run,
             local_run,
@@ -158,3 +867,78 @@ impl RunFlag {
         self.global_run.load(Relaxed) && self.local_run.load(Relaxed)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::{is_port_exhaustion, with_request_target, SocketOptions};
+    use hyper::Request;
+    use std::io;
+    use std::net::{IpAddr, SocketAddr};
+
+    #[test]
+    fn binds_addresses_in_turn() {
+        let local = |s: &str| s.parse::<IpAddr>().unwrap();
+        let options = SocketOptions {
+            bind_addresses: vec![local("10.0.0.1"), local("fd00::1"), local("10.0.0.2")],
+            ..SocketOptions::default()
+        };
+        let v4 = "192.168.1.10:80".parse::<SocketAddr>().unwrap();
+        let bound = (0..4)
+            .map(|i| options.bind_address(v4, i).unwrap().unwrap())
+            .collect::<Vec<_>>();
+        let expected = ["10.0.0.1", "10.0.0.2", "10.0.0.1", "10.0.0.2"].map(local);
+        assert_eq!(bound, expected);
+
+        let v6 = "[fd00::10]:80".parse::<SocketAddr>().unwrap();
+        assert_eq!(options.bind_address(v6, 1).unwrap(), Some(local("fd00::1")));
+
+        let v4_only = SocketOptions {
+            bind_addresses: vec![local("10.0.0.1")],
+            ..SocketOptions::default()
+        };
+        assert!(v4_only.bind_address(v6, 0).is_err());
+        assert_eq!(SocketOptions::default().bind_address(v4, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn detects_port_exhaustion() {
+        let error = |kind| anyhow::Error::from(io::Error::from(kind));
+        assert!(is_port_exhaustion(&error(io::ErrorKind::AddrNotAvailable)));
+        assert!(is_port_exhaustion(&error(io::ErrorKind::AddrInUse)));
+        assert!(!is_port_exhaustion(&error(
+            io::ErrorKind::ConnectionRefused
+        )));
+        assert!(!is_port_exhaustion(&anyhow::anyhow!("handshake failed")));
+    }
+
+    #[test]
+    fn sends_the_origin_form_unless_asked_for_the_absolute_one() {
+        let target = |url: &str, absolute_uri| {
+            let req = Request::builder().uri(url).body(()).unwrap();
+            with_request_target(req, absolute_uri).uri().to_string()
+        };
+        assert_eq!(target("http://localhost:9000/a/b?c=d", false), "/a/b?c=d");
+        assert_eq!(target("http://localhost:9000", false), "/");
+        assert_eq!(
+            target("http://localhost:9000/a/b?c=d", true),
+            "http://localhost:9000/a/b?c=d"
+        );
+    }
+
+    #[test]
+    fn fails_to_bind_addresses_that_are_not_local() {
+        let options = SocketOptions {
+            // Reserved for documentation, never assigned to an interface
+            bind_addresses: vec!["192.0.2.1".parse().unwrap()],
+            ..SocketOptions::default()
+        };
+        let server = "127.0.0.1:9".parse().unwrap();
+        let e = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(options.connect(server, 0))
+            .unwrap_err();
+        assert!(!is_port_exhaustion(&e), "{e}");
+    }
+}
@@ -0,0 +1,153 @@
+use crate::util;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+/// The schedule an open loop launches the requests of a connection on, regardless of
+/// whether the earlier ones have completed
+#[derive(Debug, Clone, Copy)]
+pub enum Schedule {
+    /// Evenly spaced launches at this interval
+    Fixed(Duration),
+    /// Launches following a Poisson process of this mean rate (per second)
+    Poisson(f64),
+}
+
+/// The arrivals of an open loop following its [`Schedule`]
+pub enum Arrivals {
+    Fixed(FixedArrivals),
+    Poisson(Box<PoissonArrivals>),
+}
+
+impl Arrivals {
+    /// The offset of fixed arrivals and the gaps between Poisson arrivals are derived
+    /// from the seed
+    pub fn new(schedule: Schedule, seed: &str) -> Self {
+        match schedule {
+            Schedule::Fixed(interval) => {
+                // Stagger the connections so their launches don't all line up
+                let offset = interval.mul_f64(util::seeded_rng(seed).gen::<f64>());
+                Arrivals::Fixed(FixedArrivals::new(interval, offset))
+            }
+            Schedule::Poisson(rate) => Arrivals::Poisson(Box::new(PoissonArrivals::new(
+                rate,
+                util::seeded_rng(&format!("{seed}-arrivals")),
+            ))),
+        }
+    }
+
+    /// Waits for the next arrival, returning the time it was scheduled at
+    pub async fn wait(&mut self) -> Instant {
+        match self {
+            Arrivals::Fixed(arrivals) => arrivals.wait().await,
+            Arrivals::Poisson(arrivals) => arrivals.wait().await,
+        }
+    }
+}
+
+/// An open-model arrival process where the gaps between requests are
+/// exponentially distributed, yielding a Poisson process of the given rate.
+///
+/// Launched by an open loop, the schedule is independent of how long the server
+/// takes to respond.
+pub struct PoissonArrivals {
+    rate: f64,
+    rng: StdRng,
+    next: Option<Instant>,
+}
+
+impl PoissonArrivals {
+    /// `rate` is the mean number of arrivals per second, the gaps between them being
+    /// drawn from `rng`
+    pub fn new(rate: f64, rng: StdRng) -> Self {
+        PoissonArrivals {
+            rate,
+            rng,
+            next: None,
+        }
+    }
+
+    /// Waits for the next arrival, returning the time it was scheduled at
+    ///
+    /// As with [`FixedArrivals`], a wait cut short doesn't skip an arrival.
+    pub async fn wait(&mut self) -> Instant {
+        let next = if let Some(next) = self.next {
+            next
+        } else {
+            let first = Instant::now() + self.interval();
+            *self.next.insert(first)
+        };
+        sleep_until(next).await;
+        self.next = Some(next + self.interval());
+        next
+    }
+
+    /// Draws the gap until the next arrival
+    fn interval(&mut self) -> Duration {
+        // The inverse of the exponential CDF, `1 - u` keeps the draw within (0, 1]
+        let u: f64 = self.rng.gen();
+        Duration::from_secs_f64(-(1.0 - u).ln() / self.rate)
+    }
+}
+
+/// An open-model arrival process where requests are evenly spaced at a fixed interval
+///
+/// Like [`PoissonArrivals`], the schedule is independent of how long the server takes
+/// to respond.
+pub struct FixedArrivals {
+    interval: Duration,
+    offset: Duration,
+    next: Option<Instant>,
+}
+
+impl FixedArrivals {
+    /// The first arrival is `offset` after the first wait, so that connections sharing
+    /// the same interval can be staggered
+    pub fn new(interval: Duration, offset: Duration) -> Self {
+        FixedArrivals {
+            interval,
+            offset,
+            next: None,
+        }
+    }
+
+    /// Waits for the next arrival, returning the time it was scheduled at
+    ///
+    /// The schedule only advances once the wait completes, so a wait cut short (e.g.
+    /// by `tokio::select!`) doesn't skip an arrival.
+    pub async fn wait(&mut self) -> Instant {
+        let offset = self.offset;
+        let next = *self.next.get_or_insert_with(|| Instant::now() + offset);
+        sleep_until(next).await;
+        self.next = Some(next + self.interval);
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::arrival::PoissonArrivals;
+    use crate::util;
+    use std::time::Duration;
+
+    #[test]
+    fn mean_interval_matches_rate() {
+        let mut arrivals = PoissonArrivals::new(100.0, util::seeded_rng("seed"));
+
+        let samples = 100_000;
+        let total: Duration = (0..samples).map(|_| arrivals.interval()).sum();
+        let mean = total.as_secs_f64() / f64::from(samples);
+        assert!((mean - 0.01).abs() < 0.0005, "mean interval was {mean}");
+    }
+
+    #[test]
+    fn intervals_are_derived_from_the_seed() {
+        let draw = |seed| {
+            let mut arrivals = PoissonArrivals::new(100.0, util::seeded_rng(seed));
+            (0..10).map(|_| arrivals.interval()).collect::<Vec<_>>()
+        };
+        assert_eq!(draw("seed"), draw("seed"));
+        assert_ne!(draw("seed"), draw("other"));
+    }
+}
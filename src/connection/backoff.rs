@@ -0,0 +1,95 @@
+use crate::cli::BackoffJitter;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::time::Duration;
+
+/// The delays between failed attempts to replace a dropped connection, doubling from
+/// the base with every attempt up to the cap
+///
+/// Jitter spreads out the attempts of the connections that were dropped together, so
+/// they don't all hit the recovering server at once. It's derived from a seed so a run
+/// can be reproduced.
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    jitter: BackoffJitter,
+    attempt: u32,
+    rng: StdRng,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration, jitter: BackoffJitter, rng: StdRng) -> Self {
+        Backoff {
+            base,
+            max,
+            jitter,
+            attempt: 0,
+            rng,
+        }
+    }
+
+    /// Starts over from the base delay for the attempts that follow, the jitter carrying
+    /// on from where it was
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// The delay before the next attempt
+    pub fn next(&mut self) -> Duration {
+        let ceiling = self
+            .base
+            .checked_mul(2_u32.saturating_pow(self.attempt))
+            .map_or(self.max, |ceiling| ceiling.min(self.max));
+        self.attempt += 1;
+        match self.jitter {
+            BackoffJitter::None => ceiling,
+            BackoffJitter::Full => ceiling.mul_f64(self.rng.gen::<f64>()),
+            BackoffJitter::Equal => ceiling / 2 + (ceiling / 2).mul_f64(self.rng.gen::<f64>()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::BackoffJitter;
+    use crate::connection::backoff::Backoff;
+    use crate::util;
+    use std::time::Duration;
+
+    fn delays(jitter: BackoffJitter) -> Vec<Duration> {
+        let mut backoff = Backoff::new(
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            jitter,
+            util::seeded_rng("seed"),
+        );
+        (0..100).map(|_| backoff.next()).collect()
+    }
+
+    #[test]
+    fn doubles_up_to_the_cap() {
+        let delays = delays(BackoffJitter::None);
+        assert_eq!(
+            delays[..6],
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+        assert_eq!(delays[99], Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jittered_within_bounds() {
+        let ceilings = delays(BackoffJitter::None);
+        for (jitter, floor) in [(BackoffJitter::Full, 0.0), (BackoffJitter::Equal, 0.5)] {
+            let delays = delays(jitter);
+            for (delay, ceiling) in delays.iter().zip(&ceilings) {
+                assert!(
+                    (ceiling.mul_f64(floor)..=*ceiling).contains(delay),
+                    "{jitter:?} delay {delay:?} out of bounds of {ceiling:?}"
+                );
+            }
+            // The delays of the connections dropped together are spread out
+            assert!(delays.windows(2).skip(5).any(|w| w[0] != w[1]));
+            assert_eq!(delays, self::delays(jitter), "same seed, same delays");
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::connection::decompress::{ContentEncoding, Decompressor};
+use crate::stats::{Decompressed, ErrorSample};
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use hyper::body::{Body, Frame, Incoming, SizeHint};
+use hyper::{Request, Response};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{ready, Context, Poll};
+use tokio::time::Instant;
+
+type FrameResult = Option<Result<Frame<Bytes>, hyper::Error>>;
+
+/// The number of bytes of the body of an error response kept to show in the summary
+const ERROR_SAMPLE_LEN: usize = 256;
+
+/// A response body that lets the parent [`crate::connection::Connection`] wait
+/// for the first frame to arrive before handing the response off to the engine.
+///
+/// The peeked frame is held onto and yielded first, so engines read the body
+/// exactly as they would an [`Incoming`]. The same goes for the rest of the body when
+/// it was read ahead into memory.
+///
+/// With a read limit the body ends early once that many bytes were read, leaving the
+/// rest of it unread on the connection, which can't be reused after.
+///
+/// When decompressing, the body still yields the bytes as they came over the wire,
+/// they're only decompressed to be counted.
+///
+/// The start of the body of an error response is kept as it's read, to tell what the
+/// server said.
+pub struct ResponseBody {
+    inner: Incoming,
+    /// The frames read ahead of the engine, the last one being `None` once the body ended
+    peeked: VecDeque<FrameResult>,
+    /// Bytes left to read before the body is cut short
+    remaining: Option<usize>,
+    truncated: bool,
+    decompressor: Option<Decompressor>,
+    /// The bytes of the body kept so far, when it's an error
+    sample: Option<Vec<u8>>,
+}
+
+impl ResponseBody {
+    pub fn new(inner: Incoming) -> Self {
+        ResponseBody {
+            inner,
+            peeked: VecDeque::new(),
+            remaining: None,
+            truncated: false,
+            decompressor: None,
+            sample: None,
+        }
+    }
+
+    /// Wraps the body of a response, decompressing it when asked to and it's encoded
+    pub fn wrap(
+        resp: Response<Incoming>,
+        read_limit: Option<usize>,
+        decompress: bool,
+    ) -> Response<Self> {
+        let encoding = ContentEncoding::of(resp.headers());
+        // An encoded body would have to be decompressed to make any sense of it
+        let sampled = !resp.status().is_success() && encoding.is_none();
+        let encoding = encoding.filter(|_| decompress);
+        resp.map(|body| {
+            let body = ResponseBody::new(body)
+                .with_read_limit(read_limit)
+                .with_decompression(encoding);
+            if sampled {
+                body.with_error_sample()
+            } else {
+                body
+            }
+        })
+    }
+
+    pub fn with_read_limit(self, limit: Option<usize>) -> Self {
+        ResponseBody {
+            remaining: limit,
+            ..self
+        }
+    }
+
+    /// Decompresses the body as it's read when it's encoded
+    pub fn with_decompression(self, encoding: Option<ContentEncoding>) -> Self {
+        ResponseBody {
+            decompressor: encoding.map(Decompressor::new),
+            ..self
+        }
+    }
+
+    /// Keeps the start of the body as it's read, see [`error_sample`](Self::error_sample)
+    pub fn with_error_sample(self) -> Self {
+        ResponseBody {
+            sample: Some(Vec::with_capacity(ERROR_SAMPLE_LEN)),
+            ..self
+        }
+    }
+
+    /// The start of the body as text on a single line, if it was kept and isn't blank,
+    /// to be called once it has been read
+    pub fn error_sample(&mut self) -> Option<ErrorSample> {
+        let sample = self.sample.take()?;
+        let text = String::from_utf8_lossy(&sample)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        (!text.is_empty()).then_some(ErrorSample(text))
+    }
+
+    /// The bytes the body came down to once decompressed, if it was, to be called once
+    /// it has been read
+    pub fn finish_decompression(&mut self) -> Option<Decompressed> {
+        self.decompressor.take().map(Decompressor::finish)
+    }
+
+    /// Whether the body was cut short by the read limit
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Waits until the first frame of the body has arrived (or the body has ended)
+    pub async fn first_frame(&mut self) {
+        if self.peeked.is_empty() {
+            let frame = self.inner.frame().await;
+            self.peeked.push_back(frame);
+        }
+    }
+
+    /// Reads the rest of the body ahead into memory, up to the read limit, so that the
+    /// engine can be handed the response without waiting on the server
+    pub async fn read_ahead(&mut self) {
+        let mut read = self.peeked.iter().map(frame_len).sum::<usize>();
+        while !matches!(self.peeked.back(), Some(None | Some(Err(_))))
+            && self.remaining.map_or(true, |limit| read < limit)
+        {
+            let frame = self.inner.frame().await;
+            read += frame_len(&frame);
+            self.peeked.push_back(frame);
+        }
+    }
+}
+
+impl Body for ResponseBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if self.remaining == Some(0) && !self.is_end_stream() {
+            self.truncated = true;
+            return Poll::Ready(None);
+        }
+        let mut frame = match self.peeked.pop_front() {
+            Some(frame) => frame,
+            None => ready!(Pin::new(&mut self.inner).poll_frame(cx)),
+        };
+        if let (Some(remaining), Some(Ok(frame))) = (&mut self.remaining, &mut frame) {
+            if let Some(d) = frame.data_mut() {
+                d.truncate(*remaining);
+                *remaining -= d.len();
+            }
+        }
+        if let (Some(decompressor), Some(Ok(frame))) = (&mut self.decompressor, &frame) {
+            if let Some(d) = frame.data_ref() {
+                decompressor.update(d);
+            }
+        }
+        if let (Some(sample), Some(Ok(frame))) = (&mut self.sample, &frame) {
+            if let Some(d) = frame.data_ref() {
+                let len = (ERROR_SAMPLE_LEN - sample.len()).min(d.len());
+                sample.extend_from_slice(&d[..len]);
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        if self.truncated {
+            return true;
+        }
+        match self.peeked.front() {
+            Some(None) => true,
+            Some(Some(_)) => false,
+            None => self.inner.is_end_stream(),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let mut hint = self.inner.size_hint();
+        let len = self.peeked.iter().map(frame_len).sum::<usize>() as u64;
+        if let Some(upper) = hint.upper() {
+            hint.set_upper(upper + len);
+        }
+        hint.set_lower(hint.lower() + len);
+        hint
+    }
+}
+
+/// The number of bytes of data of a frame
+fn frame_len(frame: &FrameResult) -> usize {
+    match frame {
+        Some(Ok(frame)) => frame.data_ref().map_or(0, Bytes::len),
+        _ => 0,
+    }
+}
+
+/// A request body that notes when it has been handed off to the connection in full,
+/// telling the time spent sending the request apart from the time spent waiting on
+/// the server
+///
+/// Hyper doesn't poll a body to its end once it reports it has ended, so the body is
+/// taken to be sent as soon as either of them says so.
+pub struct RequestBody<B> {
+    /// Boxed as the bodies of engines needn't be `Unpin`
+    inner: Pin<Box<B>>,
+    sent: Arc<OnceLock<Instant>>,
+}
+
+/// Attached as an extension to a response with when its request had been sent in
+/// full, which the server may well respond before
+#[derive(Debug, Clone, Copy)]
+pub struct RequestSent(pub Instant);
+
+impl<B: Body> RequestBody<B> {
+    /// Wraps the body of a request, returning when it will have been sent
+    pub fn wrap(req: Request<B>) -> (Request<Self>, Arc<OnceLock<Instant>>) {
+        let sent = Arc::new(OnceLock::new());
+        let req = req.map(|body| RequestBody {
+            inner: Box::pin(body),
+            sent: sent.clone(),
+        });
+        (req, sent)
+    }
+
+    fn mark_sent(&self) {
+        // Only the first time counts
+        let _ = self.sent.set(Instant::now());
+    }
+}
+
+impl<B: Body> Body for RequestBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = ready!(self.inner.as_mut().poll_frame(cx));
+        if frame.is_none() || self.inner.is_end_stream() {
+            self.mark_sent();
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        let end = self.inner.is_end_stream();
+        if end {
+            self.mark_sent();
+        }
+        end
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -1,23 +1,24 @@
+use crate::connection::lifecycle::ConnectionHttpLifecycle;
 use crate::connection::ConnectionLifecycle;
 use async_trait::async_trait;
-use std::rc::Rc;
-use std::sync::atomic::AtomicBool;
+use hyper::{Request, Response};
 use std::sync::atomic::Ordering::{Relaxed, SeqCst};
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::task::JoinHandle;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 
 /// A completion condition that marks the run as completed
 /// once the specified number of requests have been issued
 pub struct RequestCompletionCondition {
-    local_run: Rc<AtomicBool>,
+    local_run: Arc<AtomicBool>,
     num_requests: usize,
     num_requests_for_completion: usize,
 }
 
 impl RequestCompletionCondition {
-    pub fn new(local_run: Rc<AtomicBool>, num_requests_for_completion: usize) -> Self {
+    pub fn new(local_run: Arc<AtomicBool>, num_requests_for_completion: usize) -> Self {
         RequestCompletionCondition {
             local_run,
             num_requests: 0,
@@ -26,7 +27,7 @@ impl RequestCompletionCondition {
     }
 }
 
-#[async_trait(? Send)]
+#[async_trait]
 impl ConnectionLifecycle for RequestCompletionCondition {
     async fn should_issue_request(&mut self) -> bool {
         self.num_requests += 1;
@@ -39,22 +40,122 @@ impl ConnectionLifecycle for RequestCompletionCondition {
     }
 }
 
+/// A completion condition that marks the run as completed once the specified number
+/// of bytes have been transferred, written and read alike
+///
+/// Bytes are counted as the requests are issued and their responses come in, whether
+/// they succeeded or not, so the last requests may take the total past the target.
+pub struct BytesCompletionCondition {
+    local_run: Arc<AtomicBool>,
+    /// Shared with the listeners forked off the connection for requests in flight
+    transferred: Arc<AtomicUsize>,
+    bytes_for_completion: usize,
+}
+
+impl BytesCompletionCondition {
+    pub fn new(local_run: Arc<AtomicBool>, bytes_for_completion: usize) -> Self {
+        BytesCompletionCondition {
+            local_run,
+            transferred: Arc::new(AtomicUsize::new(0)),
+            bytes_for_completion,
+        }
+    }
+}
+
+#[async_trait]
+impl ConnectionLifecycle for BytesCompletionCondition {
+    fn fork(&self) -> Option<ConnectionHttpLifecycle> {
+        Some(ConnectionHttpLifecycle::BytesCompletion(
+            BytesCompletionCondition {
+                local_run: self.local_run.clone(),
+                transferred: self.transferred.clone(),
+                bytes_for_completion: self.bytes_for_completion,
+            },
+        ))
+    }
+
+    async fn should_issue_request(&mut self) -> bool {
+        if self.transferred.load(Relaxed) >= self.bytes_for_completion {
+            self.local_run.store(false, SeqCst);
+            false
+        } else {
+            true
+        }
+    }
+
+    async fn before_request<T: Sync>(&mut self, _req: &Request<T>, size: usize, _start: Instant) {
+        self.transferred.fetch_add(size, Relaxed);
+    }
+
+    async fn after_response<T: Sync>(&mut self, _resp: &Response<T>, resp_len: usize) {
+        self.transferred.fetch_add(resp_len, Relaxed);
+    }
+}
+
 /// A completion condition that marks the run as completed
 /// once the specified duration has elapsed
 pub struct DurationCompletionCondition {
     pub run: Arc<AtomicBool>,
     pub duration_cond: Duration,
+    /// How long before the end of the run to clear the recording flag
+    pub cooldown: Option<(Duration, Arc<AtomicBool>)>,
     pub handle: Option<JoinHandle<()>>,
 }
 
-#[async_trait(? Send)]
+#[async_trait]
 impl ConnectionLifecycle for DurationCompletionCondition {
     async fn after_setup(&mut self) {
         let run_flag = self.run.clone();
         let duration = self.duration_cond;
-        self.handle.replace(tokio::task::spawn_local(async move {
-            sleep(duration).await;
+        let cooldown = self.cooldown.clone();
+        self.handle.replace(tokio::spawn(async move {
+            let remaining = match cooldown {
+                Some((cooldown, recording)) => {
+                    sleep(duration.saturating_sub(cooldown)).await;
+                    recording.store(false, Relaxed);
+                    cooldown.min(duration)
+                }
+                None => duration,
+            };
+            sleep(remaining).await;
             run_flag.store(false, Relaxed);
         }));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::completion::BytesCompletionCondition;
+    use crate::connection::lifecycle::ConnectionLifecycle;
+    use hyper::{Request, Response};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+    use tokio::time::Instant;
+
+    #[test]
+    fn completes_once_the_bytes_of_requests_in_flight_add_up() {
+        let local_run = Arc::new(AtomicBool::new(true));
+        let mut condition = BytesCompletionCondition::new(local_run.clone(), 100);
+        let mut first = condition.fork().unwrap();
+        let mut second = condition.fork().unwrap();
+        futures::executor::block_on(async {
+            // Both requests are launched before either completes, as on an open loop
+            assert!(condition.should_issue_request().await);
+            first
+                .before_request(&Request::new(()), 30, Instant::now())
+                .await;
+            assert!(condition.should_issue_request().await);
+            second
+                .before_request(&Request::new(()), 40, Instant::now())
+                .await;
+            first.after_response(&Response::new(()), 10).await;
+            assert!(condition.should_issue_request().await);
+            assert!(local_run.load(SeqCst));
+
+            second.after_response(&Response::new(()), 20).await;
+            assert!(!condition.should_issue_request().await);
+        });
+        assert!(!local_run.load(SeqCst));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::connection::ConnectionLifecycle;
+use async_trait::async_trait;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// How long a parked connection waits before checking whether it's been unparked
+const PARK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A concurrency limit that hooks into the lifecycle of a connection to park it
+/// whenever its id falls outside the number of connections currently allowed to
+/// be active.
+///
+/// The limit is shared across all connections of the run and adjusted by a
+/// [`ConcurrencyController`].
+pub struct ConcurrencyLimit {
+    id: usize,
+    active: Arc<AtomicUsize>,
+}
+
+impl ConcurrencyLimit {
+    /// `id` must be unique across all connections of the run
+    pub fn new(id: usize, active: Arc<AtomicUsize>) -> Self {
+        ConcurrencyLimit { id, active }
+    }
+}
+
+#[async_trait]
+impl ConnectionLifecycle for ConcurrencyLimit {
+    async fn should_issue_request(&mut self) -> bool {
+        if self.id < self.active.load(Relaxed) {
+            true
+        } else {
+            sleep(PARK_INTERVAL).await;
+            false
+        }
+    }
+}
+
+/// An AIMD (additive increase, multiplicative decrease) controller that adjusts
+/// the number of active connections to meet a target throughput.
+///
+/// Connections are added one at a time while under the target, and halved when
+/// the target is overshot or latency climbs well above the best observed so far.
+pub struct ConcurrencyController {
+    target: f64,
+    max: usize,
+    active: Arc<AtomicUsize>,
+    best_latency: Option<f64>,
+}
+
+/// How far over the target throughput is tolerated before backing off
+const OVERSHOOT_TOLERANCE: f64 = 1.1;
+/// How many times the best observed latency is tolerated before backing off
+const LATENCY_TOLERANCE: f64 = 2.0;
+
+impl ConcurrencyController {
+    pub fn new(target: f64, max: usize) -> Self {
+        ConcurrencyController {
+            target,
+            max,
+            active: Arc::new(AtomicUsize::new(1)),
+            best_latency: None,
+        }
+    }
+
+    /// The shared number of connections that are allowed to be active
+    pub fn active(&self) -> Arc<AtomicUsize> {
+        self.active.clone()
+    }
+
+    /// Adjusts the number of active connections given the throughput and mean
+    /// latency observed over the last interval
+    pub fn adjust(&mut self, throughput: f64, latency: Option<f64>) -> usize {
+        let active = self.active.load(Relaxed);
+
+        let latency_climbed = match (latency, self.best_latency) {
+            (Some(l), Some(best)) => l > best * LATENCY_TOLERANCE,
+            _ => false,
+        };
+        if let Some(l) = latency {
+            self.best_latency = Some(self.best_latency.map_or(l, |best| best.min(l)));
+        }
+
+        let next = if throughput > self.target * OVERSHOOT_TOLERANCE || latency_climbed {
+            (active / 2).max(1)
+        } else if throughput < self.target {
+            (active + 1).min(self.max)
+        } else {
+            active
+        };
+
+        self.active.store(next, Relaxed);
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::concurrency::ConcurrencyController;
+
+    #[test]
+    fn increases_under_target() {
+        let mut controller = ConcurrencyController::new(100.0, 3);
+        assert_eq!(controller.adjust(10.0, Some(1.0)), 2);
+        assert_eq!(controller.adjust(20.0, Some(1.0)), 3);
+        // capped at the max number of connections
+        assert_eq!(controller.adjust(30.0, Some(1.0)), 3);
+    }
+
+    #[test]
+    fn holds_at_target() {
+        let mut controller = ConcurrencyController::new(100.0, 10);
+        controller.adjust(50.0, Some(1.0));
+        assert_eq!(controller.adjust(105.0, Some(1.0)), 2);
+    }
+
+    #[test]
+    fn backs_off_on_overshoot() {
+        let mut controller = ConcurrencyController::new(100.0, 10);
+        for _ in 0..7 {
+            controller.adjust(50.0, Some(1.0));
+        }
+        assert_eq!(controller.adjust(200.0, Some(1.0)), 4);
+    }
+
+    #[test]
+    fn backs_off_on_latency_climb() {
+        let mut controller = ConcurrencyController::new(100.0, 10);
+        for _ in 0..5 {
+            controller.adjust(50.0, Some(1.0));
+        }
+        assert_eq!(controller.adjust(50.0, Some(5.0)), 3);
+        // never parks every connection
+        assert_eq!(controller.adjust(50.0, Some(5.0)), 1);
+        assert_eq!(controller.adjust(50.0, Some(5.0)), 1);
+    }
+}
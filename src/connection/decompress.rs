@@ -0,0 +1,162 @@
+use crate::stats::Decompressed;
+use flate2::write::{GzDecoder, ZlibDecoder};
+use hyper::header::{HeaderMap, CONTENT_ENCODING};
+use log::warn;
+use std::io::Write;
+
+/// The `Content-Encoding`s of the response bodies that can be decompressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    /// The zlib format, which is what `deflate` stands for in HTTP
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// The encoding of a response, if it's one that can be decompressed
+    pub fn of(headers: &HeaderMap) -> Option<Self> {
+        let encoding = headers.get(CONTENT_ENCODING)?.to_str().ok()?.trim();
+        if encoding.eq_ignore_ascii_case("gzip") || encoding.eq_ignore_ascii_case("x-gzip") {
+            Some(ContentEncoding::Gzip)
+        } else if encoding.eq_ignore_ascii_case("deflate") {
+            Some(ContentEncoding::Deflate)
+        } else {
+            None
+        }
+    }
+}
+
+/// Discards the bytes written to it, only counting them
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Decoder {
+    Gzip(GzDecoder<ByteCounter>),
+    Deflate(ZlibDecoder<ByteCounter>),
+}
+
+/// Decompresses a response body as it's read to count the bytes it decompresses to,
+/// which are discarded
+///
+/// A body that fails to decompress is only counted up to where it did.
+pub struct Decompressor {
+    decoder: Decoder,
+    compressed: usize,
+    failed: bool,
+}
+
+impl Decompressor {
+    pub fn new(encoding: ContentEncoding) -> Self {
+        let decoder = match encoding {
+            ContentEncoding::Gzip => Decoder::Gzip(GzDecoder::new(ByteCounter(0))),
+            ContentEncoding::Deflate => Decoder::Deflate(ZlibDecoder::new(ByteCounter(0))),
+        };
+        Decompressor {
+            decoder,
+            compressed: 0,
+            failed: false,
+        }
+    }
+
+    /// Decompresses the next bytes of the body
+    pub fn update(&mut self, data: &[u8]) {
+        self.compressed += data.len();
+        if self.failed {
+            return;
+        }
+        let result = match &mut self.decoder {
+            Decoder::Gzip(d) => d.write_all(data),
+            Decoder::Deflate(d) => d.write_all(data),
+        };
+        if let Err(e) = result {
+            warn!("Failed to decompress the response body: {e}");
+            self.failed = true;
+        }
+    }
+
+    /// Decompresses what's left of the body, returning the bytes it came down to
+    pub fn finish(mut self) -> Decompressed {
+        if !self.failed {
+            // A body cut short by the read limit is incomplete
+            let _ = match &mut self.decoder {
+                Decoder::Gzip(d) => d.try_finish(),
+                Decoder::Deflate(d) => d.try_finish(),
+            };
+        }
+        let decompressed = match &self.decoder {
+            Decoder::Gzip(d) => d.get_ref().0,
+            Decoder::Deflate(d) => d.get_ref().0,
+        };
+        Decompressed {
+            compressed: self.compressed,
+            decompressed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::decompress::{ContentEncoding, Decompressor};
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use hyper::header::{HeaderMap, HeaderValue, CONTENT_ENCODING};
+    use std::io::Write;
+
+    fn decompress(encoding: ContentEncoding, body: &[u8]) -> (usize, usize) {
+        let mut decompressor = Decompressor::new(encoding);
+        for chunk in body.chunks(100) {
+            decompressor.update(chunk);
+        }
+        let decompressed = decompressor.finish();
+        (decompressed.compressed, decompressed.decompressed)
+    }
+
+    #[test]
+    fn counts_decompressed_bytes() {
+        let json = br#"{"items": [1, 2, 3]}"#.repeat(1000);
+
+        let mut gzip = GzEncoder::new(vec![], Compression::default());
+        gzip.write_all(&json).unwrap();
+        let gzip = gzip.finish().unwrap();
+        assert_eq!(
+            decompress(ContentEncoding::Gzip, &gzip),
+            (gzip.len(), json.len())
+        );
+
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(&json).unwrap();
+        let zlib = zlib.finish().unwrap();
+        assert_eq!(
+            decompress(ContentEncoding::Deflate, &zlib),
+            (zlib.len(), json.len())
+        );
+
+        // Garbage is counted on the wire but decompresses to nothing
+        assert_eq!(decompress(ContentEncoding::Gzip, b"not gzip"), (8, 0));
+    }
+
+    #[test]
+    fn content_encoding_of_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ContentEncoding::of(&headers), None);
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("GZIP"));
+        assert_eq!(ContentEncoding::of(&headers), Some(ContentEncoding::Gzip));
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("deflate"));
+        assert_eq!(
+            ContentEncoding::of(&headers),
+            Some(ContentEncoding::Deflate)
+        );
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("br"));
+        assert_eq!(ContentEncoding::of(&headers), None);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::cli::IpVersion;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::lookup_host;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Caches the addresses hosts resolve to, shared by the connections of a worker
+/// so they don't each hit the resolver.
+///
+/// Connections are handed the cached addresses in round robin order, spreading
+/// them across all the A/AAAA records of the host.
+pub struct DnsCache {
+    ttl: Duration,
+    ip_version: IpVersion,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+struct CacheEntry {
+    addresses: Vec<SocketAddr>,
+    resolved_at: Instant,
+    next: usize,
+}
+
+impl CacheEntry {
+    fn is_fresh(&self, ttl: Duration, now: Instant) -> bool {
+        now.duration_since(self.resolved_at) < ttl
+    }
+
+    fn next_address(&mut self) -> SocketAddr {
+        let address = self.addresses[self.next % self.addresses.len()];
+        self.next = self.next.wrapping_add(1);
+        address
+    }
+}
+
+impl DnsCache {
+    /// Addresses are resolved again once they are older than `ttl`, only those of
+    /// `ip_version` are kept
+    pub fn new(ttl: Duration, ip_version: IpVersion) -> Self {
+        DnsCache {
+            ttl,
+            ip_version,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Resolves `authority` (`host:port`) to the next of its addresses, looking them up
+    /// when they aren't cached or have expired
+    ///
+    /// Returns how long the lookup took when one was performed
+    pub async fn resolve(&self, authority: &str) -> Result<(SocketAddr, Option<Duration>)> {
+        // Holding the lock over the lookup makes concurrent connections wait on it
+        // rather than all resolving the host at once
+        let mut entries = self.entries.lock().await;
+        if let Some(entry) = entries.get_mut(authority) {
+            if entry.is_fresh(self.ttl, Instant::now()) {
+                return Ok((entry.next_address(), None));
+            }
+        }
+
+        let start = Instant::now();
+        let addresses = lookup_host(authority)
+            .await?
+            .filter(|addr| match self.ip_version {
+                IpVersion::Auto => true,
+                IpVersion::V4 => addr.is_ipv4(),
+                IpVersion::V6 => addr.is_ipv6(),
+            })
+            .collect::<Vec<_>>();
+        let resolution_time = start.elapsed();
+        if addresses.is_empty() {
+            return Err(anyhow!(
+                "Failed to resolve {authority} (IP version: {:?})",
+                self.ip_version
+            ));
+        }
+
+        let next = entries.get(authority).map_or(0, |e| e.next);
+        let mut entry = CacheEntry {
+            addresses,
+            resolved_at: Instant::now(),
+            next,
+        };
+        let address = entry.next_address();
+        entries.insert(authority.to_string(), entry);
+        Ok((address, Some(resolution_time)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::dns::CacheEntry;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn round_robins_addresses() {
+        let mut entry = CacheEntry {
+            addresses: vec![
+                "127.0.0.1:80".parse().unwrap(),
+                "127.0.0.2:80".parse().unwrap(),
+            ],
+            resolved_at: Instant::now(),
+            next: 0,
+        };
+        let addresses = (0..4).map(|_| entry.next_address()).collect::<Vec<_>>();
+        assert_eq!(addresses[0], addresses[2]);
+        assert_eq!(addresses[1], addresses[3]);
+        assert_ne!(addresses[0], addresses[1]);
+    }
+
+    #[test]
+    fn expires_after_ttl() {
+        let resolved_at = Instant::now();
+        let entry = CacheEntry {
+            addresses: vec!["127.0.0.1:80".parse().unwrap()],
+            resolved_at,
+            next: 0,
+        };
+        let ttl = Duration::from_secs(60);
+        assert!(entry.is_fresh(ttl, resolved_at + Duration::from_secs(59)));
+        assert!(!entry.is_fresh(ttl, resolved_at + ttl));
+        assert!(!entry.is_fresh(Duration::ZERO, resolved_at));
+    }
+}
@@ -0,0 +1,50 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// Injects faults into a connection by randomly dropping it after a response,
+/// forcing the connection through the reconnect path.
+///
+/// The drops are derived from a seed so a run can be reproduced.
+pub struct FaultInjector {
+    /// Probability of dropping the connection after each response, between 0 and 1
+    drop_probability: f64,
+    rng: StdRng,
+}
+
+impl FaultInjector {
+    /// `drop_rate` is the percentage of responses after which the connection is dropped
+    pub fn new(drop_rate: f64, rng: StdRng) -> Self {
+        FaultInjector {
+            drop_probability: drop_rate / 100.0,
+            rng,
+        }
+    }
+
+    /// Whether the connection should be dropped
+    pub fn should_drop(&mut self) -> bool {
+        self.rng.gen_bool(self.drop_probability)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::fault::FaultInjector;
+    use crate::util;
+
+    #[test]
+    fn drops_at_rate() {
+        let mut faults = FaultInjector::new(10.0, util::seeded_rng("seed"));
+        let drops = (0..100_000).filter(|_| faults.should_drop()).count();
+        assert!(
+            (9_500..10_500).contains(&drops),
+            "dropped {drops} connections"
+        );
+    }
+
+    #[test]
+    fn same_seed_same_drops() {
+        let mut a = FaultInjector::new(50.0, util::seeded_rng("seed"));
+        let mut b = FaultInjector::new(50.0, util::seeded_rng("seed"));
+        assert!((0..1_000).all(|_| a.should_drop() == b.should_drop()));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::connection::ConnectionLifecycle;
+use async_trait::async_trait;
+use hyper::Request;
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+/// The distribution the latency injected into each request is drawn from
+#[derive(Debug, Clone, PartialEq)]
+pub enum LatencyDistribution {
+    /// Every request is delayed by the same amount
+    Fixed(Duration),
+    /// Delays are drawn uniformly from the inclusive range
+    Uniform { min: Duration, max: Duration },
+    /// Delays are drawn from an exponential distribution with the mean, giving a long tail
+    Exponential { mean: Duration },
+}
+
+impl FromStr for LatencyDistribution {
+    type Err = String;
+
+    /// Parses either a fixed delay (`10ms`), a uniform range (`5ms..50ms`) or an
+    /// exponential distribution by its mean (`exp:20ms`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |s: &str| humantime::parse_duration(s.trim()).map_err(|e| format!("{e}"));
+        if let Some(mean) = s.strip_prefix("exp:") {
+            return Ok(LatencyDistribution::Exponential { mean: parse(mean)? });
+        }
+        if let Some((min, max)) = s.split_once("..") {
+            let (min, max) = (parse(min)?, parse(max)?);
+            if min > max {
+                return Err(format!("range `{s}` is empty"));
+            }
+            return Ok(LatencyDistribution::Uniform { min, max });
+        }
+        Ok(LatencyDistribution::Fixed(parse(s)?))
+    }
+}
+
+/// Delays every request by a latency drawn from a distribution before it's issued,
+/// as a self-test of the stats, whose percentiles should follow the distribution,
+/// or to model slow clients.
+///
+/// The delays are derived from a seed so a run can be reproduced.
+pub struct LatencyInjector {
+    distribution: LatencyDistribution,
+    /// Boxed to keep the listener about as small as the others
+    rng: Box<StdRng>,
+}
+
+impl LatencyInjector {
+    pub fn new(distribution: LatencyDistribution, rng: StdRng) -> Self {
+        LatencyInjector {
+            distribution,
+            rng: Box::new(rng),
+        }
+    }
+
+    /// Draws the delay of the next request
+    fn next(&mut self) -> Duration {
+        match &self.distribution {
+            LatencyDistribution::Fixed(delay) => *delay,
+            LatencyDistribution::Uniform { min, max } => {
+                Uniform::new_inclusive(*min, *max).sample(&mut *self.rng)
+            }
+            LatencyDistribution::Exponential { mean } => {
+                // Inverse transform sampling, `1 - u` keeps clear of ln(0)
+                let u = self.rng.gen::<f64>();
+                mean.mul_f64(-(1.0 - u).ln())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ConnectionLifecycle for LatencyInjector {
+    async fn before_request<T: Sync>(&mut self, _req: &Request<T>, _size: usize, _start: Instant) {
+        sleep(self.next()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::latency::{LatencyDistribution, LatencyInjector};
+    use crate::util;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_distributions() {
+        assert_eq!(
+            "10ms".parse::<LatencyDistribution>().unwrap(),
+            LatencyDistribution::Fixed(Duration::from_millis(10))
+        );
+        assert_eq!(
+            "5ms..50ms".parse::<LatencyDistribution>().unwrap(),
+            LatencyDistribution::Uniform {
+                min: Duration::from_millis(5),
+                max: Duration::from_millis(50)
+            }
+        );
+        assert_eq!(
+            "exp:20ms".parse::<LatencyDistribution>().unwrap(),
+            LatencyDistribution::Exponential {
+                mean: Duration::from_millis(20)
+            }
+        );
+        assert!("50ms..5ms".parse::<LatencyDistribution>().is_err());
+        assert!("slow".parse::<LatencyDistribution>().is_err());
+    }
+
+    #[test]
+    fn samples_follow_distribution() {
+        let (min, max) = (Duration::from_millis(10), Duration::from_millis(20));
+        let mut uniform = LatencyInjector::new(
+            LatencyDistribution::Uniform { min, max },
+            util::seeded_rng("seed"),
+        );
+        assert!((0..1000)
+            .map(|_| uniform.next())
+            .all(|d| (min..=max).contains(&d)));
+
+        let mean = Duration::from_millis(20);
+        let mut exponential = LatencyInjector::new(
+            LatencyDistribution::Exponential { mean },
+            util::seeded_rng("seed"),
+        );
+        let samples = 100_000_u32;
+        let sampled_mean = (0..samples).map(|_| exponential.next()).sum::<Duration>() / samples;
+        assert!(
+            (Duration::from_millis(19)..Duration::from_millis(21)).contains(&sampled_mean),
+            "sampled mean {sampled_mean:?}"
+        );
+    }
+}
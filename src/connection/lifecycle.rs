@@ -1,9 +1,17 @@
-use crate::connection::completion::{DurationCompletionCondition, RequestCompletionCondition};
+use crate::connection::completion::{
+    BytesCompletionCondition, DurationCompletionCondition, RequestCompletionCondition,
+};
+use crate::connection::concurrency::ConcurrencyLimit;
+use crate::connection::latency::LatencyInjector;
+use crate::connection::ramp::ConnectionRamp;
 use crate::connection::rate_limit::RateLimit;
 use crate::connection::stats::StatsCollector;
 use async_trait::async_trait;
 use enum_dispatch::enum_dispatch;
 use hyper::{Request, Response};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::Instant;
 
 /// Hook into the lifecycle of a Connection
 ///
@@ -18,9 +26,13 @@ use hyper::{Request, Response};
 ///     │                              ▼
 /// after_response             before_request
 ///     ▲                              │
-///     └─────── after_request ◄───────┘
+///     │                              ▼
+/// after_first_byte              after_send
+///     ▲                              │
+///     │                              ▼
+///     └────────────────────── after_request
 /// ```
-#[async_trait(? Send)]
+#[async_trait]
 #[enum_dispatch]
 #[allow(unused_variables, unused_mut)]
 pub trait ConnectionLifecycle {
@@ -30,18 +42,55 @@ pub trait ConnectionLifecycle {
     async fn should_issue_request(&mut self) -> bool {
         true
     }
-    /// Called before issuing a request
-    async fn before_request<T>(&mut self, req: &Request<T>, req_size: usize) {}
-    /// Called after issuing a request but before the engine handles the response
+    /// Called before establishing a new connection to the server
+    async fn before_connect(&mut self) {}
+    /// Called when the address of the server had to be looked up before connecting,
+    /// rather than being served from the DNS cache of the worker
+    async fn after_resolve(&mut self, resolution_time: Duration) {}
+    /// Called when establishing a connection failed as the local ephemeral ports ran
+    /// out, before backing off to try again
+    async fn after_port_exhaustion(&mut self) {}
+    /// Called once a new connection to `address` has been established, `reconnect`
+    /// is set when it replaces a keep-alive connection that was dropped
+    async fn after_connect(&mut self, address: &SocketAddr, reconnect: bool) {}
+    /// Called before issuing a request, `start` being when the request was due to be
+    /// issued (which trails behind now when an open-model arrival schedule runs late)
+    async fn before_request<T: Sync>(&mut self, req: &Request<T>, req_size: usize, start: Instant) {
+    }
+    /// Called with when the request, body included, had been handed off to the connection
+    ///
+    /// Only called once the response headers have been received, right before
+    /// `after_request`, and not at all when the server responded before the request
+    /// was sent in full.
+    async fn after_send(&mut self, sent: Instant) {}
+    /// Called after issuing a request, once the response headers have been received
     async fn after_request(&mut self) {}
+    /// Called once the first frame of the response body has arrived (or the body
+    /// has turned out to be empty) but before the engine handles the response
+    async fn after_first_byte(&mut self) {}
     /// Called after an engine has handled the response
-    async fn after_response<T>(&mut self, resp: &Response<T>, resp_len: usize) {}
+    async fn after_response<T: Sync>(&mut self, resp: &Response<T>, resp_len: usize) {}
+    /// Called once the connection has stopped issuing requests, before the engine
+    /// cleans up
+    async fn after_run(&mut self) {}
+    /// Creates a listener of its own for a request that's in flight alongside others on
+    /// an open loop, for listeners that track the state of individual requests
+    ///
+    /// Listeners that only gate whether requests are issued return `None`, they stay with
+    /// the connection.
+    fn fork(&self) -> Option<ConnectionHttpLifecycle> {
+        None
+    }
 }
 
 #[enum_dispatch(ConnectionLifecycle)]
 pub enum ConnectionHttpLifecycle {
     Stats(StatsCollector),
+    Concurrency(ConcurrencyLimit),
     RateLimit(RateLimit),
     DurationCompletion(DurationCompletionCondition),
     RequestsCompletion(RequestCompletionCondition),
+    BytesCompletion(BytesCompletionCondition),
+    Latency(LatencyInjector),
+    Ramp(ConnectionRamp),
 }
@@ -0,0 +1,262 @@
+use crate::connection::arrival::{Arrivals, Schedule};
+use crate::connection::body::{RequestBody, RequestSent, ResponseBody};
+use crate::connection::lifecycle::{ConnectionHttpLifecycle, ConnectionLifecycle};
+use crate::connection::{respond, send_with_timeout, Connection, ConnectionRunInfo};
+use crate::engine::Engine;
+use crate::logging;
+use anyhow::Result;
+use futures_util::stream::FuturesUnordered;
+use futures_util::StreamExt;
+use hyper::body::Body;
+use hyper::client::conn::http1::SendRequest;
+use hyper::{Method, Response, Uri};
+use log::{info, trace, warn};
+use std::error::Error;
+use tokio::time::Instant;
+
+/// A connection to the server that's idle in between requests
+struct Pooled<Req> {
+    sender: SendRequest<RequestBody<Req>>,
+    requests: usize,
+}
+
+/// A request that was in flight, along with the connection it was issued over
+struct Exchange<Req> {
+    conn: Pooled<Req>,
+    listeners: Vec<ConnectionHttpLifecycle>,
+    method: Method,
+    uri: Uri,
+    resp: Result<hyper::Result<Response<ResponseBody>>>,
+}
+
+impl Connection {
+    /// Launches requests on the schedule regardless of whether the previous ones have
+    /// completed, running each over an idle connection out of a pool that grows
+    /// whenever they're all busy
+    ///
+    /// Each exchange runs on a task of its own, reading the response body ahead into
+    /// memory, so that a slow response holds up none of the launches after it. Requests
+    /// are still built and their responses handed to the engine one at a time, the
+    /// lifecycle listeners that track individual requests are forked for each of them.
+    pub(super) async fn run_open_loop<E, Req>(
+        &mut self,
+        engine: &mut E,
+        url: &Uri,
+        schedule: Schedule,
+        warm_sender: Option<SendRequest<RequestBody<Req>>>,
+    ) -> Result<ConnectionRunInfo>
+    where
+        E: Engine<Req> + Send,
+        Req: Body + Send + Sync + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let mut idle = warm_sender
+            .map(|sender| Pooled {
+                sender,
+                requests: 0,
+            })
+            .into_iter()
+            .collect::<Vec<_>>();
+        let start_time = Instant::now();
+        let result = self.launch(engine, url, schedule, &mut idle).await;
+        let end_time = Instant::now();
+
+        // However the loop came to an end, what the connection recorded up to then is
+        // part of the run
+        for l in &mut self.lifecycle_listeners {
+            l.after_run().await;
+        }
+        let requests = result?;
+
+        info!("Cleaning up {} engine ({})", engine.name(), self.id);
+        self.issue_cleanup_requests(engine, url, idle.pop().map(|conn| conn.sender))
+            .await?;
+        engine.cleanup().await?;
+
+        Ok(ConnectionRunInfo {
+            start_time,
+            end_time,
+            requests,
+        })
+    }
+
+    /// Launches requests on the schedule until the run comes to an end, returning the
+    /// number that completed
+    async fn launch<E, Req>(
+        &mut self,
+        engine: &mut E,
+        url: &Uri,
+        schedule: Schedule,
+        idle: &mut Vec<Pooled<Req>>,
+    ) -> Result<usize>
+    where
+        E: Engine<Req> + Send,
+        Req: Body + Send + Sync + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let mut in_flight = FuturesUnordered::new();
+        let mut requests = 0;
+        let mut arrivals = Arrivals::new(schedule, &self.seed);
+
+        'run: loop {
+            if !self.run_flag.should_run() || engine.finished() {
+                break;
+            }
+
+            let start = loop {
+                tokio::select! {
+                    biased;
+                    Some(exchange) = in_flight.next() => {
+                        requests += self.complete(engine, exchange?, idle).await?;
+                    }
+                    start = arrivals.wait() => break start,
+                }
+            };
+
+            for l in &mut self.lifecycle_listeners {
+                if !l.should_issue_request().await {
+                    continue 'run;
+                }
+            }
+
+            let mut conn = match self.checkout(url, idle).await {
+                Ok(Some(conn)) => conn,
+                // Backing off was cut short by the end of the run
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Failed to connect, skipping the request due at {start:?}: {e}");
+                    continue;
+                }
+            };
+
+            let (req, req_len) = engine.request(self.request_builder(url)).await?;
+            let mut listeners = self
+                .lifecycle_listeners
+                .iter()
+                .filter_map(ConnectionLifecycle::fork)
+                .collect::<Vec<_>>();
+            for l in &mut listeners {
+                l.before_request(&req, req_len, start).await;
+            }
+
+            trace!("Sending request {} - {} ", req.method(), req.uri());
+            let method = req.method().clone();
+            let uri = req.uri().clone();
+            let (read_limit, decompress) = (self.options.read_limit, self.options.decompress);
+            let absolute_uri = self.options.absolute_uri;
+            in_flight.push(tokio::spawn(logging::request(&uri.clone(), async move {
+                let resp = match send_with_timeout(&mut conn.sender, req, absolute_uri).await {
+                    Ok(Ok(resp)) => {
+                        if let Some(RequestSent(sent)) = resp.extensions().get().copied() {
+                            for l in &mut listeners {
+                                l.after_send(sent).await;
+                            }
+                        }
+                        for l in &mut listeners {
+                            l.after_request().await;
+                        }
+                        let mut resp = ResponseBody::wrap(resp, read_limit, decompress);
+                        resp.body_mut().first_frame().await;
+                        for l in &mut listeners {
+                            l.after_first_byte().await;
+                        }
+                        resp.body_mut().read_ahead().await;
+                        Ok(Ok(resp))
+                    }
+                    Ok(Err(e)) => Ok(Err(e)),
+                    Err(e) => Err(e),
+                };
+                Exchange {
+                    conn,
+                    listeners,
+                    method,
+                    uri,
+                    resp,
+                }
+            })));
+        }
+
+        // Requests already launched are still part of the run
+        while let Some(exchange) = in_flight.next().await {
+            requests += self.complete(engine, exchange?, idle).await?;
+        }
+
+        Ok(requests)
+    }
+
+    /// Takes an idle connection out of the pool, establishing a new one when there's none
+    ///
+    /// Returns `None` if the run ends before a new connection could be established.
+    async fn checkout<Req>(
+        &mut self,
+        url: &Uri,
+        idle: &mut Vec<Pooled<Req>>,
+    ) -> Result<Option<Pooled<Req>>>
+    where
+        Req: Body + Send + 'static,
+        Req::Data: Send,
+        Req::Error: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let mut reconnect = false;
+        while let Some(conn) = idle.pop() {
+            if !conn.sender.is_closed() {
+                return Ok(Some(conn));
+            }
+            // The server closed the keep-alive connection
+            reconnect = true;
+        }
+        let sender = self.connect_backing_off(url, reconnect).await?;
+        Ok(sender.map(|sender| Pooled {
+            sender,
+            requests: 0,
+        }))
+    }
+
+    /// Hands the response of a completed request over to the engine, returning its
+    /// connection to the pool unless it's used up
+    ///
+    /// Returns the number of requests that completed, none when the exchange failed
+    async fn complete<E, Req>(
+        &mut self,
+        engine: &mut E,
+        exchange: Exchange<Req>,
+        idle: &mut Vec<Pooled<Req>>,
+    ) -> Result<usize>
+    where
+        E: Engine<Req> + Send,
+        Req: Body,
+    {
+        let Exchange {
+            mut conn,
+            mut listeners,
+            method,
+            uri,
+            resp,
+        } = exchange;
+        let mut resp = match resp? {
+            Ok(resp) => resp,
+            Err(e) => {
+                // The connection can't be used after a failed exchange, dropping it has
+                // the pool replace it
+                warn!("{method} request to {uri} failed: {e}");
+                return Ok(0);
+            }
+        };
+
+        logging::request(&uri, respond(engine, &mut resp, &mut listeners)).await?;
+
+        conn.requests += 1;
+        // The rest of a truncated body is left unread on the connection
+        if !resp.body().is_truncated()
+            && !self
+                .options
+                .max_requests_per_connection()
+                .is_some_and(|max| conn.requests >= max)
+        {
+            idle.push(conn);
+        }
+        Ok(1)
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::{anyhow, bail, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Largest response to a `CONNECT` we're willing to buffer before giving up on a proxy
+const MAX_RESPONSE_HEAD: usize = 8 * 1024;
+
+/// An HTTP proxy that connections tunnel through to reach the server
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    /// The `host:port` of the proxy
+    pub authority: String,
+    /// Value of the `Proxy-Authorization` header to send along with the `CONNECT`
+    pub authorization: Option<String>,
+}
+
+impl Proxy {
+    /// Asks the proxy the stream is connected to to open a tunnel to the `host:port`
+    /// target, after which the stream carries the traffic to the target
+    pub async fn tunnel(&self, stream: &mut TcpStream, target: &str) -> Result<()> {
+        let mut req = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+        if let Some(auth) = &self.authorization {
+            req.push_str(&format!("Proxy-Authorization: {auth}\r\n"));
+        }
+        req.push_str("\r\n");
+        stream.write_all(req.as_bytes()).await?;
+
+        // The target doesn't speak until spoken to, so everything read here belongs
+        // to the response of the proxy
+        let mut head = Vec::new();
+        let mut buf = [0; 1024];
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() > MAX_RESPONSE_HEAD {
+                bail!(
+                    "Response of proxy {} to CONNECT is too large",
+                    self.authority
+                );
+            }
+            let read = stream.read(&mut buf).await?;
+            if read == 0 {
+                bail!(
+                    "Proxy {} closed the connection during CONNECT",
+                    self.authority
+                );
+            }
+            head.extend_from_slice(&buf[..read]);
+        }
+
+        match status(&head)? {
+            200..=299 => Ok(()),
+            code => bail!(
+                "Proxy {} refused to tunnel to {target}: {code}",
+                self.authority
+            ),
+        }
+    }
+}
+
+/// Parses the status code out of the status line of the response head
+fn status(head: &[u8]) -> Result<u16> {
+    let head = String::from_utf8_lossy(head);
+    let status_line = head.lines().next().unwrap_or_default();
+    let mut parts = status_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/") => Ok(code.parse()?),
+        _ => Err(anyhow!("Malformed proxy response: {status_line}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::proxy::status;
+
+    #[test]
+    fn parse_status() {
+        assert_eq!(
+            status(b"HTTP/1.1 200 Connection established\r\n\r\n").unwrap(),
+            200
+        );
+        assert_eq!(
+            status(
+                b"HTTP/1.0 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic\r\n\r\n"
+            )
+            .unwrap(),
+            407
+        );
+        assert!(status(b"SSH-2.0-OpenSSH\r\n\r\n").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::connection::ConnectionLifecycle;
+use crate::util::format_duration;
+use async_trait::async_trait;
+use log::info;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+/// How often a connection waiting for its turn checks whether the run was stopped
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The start of the connections of a run, staggered evenly over a window rather than
+/// all at once past the setup barrier.
+///
+/// Shared across all connections of the run, the window starts as the first of them is
+/// set up.
+#[derive(Debug)]
+pub struct RampSchedule {
+    window: Duration,
+    connections: usize,
+    start: OnceLock<Instant>,
+    started: AtomicUsize,
+    /// How long into the window the last connection started
+    reached: OnceLock<Duration>,
+}
+
+impl RampSchedule {
+    pub fn new(window: Duration, connections: usize) -> Self {
+        RampSchedule {
+            window,
+            connections,
+            start: OnceLock::new(),
+            started: AtomicUsize::new(0),
+            reached: OnceLock::new(),
+        }
+    }
+
+    /// How long into the window the connection starts
+    #[allow(clippy::cast_precision_loss)]
+    fn delay(&self, id: usize) -> Duration {
+        self.window
+            .mul_f64(id as f64 / self.connections.max(1) as f64)
+    }
+
+    /// Counts a connection as started, noting when the last of them did
+    fn started(&self, start: Instant) {
+        if self.started.fetch_add(1, Relaxed) + 1 == self.connections {
+            let after = start.elapsed();
+            info!(
+                "Reached full concurrency of {} connections after {}",
+                self.connections,
+                format_duration(after.as_nanos())
+            );
+            let _ = self.reached.set(after);
+        }
+    }
+
+    /// How long it took for every connection to start, unless the run ended before
+    pub fn reached_after(&self) -> Option<Duration> {
+        self.reached.get().copied()
+    }
+}
+
+/// Holds a connection back from issuing requests until its turn in the ramp comes
+pub struct ConnectionRamp {
+    /// Unique across all connections of the run
+    id: usize,
+    schedule: Arc<RampSchedule>,
+    run: Arc<AtomicBool>,
+}
+
+impl ConnectionRamp {
+    pub fn new(id: usize, schedule: Arc<RampSchedule>, run: Arc<AtomicBool>) -> Self {
+        ConnectionRamp { id, schedule, run }
+    }
+}
+
+#[async_trait]
+impl ConnectionLifecycle for ConnectionRamp {
+    async fn after_setup(&mut self) {
+        let start = *self.schedule.start.get_or_init(Instant::now);
+        let turn = start + self.schedule.delay(self.id);
+        while self.run.load(Relaxed) {
+            let now = Instant::now();
+            if now >= turn {
+                self.schedule.started(start);
+                break;
+            }
+            sleep((turn - now).min(CHECK_INTERVAL)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::ramp::RampSchedule;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn staggers_the_connections_over_the_window() {
+        let schedule = RampSchedule::new(Duration::from_secs(10), 4);
+        let delays = (0..4).map(|id| schedule.delay(id)).collect::<Vec<_>>();
+        assert_eq!(delays, [0, 2_500, 5_000, 7_500].map(Duration::from_millis));
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            schedule.started(start);
+        }
+        assert_eq!(schedule.reached_after(), None);
+        schedule.started(start);
+        assert!(schedule.reached_after().is_some());
+    }
+}
@@ -26,7 +26,7 @@ impl RateLimit {
     }
 }
 
-#[async_trait(?Send)]
+#[async_trait]
 impl ConnectionLifecycle for RateLimit {
     async fn should_issue_request(&mut self) -> bool {
         if let Err(e) = self.limiter.check() {
@@ -0,0 +1,79 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+
+/// Picks the requests whose latencies and sizes are recorded, a pseudo-random
+/// fraction of them, to lighten the overhead of recording every request under
+/// extreme rates.
+///
+/// The picks are derived from a seed so a run can be reproduced. Requests are
+/// numbered by a counter shared with the clones of the sampler, so the requests
+/// in flight of an open loop are picked independently of each other.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    /// Requests whose hash falls below the threshold are picked
+    threshold: u64,
+    seed: u64,
+    count: Arc<AtomicU64>,
+}
+
+impl Sampler {
+    /// `rate` is the percentage of requests to pick
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn new(rate: f64, seed: u64) -> Self {
+        Sampler {
+            threshold: (rate / 100.0 * u64::MAX as f64) as u64,
+            seed,
+            count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Whether the next request is picked
+    pub fn sample(&self) -> bool {
+        let n = self.count.fetch_add(1, Relaxed);
+        self.threshold == u64::MAX || splitmix64(self.seed ^ n) < self.threshold
+    }
+}
+
+/// Scrambles the bits of `x` into a uniformly distributed hash
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::sampling::Sampler;
+
+    #[test]
+    fn samples_at_rate() {
+        let sampler = Sampler::new(10.0, 42);
+        let sampled = (0..100_000).filter(|_| sampler.sample()).count();
+        assert!(
+            (9_500..10_500).contains(&sampled),
+            "sampled {sampled} requests"
+        );
+        let all = Sampler::new(100.0, 42);
+        assert!((0..1_000).all(|_| all.sample()));
+    }
+
+    #[test]
+    fn clones_share_the_count() {
+        let a = Sampler::new(50.0, 7);
+        let b = a.clone();
+        let picks = (0..1_000)
+            .map(|i| if i % 2 == 0 { a.sample() } else { b.sample() })
+            .collect::<Vec<_>>();
+        let single = Sampler::new(50.0, 7);
+        assert_eq!(
+            picks,
+            (0..1_000).map(|_| single.sample()).collect::<Vec<_>>()
+        );
+    }
+}
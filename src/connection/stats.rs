@@ -1,62 +1,183 @@
-use crate::connection::lifecycle::ConnectionLifecycle;
-use crate::stats::WorkerStats;
+use crate::connection::lifecycle::{ConnectionHttpLifecycle, ConnectionLifecycle};
+use crate::connection::sampling::Sampler;
+use crate::slowest::SlowRequest;
+use crate::stats::{
+    Decompressed, ErrorSample, LabelStats, ReadOutcome, RequestLabel, RunStats, WorkerStats,
+    WriteOutcome,
+};
 use async_trait::async_trait;
-use hyper::{Request, Response};
-use std::sync::Arc;
+use hyper::{Method, Request, Response, StatusCode, Uri};
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::RwLock;
 use tokio::time::Instant;
 
+/// How often the stats of a connection are merged into those of its worker
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The stats of a connection that are yet to be merged into those of its worker
+struct ConnectionStats {
+    stats: WorkerStats,
+    flushed: Instant,
+}
+
 /// A stats collector that hooks into the connection lifecycle to gather
 /// related statistics
+///
+/// Each connection records its stats on its own, sparing the hot path the lock on
+/// the stats of the worker, and merges them into those every [`FLUSH_INTERVAL`]
+/// for the live report as well as once it's done.
 pub struct StatsCollector {
     pub stats: Arc<RwLock<WorkerStats>>,
+    /// Shared with the listeners forked off the connection, which all run on its task
+    local: Arc<Mutex<ConnectionStats>>,
+    /// Cleared once the run cools down to stop recording responses
+    recording: Arc<AtomicBool>,
     req_size: usize,
+    connect_start: Option<Instant>,
+    /// When the latest connection was established, which may be after the request
+    /// that needed it was due
+    connected: Option<Instant>,
     start: Option<Instant>,
+    /// When the request had been sent in full, if it was before the response came in
+    sent: Option<Instant>,
+    round_trip_time: Option<Duration>,
     time_to_first_byte: Option<Duration>,
+    label: Option<Arc<str>>,
+    /// Whether the method and URI of requests are kept to trace the slowest of them
+    trace: bool,
+    request: Option<(Method, Uri)>,
+    /// Picks the requests whose latencies and sizes are recorded, all of them without one
+    sampler: Option<Sampler>,
 }
 
 impl StatsCollector {
-    pub fn new(stats: Arc<RwLock<WorkerStats>>) -> Self {
+    /// The stats of the connection are recorded in `local`, empty stats recorded like
+    /// those of the worker
+    pub fn new(
+        stats: Arc<RwLock<WorkerStats>>,
+        local: WorkerStats,
+        recording: Arc<AtomicBool>,
+    ) -> Self {
         StatsCollector {
             stats,
+            local: Arc::new(Mutex::new(ConnectionStats {
+                stats: local,
+                flushed: Instant::now(),
+            })),
+            recording,
             req_size: 0,
+            connect_start: None,
+            connected: None,
             start: None,
+            sent: None,
+            round_trip_time: None,
             time_to_first_byte: None,
+            label: None,
+            trace: false,
+            request: None,
+            sampler: None,
         }
     }
-}
 
-#[async_trait(?Send)]
-impl ConnectionLifecycle for StatsCollector {
-    async fn before_request<T>(&mut self, _req: &Request<T>, req_size: usize) {
-        self.start.replace(Instant::now());
-        self.req_size = req_size;
+    /// Keeps what each request was so that the slowest of them can be listed
+    pub fn with_request_tracing(mut self) -> Self {
+        self.trace = true;
+        self
     }
 
-    async fn after_request(&mut self) {
-        self.time_to_first_byte
-            .replace(self.start.unwrap().elapsed());
+    /// Only records the latencies and sizes of the requests picked by the sampler, the
+    /// counters are kept for every request
+    pub fn with_sampling(mut self, sampler: Sampler) -> Self {
+        self.sampler = Some(sampler);
+        self
     }
 
-    async fn after_response<T>(&mut self, resp: &Response<T>, resp_len: usize) {
-        let mut guard = self.stats.write().await;
-        if resp.status().is_success() {
-            let round_trip_time = u64::try_from(self.start.unwrap().elapsed().as_nanos()).unwrap();
-            guard
-                .run_stats
-                .rtt_latency_hist
-                .record(round_trip_time)
-                .unwrap();
-            guard
-                .run_stats
-                .ttfb_latency_hist
-                .record(u64::try_from(self.time_to_first_byte.unwrap().as_nanos()).unwrap())
-                .unwrap();
+    /// Merges the stats recorded by the connection since the last flush into those of
+    /// the worker
+    async fn flush(&self) {
+        let stats = {
+            let mut local = self.local.lock().unwrap();
+            local.flushed = Instant::now();
+            local.stats.take()
+        };
+        self.stats.write().await.merge(&stats).unwrap();
+    }
+
+    /// Records the response of a request into the stats of the connection
+    fn record<T>(
+        &self,
+        guard: &mut WorkerStats,
+        resp: &Response<T>,
+        resp_len: usize,
+        traced: Option<((Method, Uri), Duration)>,
+    ) {
+        if let Some(traced) = traced {
+            trace_request(&mut guard.run_stats, traced, resp.status());
+        }
+        let outcome = resp.extensions().get::<ReadOutcome>();
+        match outcome {
+            Some(ReadOutcome::Hit) => guard.run_stats.hits += 1,
+            Some(ReadOutcome::NotModified) => guard.run_stats.not_modified += 1,
+            Some(ReadOutcome::Miss) => {
+                // Counted as neither a success nor an error
+                guard.run_stats.misses += 1;
+                return;
+            }
+            Some(ReadOutcome::Inconsistent) => return,
+            None => {}
+        }
+        if let Some(WriteOutcome::AlreadyExists) = resp.extensions().get::<WriteOutcome>() {
+            guard.run_stats.already_exists += 1;
+            guard.run_stats.failed_bytes_written += self.req_size;
+            guard.run_stats.failed_bytes_read += resp_len;
+            return;
+        }
+        if resp.status().is_success() || outcome == Some(&ReadOutcome::NotModified) {
+            if let Some(label) = &self.label {
+                let sigfig = guard.run_stats.rtt_latency_hist.sigfig();
+                let label_stats = guard
+                    .run_stats
+                    .labels
+                    .entry(label.clone())
+                    .or_insert_with(|| LabelStats::new(sigfig));
+                label_stats.requests += 1;
+                label_stats.bytes_written += self.req_size;
+                label_stats.bytes_read += resp_len;
+            }
+            if self.sampler.as_ref().map_or(true, Sampler::sample) {
+                self.record_sample(guard, resp_len);
+            }
+            if let Some(decompressed) = resp.extensions().get::<Decompressed>() {
+                guard.run_stats.compressed_bytes_read += decompressed.compressed;
+                guard.run_stats.decompressed_bytes_read += decompressed.decompressed;
+            }
+            self.record_phases(&mut guard.run_stats);
             guard.instant_stats.requests_issued += 1;
             guard.instant_stats.bytes_written += self.req_size;
             guard.instant_stats.bytes_read += resp_len;
         } else {
+            if let Some(label) = &self.label {
+                let sigfig = guard.run_stats.rtt_latency_hist.sigfig();
+                guard
+                    .run_stats
+                    .labels
+                    .entry(label.clone())
+                    .or_insert_with(|| LabelStats::new(sigfig))
+                    .errors += 1;
+            }
+            guard.run_stats.failed_bytes_written += self.req_size;
+            guard.run_stats.failed_bytes_read += resp_len;
+            if let Some(ErrorSample(sample)) = resp.extensions().get::<ErrorSample>() {
+                guard
+                    .run_stats
+                    .error_samples
+                    .entry(resp.status().as_u16())
+                    .or_insert_with(|| sample.clone());
+            }
             guard
                 .run_stats
                 .errors
@@ -64,6 +185,223 @@ impl ConnectionLifecycle for StatsCollector {
                 .and_modify(|v| *v += 1_usize)
                 .or_insert(1);
         }
+    }
+
+    /// Adds the time a successful request spent in each phase to the breakdown
+    fn record_phases(&self, stats: &mut RunStats) {
+        let start = self.start.unwrap();
+        let headers = start + self.round_trip_time.unwrap();
+        // Connecting on account of the request isn't part of sending it
+        let sending = self
+            .connected
+            .map_or(start, |connected| connected.max(start));
+        let sent = self
+            .sent
+            .map_or(headers, |sent| sent.clamp(sending, headers));
+        let phases = &mut stats.phases;
+        phases.requests += 1;
+        phases.send_ns += sent.saturating_duration_since(sending).as_nanos();
+        phases.wait_ns += headers.saturating_duration_since(sent).as_nanos();
+        phases.body_ns += headers.elapsed().as_nanos();
+    }
+
+    /// Records the latencies and sizes of a successful request
+    fn record_sample(&self, guard: &mut WorkerStats, resp_len: usize) {
+        let round_trip_time = u64::try_from(self.round_trip_time.unwrap().as_nanos()).unwrap();
+        let time_to_first_byte =
+            u64::try_from(self.time_to_first_byte.unwrap().as_nanos()).unwrap();
+        let time_to_last_byte = u64::try_from(self.start.unwrap().elapsed().as_nanos()).unwrap();
+        guard.interval_rtt_hist.record(round_trip_time).unwrap();
+        let stats = &mut guard.run_stats;
+        stats.rtt_latency_hist.record(round_trip_time).unwrap();
+        stats.ttfb_latency_hist.record(time_to_first_byte).unwrap();
+        stats.ttlb_latency_hist.record(time_to_last_byte).unwrap();
+        stats
+            .request_size_hist
+            .record(self.req_size as u64)
+            .unwrap();
+        stats.response_size_hist.record(resp_len as u64).unwrap();
+        if let Some(label) = &self.label {
+            let label_stats = stats.labels.get_mut(label).unwrap();
+            label_stats
+                .rtt_latency_hist
+                .record(round_trip_time)
+                .unwrap();
+            label_stats
+                .ttfb_latency_hist
+                .record(time_to_first_byte)
+                .unwrap();
+            label_stats
+                .ttlb_latency_hist
+                .record(time_to_last_byte)
+                .unwrap();
+        }
+    }
+}
+
+#[async_trait]
+impl ConnectionLifecycle for StatsCollector {
+    fn fork(&self) -> Option<ConnectionHttpLifecycle> {
+        Some(ConnectionHttpLifecycle::Stats(StatsCollector {
+            stats: self.stats.clone(),
+            local: self.local.clone(),
+            recording: self.recording.clone(),
+            req_size: 0,
+            connect_start: None,
+            connected: None,
+            start: None,
+            sent: None,
+            round_trip_time: None,
+            time_to_first_byte: None,
+            label: None,
+            trace: self.trace,
+            request: None,
+            sampler: self.sampler.clone(),
+        }))
+    }
+
+    async fn before_connect(&mut self) {
+        self.connect_start.replace(Instant::now());
+    }
+
+    async fn after_resolve(&mut self, resolution_time: Duration) {
+        let mut guard = self.local.lock().unwrap();
+        guard
+            .stats
+            .run_stats
+            .dns_resolution_latency_hist
+            .record(u64::try_from(resolution_time.as_nanos()).unwrap())
+            .unwrap();
+        drop(guard);
+    }
+
+    async fn after_port_exhaustion(&mut self) {
+        self.local.lock().unwrap().stats.run_stats.port_exhaustions += 1;
+    }
+
+    async fn after_connect(&mut self, address: &SocketAddr, reconnect: bool) {
+        let connected = Instant::now();
+        self.connected.replace(connected);
+        let setup_time = connected - self.connect_start.unwrap();
+        let mut guard = self.local.lock().unwrap();
+        let stats = &mut guard.stats.run_stats;
+        stats
+            .conn_setup_latency_hist
+            .record(u64::try_from(setup_time.as_nanos()).unwrap())
+            .unwrap();
+        stats.phases.connect_ns += setup_time.as_nanos();
+        stats.connections_established += 1;
+        if address.is_ipv6() {
+            stats.ipv6_connections += 1;
+        }
+        if reconnect {
+            stats.reconnections += 1;
+        }
         drop(guard);
     }
+
+    async fn before_request<T: Sync>(&mut self, req: &Request<T>, req_size: usize, start: Instant) {
+        self.start.replace(start);
+        self.sent = None;
+        self.req_size = req_size;
+        self.label = req.extensions().get::<RequestLabel>().map(|l| l.0.clone());
+        if self.trace {
+            self.request = Some((req.method().clone(), req.uri().clone()));
+        }
+    }
+
+    async fn after_send(&mut self, sent: Instant) {
+        self.sent.replace(sent);
+    }
+
+    async fn after_request(&mut self) {
+        self.round_trip_time.replace(self.start.unwrap().elapsed());
+    }
+
+    async fn after_first_byte(&mut self) {
+        self.time_to_first_byte
+            .replace(self.start.unwrap().elapsed());
+    }
+
+    async fn after_response<T: Sync>(&mut self, resp: &Response<T>, resp_len: usize) {
+        if !self.recording.load(Relaxed) {
+            return;
+        }
+        let traced = self.request.take().zip(self.round_trip_time);
+        let flush = {
+            let mut guard = self.local.lock().unwrap();
+            self.record(&mut guard.stats, resp, resp_len, traced);
+            guard.flushed.elapsed() >= FLUSH_INTERVAL
+        };
+        if flush {
+            self.flush().await;
+        }
+    }
+
+    async fn after_run(&mut self) {
+        self.flush().await;
+    }
+}
+
+/// Keeps the request that just completed if it's among the slowest
+fn trace_request(stats: &mut RunStats, traced: ((Method, Uri), Duration), status: StatusCode) {
+    let ((method, uri), rtt) = traced;
+    let rtt_ns = u64::try_from(rtt.as_nanos()).unwrap();
+    if stats.slowest.admits(rtt_ns) {
+        stats.slowest.record(SlowRequest {
+            rtt_ns,
+            method: method.to_string(),
+            uri: uri.to_string(),
+            status: status.as_u16(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::lifecycle::ConnectionLifecycle;
+    use crate::connection::stats::StatsCollector;
+    use crate::stats::{ReadOutcome, WorkerStats};
+    use hyper::{Request, Response, StatusCode};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use tokio::time::Instant;
+
+    #[test]
+    fn counts_reads_of_missing_objects_apart_from_errors() {
+        let stats = Arc::new(RwLock::new(WorkerStats::new(3)));
+        let mut collector = StatsCollector::new(
+            stats.clone(),
+            WorkerStats::new(3),
+            Arc::new(AtomicBool::new(true)),
+        );
+        futures::executor::block_on(async {
+            for outcome in [
+                None,
+                Some(ReadOutcome::Miss),
+                Some(ReadOutcome::Inconsistent),
+            ] {
+                collector
+                    .before_request(&Request::new(()), 10, Instant::now())
+                    .await;
+                collector.after_request().await;
+                collector.after_first_byte().await;
+                let mut resp = Response::new(());
+                *resp.status_mut() = StatusCode::NOT_FOUND;
+                if let Some(outcome) = outcome {
+                    resp.extensions_mut().insert(outcome);
+                }
+                collector.after_response(&resp, 20).await;
+            }
+            collector.after_run().await;
+        });
+
+        // Only the read that wasn't expected to miss is an error
+        let stats = stats.blocking_read();
+        assert_eq!(stats.run_stats.errors.get(&404), Some(&1));
+        assert_eq!(stats.run_stats.misses, 1);
+        assert_eq!(stats.run_stats.failed_bytes_written, 10);
+        assert_eq!(stats.run_stats.failed_bytes_read, 20);
+    }
 }
@@ -1,14 +1,17 @@
+use crate::connection::body::ResponseBody;
 use anyhow::Result;
 use async_trait::async_trait;
-use hyper::body::{Body, Incoming};
+use hyper::body::Body;
 use hyper::http::request;
 use hyper::{Request, Response};
 
+pub mod bench;
 pub mod s3;
+pub mod script;
 pub mod simple;
 
 /// An engine for generating http traffic to be sent to a HTTP server via an [crate::connection::Connection]
-#[async_trait(? Send)]
+#[async_trait]
 pub trait Engine<Req>
 where
     Req: Body,
@@ -24,9 +27,31 @@ where
     /// Request builder will already fill in the following:
     /// - Uri
     /// - Authority (Header, derived from url)
+    ///
+    /// Engines can attach a [crate::stats::RequestLabel] extension to the request
+    /// to have the summary break down the stats of their requests by label.
     async fn request(&mut self, req: request::Builder) -> Result<(Request<Req>, usize)>;
     /// Parses a response returning the size of the read payload
-    async fn response(&mut self, resp: &mut Response<Incoming>) -> Result<usize>;
+    async fn response(&mut self, resp: &mut Response<ResponseBody>) -> Result<usize>;
+    /// Whether the engine has no requests left to issue, ending the run of its
+    /// connection ahead of the rest
+    fn finished(&self) -> bool {
+        false
+    }
+    /// Builds up a no-op request to warm up a connection with before the run starts,
+    /// if the engine has one
+    ///
+    /// Its response is discarded and doesn't count towards the stats of the run
+    async fn warmup_request(&mut self, _req: request::Builder) -> Result<Option<Request<Req>>> {
+        Ok(None)
+    }
+    /// Builds up the next request to clean up after the run with (e.g. deleting the
+    /// objects that were written), returning `None` once there's nothing left to clean up
+    ///
+    /// Called repeatedly at the end of a run, before [Engine::cleanup]
+    async fn cleanup_request(&mut self, _req: request::Builder) -> Result<Option<Request<Req>>> {
+        Ok(None)
+    }
     /// Performs whatever cleanup is necessary for the engine before exiting
     ///
     /// Called once at the end of a run
@@ -0,0 +1,187 @@
+//! # Bench Engine
+//!
+//! Issues the smallest requests there are, to measure the overhead of loaded
+//! itself rather than that of the server, typically against the built-in
+//! [`server::LoopbackServer`].
+
+pub mod server;
+
+use crate::connection::body::ResponseBody;
+use crate::engine::Engine;
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::http::request::Builder;
+use hyper::{Method, Request, Response};
+
+/// An engine issuing body-less GETs ad nauseam
+pub struct BenchEngine;
+
+#[async_trait]
+impl Engine<Empty<Bytes>> for BenchEngine {
+    fn name<'a>(&self) -> &'a str {
+        "Bench"
+    }
+
+    async fn setup(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn request(&mut self, req: Builder) -> Result<(Request<Empty<Bytes>>, usize)> {
+        Ok((req.method(Method::GET).body(Empty::new())?, 0))
+    }
+
+    async fn response(&mut self, resp: &mut Response<ResponseBody>) -> Result<usize> {
+        let mut read = 0;
+        while let Some(next) = resp.frame().await {
+            if let Some(d) = next?.data_ref() {
+                read += d.len();
+            }
+        }
+        Ok(read)
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{BackoffJitter, BenchArgs, ConnectionMode, Engine, IpVersion};
+    use crate::connection::latency::LatencyDistribution;
+    use crate::connection::{ConnectionOptions, SocketOptions};
+    use crate::engine::bench::server::LoopbackServer;
+    use crate::stats::WorkerStats;
+    use crate::worker::{CompletionCondition, Worker};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::RwLock;
+
+    fn options(mode: ConnectionMode) -> ConnectionOptions {
+        ConnectionOptions {
+            mode,
+            max_requests: None,
+            schedule: None,
+            http10: false,
+            connection_close: false,
+            absolute_uri: false,
+            authorization: None,
+            socket: SocketOptions {
+                nodelay: true,
+                reuse_port: false,
+                send_buffer: None,
+                recv_buffer: None,
+                ip_version: IpVersion::Auto,
+                bind_addresses: vec![],
+            },
+            dry_run: None,
+            reconnect_backoff: Duration::from_millis(10),
+            backoff_jitter: BackoffJitter::None,
+            fault_drop_rate: None,
+            inject_latency: None,
+            read_limit: None,
+            decompress: false,
+            sample_rate: None,
+            proxy: None,
+            dns_cache_ttl: Duration::from_secs(60),
+            warmup: None,
+        }
+    }
+
+    /// Runs a worker against the loopback server, returning the stats it recorded
+    /// alongside the server
+    fn run_worker(
+        options: ConnectionOptions,
+        connections: usize,
+        requests: usize,
+    ) -> (Arc<RwLock<WorkerStats>>, LoopbackServer) {
+        let server = LoopbackServer::start("127.0.0.1:0", 16).unwrap();
+        let stats = Arc::new(RwLock::new(WorkerStats::new(3)));
+        let mut worker = Worker {
+            worker_id: 0,
+            run_flag: Arc::new(AtomicBool::new(true)),
+            stats: stats.clone(),
+            rate_limit: None,
+            connection_options: options,
+            num_workers: 1,
+            num_connections: connections,
+            concurrency_limit: None,
+            connection_ramp: None,
+        };
+        let engine = Engine::Bench(BenchArgs {
+            serve: true,
+            response_size: 16,
+        });
+        let info = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(worker.run(
+                engine,
+                vec![format!("http://{}", server.addr()); connections],
+                "seed".to_string(),
+                Some(CompletionCondition::NumRequests(requests)),
+            ))
+            .unwrap();
+        assert_eq!(info.run_infos.len(), connections);
+        (stats, server)
+    }
+
+    /// Runs a worker against the loopback server, returning the requests reported
+    /// in its stats and those the server received
+    fn run(mode: ConnectionMode, connections: usize, requests: usize) -> (usize, usize) {
+        let (stats, server) = run_worker(options(mode), connections, requests);
+        let stats = stats.blocking_read();
+        assert_eq!(
+            stats.instant_stats.bytes_read,
+            stats.instant_stats.requests_issued * 16
+        );
+        (stats.instant_stats.requests_issued, server.requests())
+    }
+
+    #[test]
+    fn reports_the_requests_the_server_received() {
+        assert_eq!(run(ConnectionMode::Reuse, 4, 250), (250, 250));
+        assert_eq!(run(ConnectionMode::PerRequest, 3, 31), (31, 31));
+    }
+
+    #[test]
+    fn reports_percentiles_tracking_the_latency_injected() {
+        let (min, max) = (Duration::from_millis(10), Duration::from_millis(30));
+        let options = ConnectionOptions {
+            inject_latency: Some(LatencyDistribution::Uniform { min, max }),
+            ..options(ConnectionMode::Reuse)
+        };
+        let (stats, _server) = run_worker(options, 4, 200);
+        let stats = stats.blocking_read();
+        let rtt = &stats.run_stats.rtt_latency_hist;
+        assert_eq!(rtt.len(), 200);
+
+        // Sleeps never end early, so no percentile falls short of that of the latency
+        // injected, give or take the sampling. How far they overshoot it depends on how
+        // busy the machine is, the tail by hundreds of milliseconds on an oversubscribed
+        // one, so the upper bound only catches the latencies being off by orders of
+        // magnitude
+        assert!(rtt.min() >= 10_000_000, "{}", rtt.min());
+        let mut last = Duration::ZERO;
+        for quantile in [0.1, 0.5, 0.9, 0.99] {
+            let injected = min + (max - min).mul_f64(quantile);
+            let reported = Duration::from_nanos(rtt.value_at_quantile(quantile));
+            assert!(
+                reported + Duration::from_millis(2) >= injected
+                    && reported <= injected + Duration::from_secs(1),
+                "p{}: reported {reported:?}, injected {injected:?}",
+                quantile * 100.0
+            );
+            assert!(
+                reported >= last,
+                "p{}: {reported:?} < {last:?}",
+                quantile * 100.0
+            );
+            last = reported;
+        }
+    }
+}
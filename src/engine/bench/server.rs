@@ -0,0 +1,92 @@
+//! # Loopback Server
+//!
+//! A minimal HTTP server for the bench engine to target, answering every request
+//! with the same response and counting the requests it received.
+
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use log::debug;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Arc;
+use std::thread;
+
+pub struct LoopbackServer {
+    addr: SocketAddr,
+    requests: Arc<AtomicUsize>,
+}
+
+impl LoopbackServer {
+    /// Starts serving on `addr`, on a thread of its own so the server isn't scheduled
+    /// on the runtimes of the workers, with responses of `response_size` bytes
+    ///
+    /// The server runs until loaded exits.
+    pub fn start(addr: impl ToSocketAddrs, response_size: usize) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let body = Bytes::from(vec![0; response_size]);
+        let counter = requests.clone();
+        thread::Builder::new()
+            .name("Loopback server".to_string())
+            .spawn(move || {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("build runtime")
+                    .block_on(serve(listener, body, counter));
+            })?;
+
+        Ok(LoopbackServer { addr, requests })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The number of requests received so far
+    pub fn requests(&self) -> usize {
+        self.requests.load(Relaxed)
+    }
+}
+
+async fn serve(listener: TcpListener, body: Bytes, requests: Arc<AtomicUsize>) {
+    let listener = tokio::net::TcpListener::from_std(listener).expect("register listener");
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                debug!("Loopback server failed to accept a connection: {e}");
+                continue;
+            }
+        };
+        let body = body.clone();
+        let requests = requests.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req: Request<Incoming>| {
+                let body = body.clone();
+                let requests = requests.clone();
+                async move {
+                    req.into_body().collect().await?;
+                    requests.fetch_add(1, Relaxed);
+                    Ok::<_, hyper::Error>(Response::new(Full::new(body)))
+                }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Loopback connection failed: {e}");
+            }
+        });
+    }
+}
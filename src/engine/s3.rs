@@ -1,10 +1,15 @@
-mod traffic;
+pub mod popularity;
+pub mod ranges;
+pub mod size;
+pub mod traffic;
 pub mod uri;
 
-use crate::cli::TrafficPattern;
+use crate::cli::{ServerSideEncryption, TrafficPattern};
+use crate::connection::body::ResponseBody;
 use crate::engine::Engine;
-use crate::stream::checksum::Checksum;
-use crate::stream::StreamProvider;
+use crate::stats::{ReadOutcome, RequestLabel, WorkerStats, WriteOutcome};
+use crate::stream::checksum::{Checksum, ChecksumHasher};
+use crate::stream::{StreamChecksum, StreamProvider};
 use crate::util;
 use anyhow::{bail, Result};
 use async_trait::async_trait;
@@ -12,12 +17,19 @@ use bytes::Buf;
 use chrono::Utc;
 use futures::Stream;
 use http_body_util::{BodyExt, StreamBody};
-use hyper::body::{Frame, Incoming};
+use hyper::body::Frame;
+use hyper::header::HeaderValue;
 use hyper::http::request;
-use hyper::{Request, Response};
-use log::warn;
-use std::cell::RefCell;
+use hyper::{Request, Response, StatusCode, Uri};
+use log::{debug, warn};
+use popularity::KeySampler;
+use ranges::RandomRanges;
+use size::SizeSampler;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
 use traffic::{TrafficState, TrafficStateMachine};
 use uri::UriProvider;
 
@@ -27,17 +39,73 @@ use uri::UriProvider;
 /// This S3 engine does not make use of any provided S3 client but instead manually
 /// crafts the requests to ensure control over the payload and push as much load
 /// as possible.
+#[allow(clippy::struct_excessive_bools)]
 pub struct S3Engine<P, S>
 where
     P: StreamProvider<S>,
     S: Stream,
 {
-    stream_supplier: RefCell<P>,
-    object_size: usize,
+    stream_supplier: P,
+    sizes: SizeSampler,
     phantom: PhantomData<S>,
     checksum_algo: Option<Checksum>,
+    expect_continue: bool,
     traffic_cop: TrafficStateMachine,
     last_traffic_state: Option<TrafficState>,
+    /// The uri and size of the last object PUT
+    last_put: Option<(Uri, usize)>,
+    /// The size the object of the last GET is expected to have, if known
+    expected_size: Option<usize>,
+    /// The digest sent along with the last PUT, if any
+    last_digest: Option<String>,
+    /// Where to record integrity check failures, only set when verifying
+    verify_stats: Option<Arc<RwLock<WorkerStats>>>,
+    /// Where to record the checksums of the objects read, only set when verifying them
+    checksum_stats: Option<Arc<RwLock<WorkerStats>>>,
+    /// Where to add up the distinct keys PUT once the connection is done
+    key_stats: Option<Arc<RwLock<WorkerStats>>>,
+    /// Labels breaking the stats down by operation, only set when mixing operations
+    op_labels: Option<OpLabels>,
+    /// Whether GETs of objects that don't exist are counted as misses rather than errors
+    allow_misses: bool,
+    /// Whether PUTs are conditional on the object not existing yet
+    if_none_match: bool,
+    /// Whether PUT bodies are sent chunked, without a `Content-Length`
+    chunked: bool,
+    /// The distinct keys successfully PUT that are yet to be deleted, only set when
+    /// cleaning up
+    cleanup_keys: Option<HashSet<Uri>>,
+    /// The `Content-Type` of the objects PUT, and the `Accept` of GETs
+    content_type: HeaderValue,
+    /// Headers attached to every PUT, e.g. for encryption and the storage class
+    put_headers: Vec<(&'static str, HeaderValue)>,
+    /// Checks the objects PUT are read back as written, only set with a delay
+    read_after_write: Option<ReadAfterWrite>,
+    /// Picks the ranges GETs read of the objects, only set when reading random ranges
+    random_ranges: Option<RandomRanges>,
+}
+
+struct OpLabels {
+    put: RequestLabel,
+    get: RequestLabel,
+    head: RequestLabel,
+}
+
+/// The objects that were PUT, waiting to be read back once the delay has passed
+struct ReadAfterWrite {
+    delay: Duration,
+    /// Where to record the objects read back inconsistently
+    stats: Arc<RwLock<WorkerStats>>,
+    /// Keyed by uri, a key written again before it's read back keeps the latest object
+    written: HashMap<Uri, WrittenObject>,
+    /// The object the last GET read back, if it was one
+    reading: Option<WrittenObject>,
+}
+
+/// What a successful PUT wrote
+struct WrittenObject {
+    size: usize,
+    etag: Option<String>,
 }
 
 impl<P, S> S3Engine<P, S>
@@ -48,26 +116,336 @@ where
     pub fn new(
         stream_supplier: P,
         uri_supplier: UriProvider,
-        object_size: usize,
+        sizes: SizeSampler,
         checksum_algo: Option<Checksum>,
+        expect_continue: bool,
         traffic_pattern: TrafficPattern,
+        key_sampler: Option<KeySampler>,
     ) -> Self {
-        S3Engine {
-            stream_supplier: RefCell::new(stream_supplier),
-            object_size,
+        let mixes_ops = matches!(
+            traffic_pattern,
+            TrafficPattern::Both | TrafficPattern::ReadModifyWrite | TrafficPattern::ConditionalGet
+        );
+        let engine = S3Engine {
+            stream_supplier,
+            sizes,
             phantom: PhantomData,
             checksum_algo,
-            traffic_cop: TrafficStateMachine::new(traffic_pattern, uri_supplier),
+            expect_continue,
+            traffic_cop: TrafficStateMachine::new(traffic_pattern, uri_supplier, key_sampler),
             last_traffic_state: None,
+            last_put: None,
+            expected_size: None,
+            last_digest: None,
+            verify_stats: None,
+            checksum_stats: None,
+            key_stats: None,
+            op_labels: None,
+            allow_misses: false,
+            if_none_match: false,
+            chunked: false,
+            cleanup_keys: None,
+            content_type: HeaderValue::from_static("application/octet-stream"),
+            put_headers: vec![],
+            read_after_write: None,
+            random_ranges: None,
+        };
+        if mixes_ops {
+            engine.with_op_labels()
+        } else {
+            engine
+        }
+    }
+
+    /// Breaks the stats down by operation, for when operations are mixed within the
+    /// run (if not within the connection)
+    pub fn with_op_labels(mut self) -> Self {
+        self.op_labels = Some(OpLabels {
+            put: RequestLabel("PUT".into()),
+            get: RequestLabel("GET".into()),
+            head: RequestLabel("HEAD".into()),
+        });
+        self
+    }
+
+    /// Verifies the integrity of the PUT objects, recording mismatches in the stats
+    pub fn with_verification(mut self, stats: Arc<RwLock<WorkerStats>>) -> Self {
+        self.verify_stats = Some(stats);
+        self
+    }
+
+    /// Verifies the objects read by GETs against the checksum the server returns with them,
+    /// recording those verified and mismatched in the stats
+    pub fn with_checksum_verification(mut self, stats: Arc<RwLock<WorkerStats>>) -> Self {
+        self.checksum_stats = Some(stats);
+        self
+    }
+
+    /// Adds the number of distinct keys PUT by the connection to the stats once it's done
+    pub fn with_key_count(mut self, stats: Arc<RwLock<WorkerStats>>) -> Self {
+        self.key_stats = Some(stats);
+        self
+    }
+
+    /// Reads back each object PUT by the `Both` pattern once `delay` has passed since it
+    /// was written, recording those found missing or stale in the stats
+    pub fn with_read_after_write(
+        mut self,
+        delay: Duration,
+        stats: Arc<RwLock<WorkerStats>>,
+    ) -> Self {
+        self.traffic_cop = self.traffic_cop.with_read_after_write_delay(delay);
+        self.read_after_write = Some(ReadAfterWrite {
+            delay,
+            stats,
+            written: HashMap::new(),
+            reading: None,
+        });
+        self
+    }
+
+    /// Has GETs read ranges at random offsets within the objects rather than the whole
+    /// objects, which must all have the same size
+    pub fn with_random_ranges(mut self, ranges: RandomRanges) -> Self {
+        self.random_ranges = Some(ranges);
+        self
+    }
+
+    /// Counts GETs that find no object as misses rather than failing them, for
+    /// reading from a keyspace that wasn't fully written
+    pub fn with_misses_allowed(mut self) -> Self {
+        self.allow_misses = true;
+        self
+    }
+
+    /// Sends the PUT bodies with `Transfer-Encoding: chunked`, leaving out the
+    /// `Content-Length` so the server can't rely on knowing the size up front
+    pub fn with_chunked_uploads(mut self) -> Self {
+        self.chunked = true;
+        self
+    }
+
+    /// Makes PUTs conditional on the object not existing yet, counting those rejected
+    /// as it already exists rather than failing them
+    pub fn with_if_none_match(mut self) -> Self {
+        self.if_none_match = true;
+        self.put_headers
+            .push(("If-None-Match", HeaderValue::from_static("*")));
+        self
+    }
+
+    /// Deletes the objects PUT during the run on cleanup
+    ///
+    /// The keys of the successful PUTs are kept until then, whichever order they were
+    /// PUT in.
+    pub fn with_cleanup(mut self) -> Self {
+        self.cleanup_keys = Some(HashSet::new());
+        self
+    }
+
+    /// Encrypts the objects being PUT server side, with the given KMS key when
+    /// encrypting with `aws:kms`
+    pub fn with_encryption(
+        mut self,
+        sse: ServerSideEncryption,
+        kms_key_id: Option<&str>,
+    ) -> Result<Self> {
+        self.put_headers.push((
+            "x-amz-server-side-encryption",
+            HeaderValue::from_static(sse.header_value()),
+        ));
+        if let Some(key_id) = kms_key_id {
+            self.put_headers.push((
+                "x-amz-server-side-encryption-aws-kms-key-id",
+                HeaderValue::from_str(key_id)?,
+            ));
+        }
+        Ok(self)
+    }
+
+    /// Declares the objects being PUT of the given type, rather than as opaque bytes,
+    /// and has GETs accept it
+    pub fn with_content_type(mut self, content_type: HeaderValue) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    /// Stores the objects being PUT in the given storage class
+    pub fn with_storage_class(mut self, storage_class: &str) -> Result<Self> {
+        self.put_headers
+            .push(("x-amz-storage-class", HeaderValue::from_str(storage_class)?));
+        Ok(self)
+    }
+
+    /// Tags the objects being PUT with the given key-value pairs
+    pub fn with_tagging(mut self, tags: &[(String, String)]) -> Result<Self> {
+        self.put_headers
+            .push(("x-amz-tagging", HeaderValue::from_str(&encode_tags(tags))?));
+        Ok(self)
+    }
+
+    /// Checks the `ETag` of a successful PUT against the MD5 digest that was sent
+    async fn verify_put(&mut self, resp: &Response<ResponseBody>) {
+        let (Some(stats), Some(Checksum::Md5), Some(digest)) =
+            (&self.verify_stats, self.checksum_algo, &self.last_digest)
+        else {
+            return;
+        };
+
+        let etag = etag(resp);
+        if !etag.is_some_and(|etag| etag.eq_ignore_ascii_case(digest)) {
+            warn!("ETag {etag:?} doesn't match the MD5 digest {digest} of the PUT object");
+            stats.write().await.run_stats.etag_mismatches += 1;
+        }
+    }
+
+    /// Starts calculating the checksum of the object being read, when verifying it
+    fn read_hasher(&self) -> Option<ChecksumHasher> {
+        let reading = matches!(
+            self.last_traffic_state,
+            Some(TrafficState::Get { .. } | TrafficState::ConditionalGet { .. })
+        );
+        self.checksum_algo
+            .filter(|_| reading && self.checksum_stats.is_some())
+            .map(Checksum::hasher)
+    }
+
+    /// Reads the object the traffic cop asked for, or a random range of it
+    fn get_request(&mut self, req: request::Builder) -> Result<Request<StreamBody<S>>> {
+        let Some(TrafficState::Get { uri }) = &self.last_traffic_state else {
+            unreachable!("Not reading an object");
+        };
+        let reading_back = match &mut self.read_after_write {
+            Some(raw) => {
+                raw.reading = raw.written.remove(uri);
+                raw.reading.is_some()
+            }
+            None => false,
+        };
+        // Objects we just PUT are of a known size, otherwise they're only known
+        // when all objects have the same size. Those read back after a delay have
+        // their size checked along with the rest
+        self.expected_size = match &self.last_put {
+            _ if reading_back => None,
+            Some((put_uri, size)) if put_uri == uri => Some(*size),
+            _ => self.sizes.fixed(),
+        };
+        let req = self.get_headers(req);
+        let req = match (&mut self.random_ranges, self.sizes.fixed()) {
+            (Some(ranges), Some(object_size)) => {
+                let range = ranges.next(uri, object_size);
+                self.expected_size = Some(range.len);
+                req.header(hyper::header::RANGE, range.header_value())
+            }
+            _ => req,
+        };
+        let label = self.op_labels.as_ref().map(|labels| &labels.get);
+        read_request(req, uri, "GET", label, self.stream_supplier.empty())
+    }
+
+    /// Accepts the type of the objects PUT, and asks the server to return the checksum of
+    /// the object when verifying it
+    fn get_headers(&self, req: request::Builder) -> request::Builder {
+        let req = req.header(hyper::header::ACCEPT, &self.content_type);
+        if self.checksum_stats.is_some() {
+            req.header("x-amz-checksum-mode", "ENABLED")
+        } else {
+            req
+        }
+    }
+
+    /// Checks the checksum calculated over an object read against the one the server
+    /// returned with it, if it did
+    async fn verify_checksum(&mut self, resp: &Response<ResponseBody>, hasher: ChecksumHasher) {
+        let (Some(stats), Some(checksum)) = (&self.checksum_stats, self.checksum_algo) else {
+            return;
+        };
+        // Neither part of an object nor the parts of a multipart upload (`<checksum>-<parts>`)
+        // add up to the checksum of the whole object
+        let advertised = resp
+            .headers()
+            .get(checksum.header_name())
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.contains('-'));
+        let Some(advertised) = advertised.filter(|_| {
+            resp.status() != StatusCode::PARTIAL_CONTENT && !resp.body().is_truncated()
+        }) else {
+            return;
+        };
+        let calculated = hasher.finalize();
+        let mut stats = stats.write().await;
+        if checksum.matches(&calculated, advertised) {
+            stats.run_stats.checksums_verified += 1;
+        } else {
+            warn!(
+                "Checksum {advertised} returned doesn't match the {calculated} of the object read"
+            );
+            stats.run_stats.checksum_mismatches += 1;
+        }
+    }
+
+    /// Keeps what a successful PUT wrote, to check it's read back the same and count the
+    /// keys written
+    fn record_written(&mut self, resp: &Response<ResponseBody>) {
+        let Some((uri, size)) = &self.last_put else {
+            return;
+        };
+        if let Some(raw) = &mut self.read_after_write {
+            let object = WrittenObject {
+                size: *size,
+                etag: etag(resp).map(str::to_string),
+            };
+            raw.written.insert(uri.clone(), object);
         }
+        if let Some(keys) = &mut self.cleanup_keys {
+            keys.insert(uri.clone());
+        }
+        self.traffic_cop.written(uri.clone());
+    }
+
+    /// Checks an object read back once the delay has passed is the one that was written,
+    /// recording it as inconsistent when it's missing or stale
+    ///
+    /// Returns whether it was missing
+    async fn check_read_back(
+        &mut self,
+        object: WrittenObject,
+        resp: &Response<ResponseBody>,
+        read: usize,
+    ) -> bool {
+        let (Some(raw), Some(TrafficState::Get { uri })) =
+            (&self.read_after_write, &self.last_traffic_state)
+        else {
+            return false;
+        };
+        let missing = resp.status() == StatusCode::NOT_FOUND;
+        let inconsistency = match (etag(resp), &object.etag) {
+            _ if missing => Some("missing (404)".to_string()),
+            _ if !resp.status().is_success() => None,
+            _ if !resp.body().is_truncated() && read != object.size => {
+                Some(format!("stale, {read} bytes rather than {}", object.size))
+            }
+            (Some(etag), Some(written)) if etag != written => {
+                Some(format!("stale, ETag {etag} rather than {written}"))
+            }
+            _ => None,
+        };
+        if let Some(inconsistency) = inconsistency {
+            warn!(
+                "Read {uri} back {inconsistency}, at least {:?} after writing it",
+                raw.delay
+            );
+            raw.stats.write().await.run_stats.inconsistent_reads += 1;
+        }
+        missing
     }
 }
 
-#[async_trait(? Send)]
+#[async_trait]
 impl<P, S, D, E> Engine<StreamBody<S>> for S3Engine<P, S>
 where
     P: StreamProvider<S>,
-    S: Stream<Item = Result<Frame<D>, E>>,
+    S: Stream<Item = Result<Frame<D>, E>> + Send,
     D: Buf,
 {
     fn name<'a>(&self) -> &'a str {
@@ -78,77 +456,206 @@ where
         Ok(())
     }
 
-    #[allow(clippy::await_holding_refcell_ref)]
     async fn request(&mut self, req: request::Builder) -> Result<(Request<StreamBody<S>>, usize)> {
-        self.last_traffic_state = Some(self.traffic_cop.next());
+        // Ranges are read from the same object until all of them were
+        self.last_traffic_state = Some(
+            match self.random_ranges.as_mut().and_then(RandomRanges::repeat) {
+                Some(uri) => TrafficState::Get { uri },
+                None => self.traffic_cop.next(),
+            },
+        );
         match self.last_traffic_state.as_ref().unwrap() {
             TrafficState::Put { uri } => {
+                let size = self
+                    .stream_supplier
+                    .next_len()
+                    .unwrap_or_else(|| self.sizes.next());
+                self.last_put = Some((uri.clone(), size));
+                let req = with_host(req, uri);
+                self.last_digest = None;
+                let mut encoded_len = None;
                 let (req, stream) = match &self.checksum_algo {
-                    None => (req, self.stream_supplier.borrow_mut().new_stream()),
-                    Some(c) => {
-                        let (stream, digest) = self
-                            .stream_supplier
-                            .borrow_mut()
-                            .new_stream_with_checksum(c)
-                            .await;
-
-                        let req = match c {
-                            Checksum::Md5 => req.header("Content-MD5", digest),
-                            Checksum::Crc32 => req.header("x-amz-checksum-crc32", digest),
-                            Checksum::Crc32c => req.header("x-amz-checksum-crc32c", digest),
-                            Checksum::Sha1 => req.header("x-amz-checksum-sha1", digest),
-                            Checksum::Sha2 => req.header("x-amz-checksum-sha256", digest),
-                        };
-                        (req, stream)
-                    }
+                    None => (req, self.stream_supplier.new_stream(size)),
+                    Some(c) => match self.stream_supplier.new_stream_with_checksum(c, size).await {
+                        (stream, StreamChecksum::Header(digest)) => {
+                            self.last_digest = Some(digest.clone());
+                            (req.header(c.header_name(), digest), stream)
+                        }
+                        (stream, StreamChecksum::Trailer { encoded_len: len }) => {
+                            encoded_len = Some(len);
+                            let req = req
+                                .header(hyper::header::CONTENT_ENCODING, "aws-chunked")
+                                .header(
+                                    "x-amz-content-sha256",
+                                    "STREAMING-UNSIGNED-PAYLOAD-TRAILER",
+                                )
+                                .header("x-amz-decoded-content-length", size.to_string())
+                                .header("x-amz-trailer", c.header_name());
+                            (req, stream)
+                        }
+                    },
                 };
 
+                let req = if self.expect_continue {
+                    req.header(hyper::header::EXPECT, "100-continue")
+                } else {
+                    req
+                };
+
+                let req = match &self.op_labels {
+                    Some(labels) => req.extension(labels.put.clone()),
+                    None => req,
+                };
+
+                // Without a length, hyper frames the body of unknown size as chunked. An
+                // `aws-chunked` body is sent as is, its length being that once encoded
+                let req = if self.chunked {
+                    req
+                } else {
+                    let len = encoded_len.unwrap_or(size);
+                    req.header(hyper::header::CONTENT_LENGTH, len.to_string())
+                };
+
+                let req = self
+                    .put_headers
+                    .iter()
+                    .fold(req, |req, (name, value)| req.header(*name, value));
+
                 let req = req
                     .uri(uri)
                     .method("PUT")
                     .header(hyper::header::USER_AGENT, util::user_agent())
-                    .header(hyper::header::CONTENT_TYPE, "application/octet-stream")
-                    .header(hyper::header::CONTENT_LENGTH, self.object_size.to_string())
+                    .header(hyper::header::CONTENT_TYPE, &self.content_type)
                     .header(
                         "X-Amz-Date",
                         Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
                     )
                     .body(StreamBody::new(stream))?;
 
-                Ok((req, self.object_size))
+                Ok((req, size))
             }
-            TrafficState::Get { uri } => {
-                let req = req
-                    .uri(uri)
-                    .method("GET")
-                    .header(hyper::header::ACCEPT, "application/octet-stream")
-                    .body(StreamBody::new(self.stream_supplier.borrow_mut().empty()))?;
+            TrafficState::Get { .. } => Ok((self.get_request(req)?, 0)),
+            TrafficState::Head { uri } => {
+                let label = self.op_labels.as_ref().map(|labels| &labels.head);
+                let req = read_request(req, uri, "HEAD", label, self.stream_supplier.empty())?;
+                Ok((req, 0))
+            }
+            TrafficState::ConditionalGet { uri, etag } => {
+                self.expected_size = self.sizes.fixed();
+                let req = match etag {
+                    Some(etag) => req.header(hyper::header::IF_NONE_MATCH, etag),
+                    None => req,
+                };
+                let req = self.get_headers(req);
+                let label = self.op_labels.as_ref().map(|labels| &labels.get);
+                let req = read_request(req, uri, "GET", label, self.stream_supplier.empty())?;
                 Ok((req, 0))
             }
         }
     }
 
-    async fn response(&mut self, resp: &mut Response<Incoming>) -> Result<usize> {
+    async fn response(&mut self, resp: &mut Response<ResponseBody>) -> Result<usize> {
         let mut read = 0;
+        let mut hasher = self.read_hasher();
         while let Some(next) = resp.frame().await {
             let frame = next.unwrap();
             if let Some(d) = frame.data_ref() {
                 read += d.len();
+                if let Some(hasher) = &mut hasher {
+                    hasher.update(d);
+                }
+            }
+        }
+
+        if let Some(object) = self
+            .read_after_write
+            .as_mut()
+            .and_then(|r| r.reading.take())
+        {
+            // A missing object is counted as an inconsistent read rather than failing
+            if self.check_read_back(object, resp, read).await {
+                resp.extensions_mut().insert(ReadOutcome::Inconsistent);
+                return Ok(read);
             }
         }
 
+        // The object is unchanged since its HEAD, as expected of the conditional GET
+        if resp.status() == StatusCode::NOT_MODIFIED
+            && matches!(
+                self.last_traffic_state,
+                Some(TrafficState::ConditionalGet { etag: Some(_), .. })
+            )
+        {
+            resp.extensions_mut().insert(ReadOutcome::NotModified);
+            return Ok(read);
+        }
+
+        if self.allow_misses
+            && matches!(
+                self.last_traffic_state,
+                Some(
+                    TrafficState::Get { .. }
+                        | TrafficState::Head { .. }
+                        | TrafficState::ConditionalGet { .. }
+                )
+            )
+        {
+            if resp.status() == StatusCode::NOT_FOUND {
+                resp.extensions_mut().insert(ReadOutcome::Miss);
+                return Ok(read);
+            } else if resp.status().is_success() {
+                resp.extensions_mut().insert(ReadOutcome::Hit);
+            }
+        }
+
+        if self.if_none_match
+            && resp.status() == StatusCode::PRECONDITION_FAILED
+            && matches!(self.last_traffic_state, Some(TrafficState::Put { .. }))
+        {
+            resp.extensions_mut().insert(WriteOutcome::AlreadyExists);
+            return Ok(read);
+        }
+
         if resp.status().is_success() {
-            if let Some(TrafficState::Get { .. }) = self.last_traffic_state.as_ref() {
-                if read != self.object_size {
-                    warn!(
-                        "Unexpected object size {read}, expected {}",
-                        self.object_size
-                    );
+            match self.last_traffic_state.as_ref() {
+                Some(TrafficState::Get { .. } | TrafficState::ConditionalGet { .. }) => {
+                    let expected_size = self.expected_size.filter(|_| !resp.body().is_truncated());
+                    if let Some(expected) = expected_size.filter(|size| *size != read) {
+                        if let Some(range) =
+                            self.random_ranges.as_ref().and_then(RandomRanges::last)
+                        {
+                            warn!("Unexpected length {read} of the range {range} read, expected {expected}");
+                        } else {
+                            warn!("Unexpected object size {read}, expected {expected}");
+                        }
+                    }
+                    if let Some(hasher) = hasher {
+                        self.verify_checksum(resp, hasher).await;
+                    }
                 }
+                Some(TrafficState::Put { .. }) => {
+                    self.verify_put(resp).await;
+                    self.record_written(resp);
+                }
+                Some(TrafficState::Head { .. }) => {
+                    // Kept as sent, quotes included, to be matched against verbatim
+                    let etag = resp
+                        .headers()
+                        .get(hyper::header::ETAG)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    self.traffic_cop.head_etag(etag);
+                }
+                None => unreachable!(),
             }
         } else {
             match self.last_traffic_state.as_ref() {
-                Some(TrafficState::Get { uri } | TrafficState::Put { uri }) => {
+                Some(
+                    TrafficState::Get { uri }
+                    | TrafficState::Put { uri }
+                    | TrafficState::Head { uri }
+                    | TrafficState::ConditionalGet { uri, .. },
+                ) => {
                     bail!("Request {:?} failed: {}", uri, resp.status())
                 }
                 None => unreachable!(),
@@ -158,7 +665,117 @@ where
         Ok(read)
     }
 
+    async fn warmup_request(
+        &mut self,
+        req: request::Builder,
+    ) -> Result<Option<Request<StreamBody<S>>>> {
+        let req = req
+            .method("HEAD")
+            .header(hyper::header::CONTENT_LENGTH, "0")
+            .body(StreamBody::new(self.stream_supplier.empty()))?;
+        Ok(Some(req))
+    }
+
+    async fn cleanup_request(
+        &mut self,
+        req: request::Builder,
+    ) -> Result<Option<Request<StreamBody<S>>>> {
+        let Some(keys) = &mut self.cleanup_keys else {
+            return Ok(None);
+        };
+        let Some(uri) = keys.iter().next().cloned() else {
+            return Ok(None);
+        };
+        keys.remove(&uri);
+
+        let req = with_host(req, &uri)
+            .uri(uri)
+            .method("DELETE")
+            .header(hyper::header::CONTENT_LENGTH, "0")
+            .body(StreamBody::new(self.stream_supplier.empty()))?;
+        Ok(Some(req))
+    }
+
     async fn cleanup(&mut self) -> Result<()> {
+        if let Some((keys, prefix)) = self.traffic_cop.keys_written() {
+            // The summary has those of the whole run
+            if prefix.is_empty() {
+                debug!("PUT {keys} distinct keys");
+            } else {
+                debug!("PUT {keys} distinct keys, reaching as far as the prefix `{prefix}`");
+            }
+            if let Some(stats) = &self.key_stats {
+                stats.write().await.run_stats.keys_written += keys;
+            }
+        }
         Ok(())
     }
 }
+
+/// Points the `Host` header at the authority of the uri, which differs from the
+/// url being connected to when the bucket is addressed virtual-hosted style
+fn with_host(mut req: request::Builder, uri: &Uri) -> request::Builder {
+    if let (Some(headers), Some(authority)) = (req.headers_mut(), uri.authority()) {
+        if let Ok(host) = HeaderValue::from_str(authority.as_str()) {
+            headers.insert(hyper::header::HOST, host);
+        }
+    }
+    req
+}
+
+/// A bodiless request reading the object at `uri`, labelled by the operation when
+/// breaking down the stats
+fn read_request<S>(
+    req: request::Builder,
+    uri: &Uri,
+    method: &str,
+    label: Option<&RequestLabel>,
+    body: S,
+) -> Result<Request<StreamBody<S>>> {
+    let req = with_host(req, uri);
+    let req = match label {
+        Some(label) => req.extension(label.clone()),
+        None => req,
+    };
+    Ok(req.uri(uri).method(method).body(StreamBody::new(body))?)
+}
+
+/// The `ETag` of the response, without its quotes
+fn etag<T>(resp: &Response<T>) -> Option<&str> {
+    resp.headers()
+        .get(hyper::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim_matches('"'))
+}
+
+/// Encodes tags as the URL query string the `x-amz-tagging` header expects
+fn encode_tags(tags: &[(String, String)]) -> String {
+    tags.iter()
+        .map(|(key, value)| {
+            format!(
+                "{}={}",
+                util::percent_encode(key),
+                util::percent_encode(value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::s3::encode_tags;
+
+    #[test]
+    fn encodes_tags() {
+        let tags = vec![
+            ("project".to_string(), "loaded".to_string()),
+            ("owner name".to_string(), "a&b=c/d+é".to_string()),
+        ];
+        assert_eq!(
+            encode_tags(&tags),
+            "project=loaded&owner%20name=a%26b%3Dc%2Fd%2B%C3%A9"
+        );
+        assert_eq!(encode_tags(&[]), "");
+    }
+}
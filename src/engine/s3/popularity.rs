@@ -0,0 +1,155 @@
+use crate::cli::KeyDistribution;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// Picks the index of the key to GET next from a keyspace, following the
+/// popularity distribution of the keys
+pub struct KeySampler {
+    popularity: Popularity,
+    keyspace_size: usize,
+    rng: StdRng,
+}
+
+enum Popularity {
+    Uniform,
+    Zipf(Zipf),
+}
+
+impl KeySampler {
+    /// Returns `None` for the sequential distribution, which doesn't sample
+    pub fn new(
+        distribution: KeyDistribution,
+        zipf_exponent: f64,
+        keyspace_size: usize,
+        rng: StdRng,
+    ) -> Option<Self> {
+        let popularity = match distribution {
+            KeyDistribution::Sequential => return None,
+            KeyDistribution::Uniform => Popularity::Uniform,
+            KeyDistribution::Zipf => Popularity::Zipf(Zipf::new(keyspace_size, zipf_exponent)),
+        };
+        Some(KeySampler {
+            popularity,
+            keyspace_size,
+            rng,
+        })
+    }
+
+    /// The index of the next key, between 0 and the size of the keyspace
+    pub fn next_index(&mut self) -> usize {
+        match &self.popularity {
+            Popularity::Uniform => self.rng.gen_range(0..self.keyspace_size),
+            Popularity::Zipf(zipf) => zipf.sample(&mut self.rng),
+        }
+    }
+}
+
+/// A Zipfian distribution over the ranks `0..n`, where the probability of rank
+/// `k` is proportional to `1 / (k + 1)^s`
+///
+/// Samples by rejection-inversion so it takes constant time and memory whatever
+/// the size of the keyspace.
+struct Zipf {
+    s: f64,
+    t: f64,
+    q: f64,
+}
+
+impl Zipf {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(n: usize, s: f64) -> Self {
+        let n = n as f64;
+        let (q, t) = if (s - 1.0).abs() < f64::EPSILON {
+            (0.0, 1.0 + n.ln())
+        } else {
+            let q = 1.0 / (1.0 - s);
+            (q, (n.powf(1.0 - s) - s) * q)
+        };
+        Zipf { s, t, q }
+    }
+
+    /// Inverse of the cumulative distribution function of the bounding distribution
+    fn inv_cdf(&self, p: f64) -> f64 {
+        let pt = p * self.t;
+        if pt <= 1.0 {
+            pt
+        } else if (self.s - 1.0).abs() < f64::EPSILON {
+            (pt - 1.0).exp()
+        } else {
+            (pt * (1.0 - self.s) + self.s).powf(self.q)
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn sample(&self, rng: &mut StdRng) -> usize {
+        loop {
+            let inv_b = self.inv_cdf(rng.gen::<f64>());
+            let x = (inv_b + 1.0).floor();
+            let mut ratio = x.powf(-self.s);
+            if x > 1.0 {
+                ratio *= inv_b.powf(self.s);
+            }
+            if rng.gen::<f64>() < ratio {
+                return x as usize - 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::KeyDistribution;
+    use crate::engine::s3::popularity::KeySampler;
+    use crate::util;
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn zipf_top_key_frequencies() {
+        let (n, s) = (1000, 1.1);
+        let mut sampler =
+            KeySampler::new(KeyDistribution::Zipf, s, n, util::seeded_rng("seed")).unwrap();
+        let samples = 200_000_usize;
+        let mut counts = vec![0_usize; n];
+        for _ in 0..samples {
+            counts[sampler.next_index()] += 1;
+        }
+
+        let harmonic = (1..=n).map(|k| 1.0 / (k as f64).powf(s)).sum::<f64>();
+        for (rank, count) in counts.iter().take(3).enumerate() {
+            let expected = 1.0 / ((rank + 1) as f64).powf(s) / harmonic;
+            let actual = *count as f64 / samples as f64;
+            assert!(
+                (actual - expected).abs() < 0.01,
+                "rank {rank}: expected frequency {expected}, got {actual}"
+            );
+        }
+        assert!(counts.windows(2).take(10).all(|w| w[0] > w[1]));
+    }
+
+    #[test]
+    fn zipf_stays_within_keyspace() {
+        let mut sampler =
+            KeySampler::new(KeyDistribution::Zipf, 1.0, 10, util::seeded_rng("seed")).unwrap();
+        assert!((0..10_000).all(|_| sampler.next_index() < 10));
+    }
+
+    #[test]
+    fn same_seed_same_keys() {
+        let mut a =
+            KeySampler::new(KeyDistribution::Zipf, 1.1, 100, util::seeded_rng("seed")).unwrap();
+        let mut b =
+            KeySampler::new(KeyDistribution::Zipf, 1.1, 100, util::seeded_rng("seed")).unwrap();
+        assert!((0..1_000).all(|_| a.next_index() == b.next_index()));
+    }
+
+    #[test]
+    fn sequential_doesnt_sample() {
+        assert!(KeySampler::new(
+            KeyDistribution::Sequential,
+            1.1,
+            100,
+            util::seeded_rng("seed")
+        )
+        .is_none());
+    }
+}
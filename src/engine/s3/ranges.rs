@@ -0,0 +1,106 @@
+use hyper::Uri;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::fmt::{Display, Formatter};
+
+/// A range of the bytes of an object, as read by a ranged GET
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: usize,
+    pub len: usize,
+}
+
+impl ByteRange {
+    /// The value of the `Range` header reading the range, whose end is inclusive
+    pub fn header_value(self) -> String {
+        format!("bytes={}-{}", self.start, self.start + self.len - 1)
+    }
+}
+
+impl Display for ByteRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.start + self.len)
+    }
+}
+
+/// Picks the ranges GETs read at random offsets within the objects, reading a number of
+/// them from each object before moving on to the next
+pub struct RandomRanges {
+    size: usize,
+    count: usize,
+    rng: StdRng,
+    /// The object being read and the ranges left to read from it
+    object: Option<(Uri, usize)>,
+    /// The range read last
+    last: Option<ByteRange>,
+}
+
+impl RandomRanges {
+    pub fn new(size: usize, count: usize, rng: StdRng) -> Self {
+        RandomRanges {
+            size,
+            count: count.max(1),
+            rng,
+            object: None,
+            last: None,
+        }
+    }
+
+    /// The object to read another range of, until as many ranges as asked were read
+    pub fn repeat(&mut self) -> Option<Uri> {
+        self.object
+            .as_ref()
+            .filter(|(_, left)| *left > 0)
+            .map(|(uri, _)| uri.clone())
+    }
+
+    /// Picks the next range to read of the object at `uri`, which is `object_size` long
+    pub fn next(&mut self, uri: &Uri, object_size: usize) -> ByteRange {
+        match &mut self.object {
+            Some((current, left)) if current == uri && *left > 0 => *left -= 1,
+            _ => self.object = Some((uri.clone(), self.count - 1)),
+        }
+        let len = self.size.min(object_size).max(1);
+        let range = ByteRange {
+            start: self.rng.gen_range(0..=object_size.saturating_sub(len)),
+            len,
+        };
+        self.last = Some(range);
+        range
+    }
+
+    pub fn last(&self) -> Option<ByteRange> {
+        self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::s3::ranges::RandomRanges;
+    use crate::util;
+    use hyper::Uri;
+
+    #[test]
+    fn reads_ranges_within_the_object() {
+        let mut ranges = RandomRanges::new(4096, 3, util::seeded_rng("seed"));
+        let object = Uri::from_static("http://localhost/bucket/object");
+        assert_eq!(ranges.repeat(), None);
+
+        for i in 0..3 {
+            let range = ranges.next(&object, 1 << 20);
+            assert_eq!(range.len, 4096);
+            assert!(range.start + range.len <= 1 << 20);
+            let expected = if i < 2 { Some(object.clone()) } else { None };
+            assert_eq!(ranges.repeat(), expected);
+        }
+        // The next object starts over
+        let next = Uri::from_static("http://localhost/bucket/next");
+        ranges.next(&next, 1 << 20);
+        assert_eq!(ranges.repeat(), Some(next));
+
+        // Ranges can't be longer than the object
+        let range = ranges.next(&object, 100);
+        assert_eq!((range.start, range.len), (0, 100));
+        assert_eq!(range.header_value(), "bytes=0-99");
+    }
+}
@@ -0,0 +1,180 @@
+use bytesize::ByteSize;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The distribution the sizes of the objects being PUT are drawn from
+#[derive(Debug, Clone, PartialEq)]
+pub enum SizeDistribution {
+    /// Every object has the same size
+    Fixed(usize),
+    /// Sizes are drawn uniformly from the inclusive range
+    Uniform { min: usize, max: usize },
+    /// Sizes are drawn from a histogram of `(size, weight)` buckets
+    Histogram(Vec<(usize, u32)>),
+}
+
+impl SizeDistribution {
+    /// The size of every object, if they all have the same size
+    pub fn fixed(&self) -> Option<usize> {
+        match self {
+            SizeDistribution::Fixed(size) => Some(*size),
+            SizeDistribution::Uniform { .. } | SizeDistribution::Histogram(_) => None,
+        }
+    }
+
+    /// Parses a histogram from lines of `size,weight`, ignoring blank lines and `#` comments
+    fn parse_histogram(s: &str) -> Result<Self, String> {
+        let buckets = s
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| {
+                let (size, weight) = l.split_once(',').ok_or_else(|| {
+                    format!("histogram line `{l}` isn't of the form `size,weight`")
+                })?;
+                let weight = weight
+                    .trim()
+                    .parse()
+                    .map_err(|e| format!("invalid weight in `{l}`: {e}"))?;
+                Ok((parse_size(size.trim())?, weight))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if buckets.iter().all(|(_, weight)| *weight == 0) {
+            return Err("histogram must have at least one bucket with a non-zero weight".into());
+        }
+        Ok(SizeDistribution::Histogram(buckets))
+    }
+}
+
+impl FromStr for SizeDistribution {
+    type Err = String;
+
+    /// Parses either a fixed size (`1MiB`), a uniform range (`1KiB..10MiB`) or a path
+    /// to a histogram file
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((min, max)) = s.split_once("..") {
+            let (min, max) = (parse_size(min)?, parse_size(max)?);
+            if min > max {
+                return Err(format!("range `{s}` is empty"));
+            }
+            return Ok(SizeDistribution::Uniform { min, max });
+        }
+        if let Ok(size) = parse_size(s) {
+            return Ok(SizeDistribution::Fixed(size));
+        }
+        let path = Path::new(s);
+        if !path.is_file() {
+            return Err(format!(
+                "`{s}` is neither a size, a range of sizes nor a histogram file"
+            ));
+        }
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("{e}"))?;
+        Self::parse_histogram(&contents)
+    }
+}
+
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let size = s.parse::<ByteSize>()?;
+    usize::try_from(size.as_u64()).map_err(|e| format!("{e}"))
+}
+
+/// Draws the sizes of objects from a distribution
+pub struct SizeSampler {
+    distribution: SizeDistribution,
+    weights: Option<WeightedIndex<u32>>,
+    rng: StdRng,
+}
+
+impl SizeSampler {
+    pub fn new(distribution: SizeDistribution, rng: StdRng) -> Self {
+        let weights = match &distribution {
+            SizeDistribution::Histogram(buckets) => {
+                Some(WeightedIndex::new(buckets.iter().map(|(_, weight)| *weight)).unwrap())
+            }
+            SizeDistribution::Fixed(_) | SizeDistribution::Uniform { .. } => None,
+        };
+        SizeSampler {
+            distribution,
+            weights,
+            rng,
+        }
+    }
+
+    /// The size of every object, if they all have the same size
+    pub fn fixed(&self) -> Option<usize> {
+        self.distribution.fixed()
+    }
+
+    /// Draws the size of the next object
+    pub fn next(&mut self) -> usize {
+        match &self.distribution {
+            SizeDistribution::Fixed(size) => *size,
+            SizeDistribution::Uniform { min, max } => self.rng.gen_range(*min..=*max),
+            SizeDistribution::Histogram(buckets) => {
+                buckets[self.weights.as_ref().unwrap().sample(&mut self.rng)].0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::s3::size::{SizeDistribution, SizeSampler};
+    use crate::util;
+
+    #[test]
+    fn parse_fixed() {
+        assert_eq!(
+            "1MiB".parse::<SizeDistribution>().unwrap(),
+            SizeDistribution::Fixed(1024 * 1024)
+        );
+        assert_eq!(
+            "512".parse::<SizeDistribution>().unwrap(),
+            SizeDistribution::Fixed(512)
+        );
+    }
+
+    #[test]
+    fn parse_uniform() {
+        assert_eq!(
+            "1KiB..10MiB".parse::<SizeDistribution>().unwrap(),
+            SizeDistribution::Uniform {
+                min: 1024,
+                max: 10 * 1024 * 1024
+            }
+        );
+        assert!("10MiB..1KiB".parse::<SizeDistribution>().is_err());
+    }
+
+    #[test]
+    fn parse_histogram() {
+        assert_eq!(
+            SizeDistribution::parse_histogram("# size,weight\n4KiB,90\n\n1MiB, 10\n").unwrap(),
+            SizeDistribution::Histogram(vec![(4096, 90), (1024 * 1024, 10)])
+        );
+        assert!(SizeDistribution::parse_histogram("4KiB\n").is_err());
+        assert!(SizeDistribution::parse_histogram("4KiB,0\n").is_err());
+    }
+
+    #[test]
+    fn samples_within_distribution() {
+        let mut uniform = SizeSampler::new(
+            SizeDistribution::Uniform { min: 10, max: 20 },
+            util::seeded_rng("seed"),
+        );
+        assert!((0..1000)
+            .map(|_| uniform.next())
+            .all(|s| (10..=20).contains(&s)));
+
+        let mut histogram = SizeSampler::new(
+            SizeDistribution::Histogram(vec![(1, 1), (2, 0), (3, 1)]),
+            util::seeded_rng("seed"),
+        );
+        assert!((0..1000)
+            .map(|_| histogram.next())
+            .all(|s| s == 1 || s == 3));
+    }
+}
@@ -1,45 +1,142 @@
 use crate::cli::TrafficPattern;
+use crate::engine::s3::popularity::KeySampler;
 use crate::engine::s3::uri::UriProvider;
 use hyper::Uri;
+use log::info;
+use std::collections::VecDeque;
 use std::mem;
+use std::time::Duration;
+use tokio::time::Instant;
 
 #[derive(Debug, Clone)]
 pub enum TrafficState {
-    Put { uri: Uri },
-    Get { uri: Uri },
+    Put {
+        uri: Uri,
+    },
+    Get {
+        uri: Uri,
+    },
+    /// HEADs the object to capture its `ETag` for the conditional GET that follows
+    Head {
+        uri: Uri,
+    },
+    /// GETs the object unless it still has the `ETag` captured by the HEAD, when it had one
+    ConditionalGet {
+        uri: Uri,
+        etag: Option<String>,
+    },
 }
 
 pub struct TrafficStateMachine {
     pattern: TrafficPattern,
     uri_supplier: UriProvider,
+    /// Picks the keys of GETs that don't follow a PUT, they're sequential otherwise
+    key_sampler: Option<KeySampler>,
     state: TrafficState,
+    /// Number of PUTs of keys drawn from the uri supplier that succeeded so far
+    puts: usize,
+    /// Defers reading back the keys PUT by the `Both` pattern, only set with a delay
+    read_back: Option<ReadBack>,
+    /// The `ETag` captured by the last HEAD, carried over to the conditional GET of the object
+    etag: Option<String>,
+}
+
+/// The keys that were written, in order, waiting out the delay before being read back
+struct ReadBack {
+    delay: Duration,
+    written: VecDeque<(Uri, Instant)>,
+}
+
+impl ReadBack {
+    /// The oldest key that was written at least the delay ago
+    fn due(&mut self) -> Option<Uri> {
+        self.written
+            .front()
+            .filter(|(_, at)| at.elapsed() >= self.delay)?;
+        self.written.pop_front().map(|(uri, _)| uri)
+    }
 }
 
 impl TrafficStateMachine {
-    pub fn new(pattern: TrafficPattern, mut uri_supplier: UriProvider) -> Self {
+    pub fn new(
+        pattern: TrafficPattern,
+        mut uri_supplier: UriProvider,
+        mut key_sampler: Option<KeySampler>,
+    ) -> Self {
+        // Connections of split traffic are handed the pattern of their role, one that
+        // wasn't assigned any, e.g. a lone connection, writes
+        let pattern = match pattern {
+            TrafficPattern::Split => SplitRole::Writer.pattern(),
+            pattern => pattern,
+        };
         let state = match pattern {
-            TrafficPattern::Both | TrafficPattern::Put => TrafficState::Put {
-                uri: uri_supplier.next(),
+            // Split traffic was resolved above
+            TrafficPattern::Both | TrafficPattern::Put | TrafficPattern::Split => {
+                TrafficState::Put {
+                    uri: uri_supplier.next(),
+                }
+            }
+            TrafficPattern::Get | TrafficPattern::ReadModifyWrite => TrafficState::Get {
+                uri: next_get(&mut uri_supplier, key_sampler.as_mut()),
             },
-            TrafficPattern::Get => TrafficState::Get {
-                uri: uri_supplier.next(),
+            TrafficPattern::ConditionalGet => TrafficState::Head {
+                uri: next_get(&mut uri_supplier, key_sampler.as_mut()),
             },
         };
         TrafficStateMachine {
             pattern,
             uri_supplier,
+            key_sampler,
             state,
+            puts: 0,
+            read_back: None,
+            etag: None,
         }
     }
 
+    /// Has the `Both` pattern read back each key only once `delay` has passed since it
+    /// was written, issuing further PUTs in the meantime, rather than straight after
+    ///
+    /// The keys are only read back once they're reported [`written`](Self::written).
+    pub fn with_read_after_write_delay(mut self, delay: Duration) -> Self {
+        self.read_back = Some(ReadBack {
+            delay,
+            written: VecDeque::new(),
+        });
+        self
+    }
+
+    /// Reports the object at `uri` was successfully written, to be counted and read
+    /// back later when there's a read-after-write delay
+    pub fn written(&mut self, uri: Uri) {
+        self.puts += 1;
+        if let Some(read_back) = &mut self.read_back {
+            read_back.written.push_back((uri, Instant::now()));
+        }
+    }
+
+    /// Reports the `ETag` the last HEAD responded with, for the conditional GET of the object
+    pub fn head_etag(&mut self, etag: Option<String>) {
+        self.etag = etag;
+    }
+
     pub fn next(&mut self) -> TrafficState {
+        if let Some(uri) = self.read_back.as_mut().and_then(ReadBack::due) {
+            return TrafficState::Get { uri };
+        }
+        let cycles = self.uri_supplier.cycles();
         let new_state = match &self.pattern {
-            // If we're in a PUT traffic pattern, keep issuing PUTs
-            TrafficPattern::Put => TrafficState::Put {
+            // If we're in a PUT traffic pattern, keep issuing PUTs (split traffic having
+            // been resolved when the machine was built)
+            TrafficPattern::Put | TrafficPattern::Split => TrafficState::Put {
                 uri: self.uri_supplier.next(),
             },
             // If we're in a GET traffic pattern, keep issuing GETs
             TrafficPattern::Get => TrafficState::Get {
+                uri: next_get(&mut self.uri_supplier, self.key_sampler.as_mut()),
+            },
+            // Keys are read back once they're due, keep issuing PUTs until then
+            TrafficPattern::Both if self.read_back.is_some() => TrafficState::Put {
                 uri: self.uri_supplier.next(),
             },
             // If we're in a BOTH traffic pattern, switch between PUTs and GETs, starting
@@ -47,41 +144,204 @@ impl TrafficStateMachine {
             TrafficPattern::Both => match &self.state {
                 // Take the URI from the PUT we just issued and use it for our next GET request
                 TrafficState::Put { uri } => TrafficState::Get { uri: uri.clone() },
-                TrafficState::Get { .. } => TrafficState::Put {
+                _ => TrafficState::Put {
                     uri: self.uri_supplier.next(),
                 },
             },
+            // If we're in a READ-MODIFY-WRITE traffic pattern, switch between GETs and PUTs,
+            // writing back each object we just read
+            TrafficPattern::ReadModifyWrite => match &self.state {
+                TrafficState::Get { uri } => TrafficState::Put { uri: uri.clone() },
+                _ => TrafficState::Get {
+                    uri: next_get(&mut self.uri_supplier, self.key_sampler.as_mut()),
+                },
+            },
+            // If we're in a CONDITIONAL-GET traffic pattern, switch between HEADs and GETs
+            // of the object just HEADed
+            TrafficPattern::ConditionalGet => match &self.state {
+                // The ETag is only known once the HEAD responds, it's filled in on the way out
+                TrafficState::Head { uri } => TrafficState::ConditionalGet {
+                    uri: uri.clone(),
+                    etag: None,
+                },
+                _ => TrafficState::Head {
+                    uri: next_get(&mut self.uri_supplier, self.key_sampler.as_mut()),
+                },
+            },
         };
-        mem::replace(&mut self.state, new_state)
+        if cycles == 0 && self.uri_supplier.cycles() > 0 && self.keys_written().is_some() {
+            info!(
+                "PUTs went through the whole keyspace of {} keys, overwriting them from here on",
+                self.uri_supplier.keyspace_size()
+            );
+        }
+        let mut state = mem::replace(&mut self.state, new_state);
+        match &mut state {
+            // Don't carry over the ETag of an earlier object whose HEAD got no response
+            TrafficState::Head { .. } => self.etag = None,
+            TrafficState::ConditionalGet { etag, .. } => *etag = self.etag.take(),
+            TrafficState::Put { .. } | TrafficState::Get { .. } => {}
+        }
+        state
     }
+
+    /// The number of distinct keys PUT so far and the directory prefix of the furthest
+    /// of them, for the patterns that PUT their way through the keyspace in order
+    pub fn keys_written(&self) -> Option<(usize, String)> {
+        if !matches!(self.pattern, TrafficPattern::Put | TrafficPattern::Both) {
+            return None;
+        }
+        // Keys are reused once the keyspace wraps around
+        let keys = self.puts.min(self.uri_supplier.keyspace_size());
+        let prefix = match keys {
+            0 => String::new(),
+            n => self.uri_supplier.prefix_at(n - 1),
+        };
+        Some((keys, prefix))
+    }
+}
+
+/// The uri of the next GET, drawn by the key sampler if there is one
+fn next_get(uri_supplier: &mut UriProvider, key_sampler: Option<&mut KeySampler>) -> Uri {
+    match key_sampler {
+        Some(sampler) => uri_supplier.uri_at(sampler.next_index()),
+        None => uri_supplier.next(),
+    }
+}
+
+/// The role of a connection when splitting the traffic between readers and writers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitRole {
+    /// Only PUTs objects
+    Writer,
+    /// Only GETs the objects PUT by the connection at index `writer`, which is the
+    /// reader itself when there are no writers
+    Reader { writer: usize },
+}
+
+impl SplitRole {
+    /// The traffic pattern the connection follows
+    pub fn pattern(self) -> TrafficPattern {
+        match self {
+            SplitRole::Writer => TrafficPattern::Put,
+            SplitRole::Reader { .. } => TrafficPattern::Get,
+        }
+    }
+}
+
+/// Assigns the roles of `num_connections` connections, the first `reader_fraction` of
+/// them (rounded) being readers spread evenly across the writers
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub fn split_roles(num_connections: usize, reader_fraction: f64) -> Vec<SplitRole> {
+    let readers =
+        ((num_connections as f64 * reader_fraction).round() as usize).min(num_connections);
+    let writers = num_connections - readers;
+    (0..num_connections)
+        .map(|i| match i {
+            i if i >= readers => SplitRole::Writer,
+            i if writers == 0 => SplitRole::Reader { writer: i },
+            i => SplitRole::Reader {
+                writer: readers + i % writers,
+            },
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cli::TrafficPattern;
-    use crate::engine::s3::traffic::TrafficState;
+    use crate::cli::{Addressing, TrafficPattern};
     use crate::engine::s3::traffic::TrafficStateMachine;
+    use crate::engine::s3::traffic::{split_roles, SplitRole, TrafficState};
     use crate::engine::s3::uri::UriProvider;
+    use std::time::Duration;
 
     #[test]
     fn put_traffic_pattern() {
+        // Split traffic without a role writes
+        for pattern in [TrafficPattern::Put, TrafficPattern::Split] {
+            let mut expected_uri_provider =
+                UriProvider::new("", "", Addressing::Path, String::new(), 0, 1, 1);
+            let mut machine =
+                TrafficStateMachine::new(pattern, expected_uri_provider.clone(), None);
+
+            for _ in 0..1000 {
+                let next_uri = expected_uri_provider.next();
+                assert!(matches!(machine.next(), TrafficState::Put { uri } if uri == next_uri));
+            }
+        }
+    }
+
+    #[test]
+    fn counts_keys_written() {
+        let uris = UriProvider::new("", "", Addressing::Path, String::new(), 2, 3, 2);
+        let mut machine = TrafficStateMachine::new(TrafficPattern::Both, uris.clone(), None);
+        assert_eq!(machine.keys_written(), Some((0, String::new())));
+
+        // Every other request is a GET of the key just PUT, only those that succeed count
+        let put = |machine: &mut TrafficStateMachine, requests, failing| {
+            for i in 0..requests {
+                if let TrafficState::Put { uri } = machine.next() {
+                    if i != failing {
+                        machine.written(uri);
+                    }
+                }
+            }
+        };
+        put(&mut machine, 14, 2);
+        assert_eq!(machine.keys_written(), Some((6, "0/1/".to_string())));
+
+        // Wrapping around the keyspace reaches no further
+        put(&mut machine, 20, 20);
+        assert_eq!(machine.keys_written(), Some((12, "1/1/".to_string())));
+
+        let machine = TrafficStateMachine::new(TrafficPattern::Get, uris, None);
+        assert_eq!(machine.keys_written(), None);
+    }
+
+    #[test]
+    fn reads_back_after_delay() {
         let mut expected_uri_provider =
-            UriProvider::new(String::new(), String::new(), String::new(), 0, 1, 1);
+            UriProvider::new("", "", Addressing::Path, String::new(), 0, 100, 1);
         let mut machine =
-            TrafficStateMachine::new(TrafficPattern::Put, expected_uri_provider.clone());
+            TrafficStateMachine::new(TrafficPattern::Both, expected_uri_provider.clone(), None)
+                .with_read_after_write_delay(Duration::ZERO);
 
-        for _ in 0..1000 {
-            let next_uri = expected_uri_provider.next();
-            assert!(matches!(machine.next(), TrafficState::Put { uri } if uri == next_uri));
+        // Only the keys reported written are read back, in the order they were written
+        let first = expected_uri_provider.next();
+        let second = expected_uri_provider.next();
+        assert!(matches!(machine.next(), TrafficState::Put { uri } if uri == first));
+        assert!(matches!(machine.next(), TrafficState::Put { uri } if uri == second));
+        machine.written(first.clone());
+        machine.written(second.clone());
+        assert!(matches!(machine.next(), TrafficState::Get { uri } if uri == first));
+        assert!(matches!(machine.next(), TrafficState::Get { uri } if uri == second));
+        let third = expected_uri_provider.next();
+        assert!(matches!(machine.next(), TrafficState::Put { uri } if uri == third));
+
+        // Keys aren't read back before the delay is up
+        let mut machine =
+            TrafficStateMachine::new(TrafficPattern::Both, expected_uri_provider.clone(), None)
+                .with_read_after_write_delay(Duration::from_secs(3600));
+        for _ in 0..10 {
+            let state = machine.next();
+            let TrafficState::Put { uri } = state else {
+                panic!("read back {state:?} before the delay");
+            };
+            machine.written(uri);
         }
+        assert_eq!(machine.keys_written().map(|(keys, _)| keys), Some(10));
     }
 
     #[test]
     fn get_traffic_pattern() {
         let mut expected_uri_provider =
-            UriProvider::new(String::new(), String::new(), String::new(), 0, 1, 1);
+            UriProvider::new("", "", Addressing::Path, String::new(), 0, 1, 1);
         let mut machine =
-            TrafficStateMachine::new(TrafficPattern::Get, expected_uri_provider.clone());
+            TrafficStateMachine::new(TrafficPattern::Get, expected_uri_provider.clone(), None);
 
         for _ in 0..1000 {
             let next_uri = expected_uri_provider.next();
@@ -92,9 +352,9 @@ mod tests {
     #[test]
     fn both_traffic_pattern() {
         let mut expected_uri_provider =
-            UriProvider::new(String::new(), String::new(), String::new(), 0, 1, 1);
+            UriProvider::new("", "", Addressing::Path, String::new(), 0, 1, 1);
         let mut machine =
-            TrafficStateMachine::new(TrafficPattern::Both, expected_uri_provider.clone());
+            TrafficStateMachine::new(TrafficPattern::Both, expected_uri_provider.clone(), None);
 
         let mut last_state = None;
         for _ in 0..1000 {
@@ -118,10 +378,108 @@ mod tests {
                             matches!(next_state.clone(), TrafficState::Put { uri } if uri == next_uri)
                         );
                     }
+                    s => panic!("unexpected {s:?}"),
+                },
+            }
+
+            last_state = Some(next_state);
+        }
+    }
+
+    #[test]
+    fn read_modify_write_traffic_pattern() {
+        let mut expected_uri_provider =
+            UriProvider::new("", "", Addressing::Path, String::new(), 0, 1, 1);
+        let mut machine = TrafficStateMachine::new(
+            TrafficPattern::ReadModifyWrite,
+            expected_uri_provider.clone(),
+            None,
+        );
+
+        let mut last_state = None;
+        for _ in 0..1000 {
+            let next_state = machine.next();
+            match last_state {
+                None => {
+                    let next_uri = expected_uri_provider.next();
+                    assert!(
+                        matches!(next_state.clone(), TrafficState::Get { uri }  if uri == next_uri)
+                    );
+                }
+                Some(s) => match s {
+                    TrafficState::Get { uri: last_uri } => {
+                        assert!(
+                            matches!(next_state.clone(), TrafficState::Put { uri } if uri == last_uri)
+                        );
+                    }
+                    TrafficState::Put { .. } => {
+                        let next_uri = expected_uri_provider.next();
+                        assert!(
+                            matches!(next_state.clone(), TrafficState::Get { uri } if uri == next_uri)
+                        );
+                    }
+                    s => panic!("unexpected {s:?}"),
                 },
             }
 
             last_state = Some(next_state);
         }
     }
+
+    #[test]
+    fn conditional_get_traffic_pattern() {
+        let mut expected_uri_provider =
+            UriProvider::new("", "", Addressing::Path, String::new(), 0, 1, 1);
+        let mut machine = TrafficStateMachine::new(
+            TrafficPattern::ConditionalGet,
+            expected_uri_provider.clone(),
+            None,
+        );
+
+        // The GET carries the ETag the HEAD of the same object responded with
+        let first = expected_uri_provider.next();
+        assert!(matches!(machine.next(), TrafficState::Head { uri } if uri == first));
+        machine.head_etag(Some("\"abc\"".to_string()));
+        assert!(matches!(
+            machine.next(),
+            TrafficState::ConditionalGet { uri, etag: Some(etag) } if uri == first && etag == "\"abc\""
+        ));
+
+        // Without an ETag the GET is unconditional, and an ETag is only carried over once
+        let second = expected_uri_provider.next();
+        assert!(matches!(machine.next(), TrafficState::Head { uri } if uri == second));
+        assert!(matches!(
+            machine.next(),
+            TrafficState::ConditionalGet { uri, etag: None } if uri == second
+        ));
+        machine.head_etag(Some("\"stale\"".to_string()));
+        let third = expected_uri_provider.next();
+        assert!(matches!(machine.next(), TrafficState::Head { uri } if uri == third));
+        assert!(matches!(
+            machine.next(),
+            TrafficState::ConditionalGet { uri, etag: None } if uri == third
+        ));
+    }
+
+    #[test]
+    fn split_traffic_roles() {
+        assert_eq!(
+            split_roles(5, 0.6),
+            vec![
+                SplitRole::Reader { writer: 3 },
+                SplitRole::Reader { writer: 4 },
+                SplitRole::Reader { writer: 3 },
+                SplitRole::Writer,
+                SplitRole::Writer,
+            ]
+        );
+        assert_eq!(split_roles(2, 0.0), vec![SplitRole::Writer; 2]);
+        assert_eq!(
+            split_roles(2, 1.0),
+            vec![
+                SplitRole::Reader { writer: 0 },
+                SplitRole::Reader { writer: 1 }
+            ]
+        );
+    }
 }
@@ -1,5 +1,43 @@
+use crate::cli::Addressing;
+use crate::util;
 use hyper::Uri;
 use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// An explicit list of object keys for the uri provider to cycle through
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyList(Arc<[String]>);
+
+impl KeyList {
+    /// Parses a key from each line, ignoring blank lines
+    fn parse(s: &str) -> Result<Self, String> {
+        let keys = s
+            .lines()
+            .map(|l| l.trim_end_matches('\r'))
+            .filter(|l| !l.trim().is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return Err("key file contains no keys".into());
+        }
+        Ok(KeyList(keys.into()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl FromStr for KeyList {
+    type Err = String;
+
+    /// Reads the keys from the file at the given path
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let contents = std::fs::read_to_string(s).map_err(|e| format!("{s}: {e}"))?;
+        Self::parse(&contents)
+    }
+}
 
 #[derive(Debug, Clone)]
 struct ArbitraryRadixNumber {
@@ -15,22 +53,23 @@ impl ArbitraryRadixNumber {
         }
     }
 
-    fn increment(&mut self) {
+    /// Returns whether the number overflowed, every digit wrapping back to zero
+    fn increment(&mut self) -> bool {
         let mut i = self.digits.len() - 1;
         while i < self.digits.len() {
             //increment current digit
             self.digits[i] = (self.digits[i] + 1) % self.radix;
-            if self.digits[i] == 0 && i != 0 {
-                // we wrapped the current digit, move up a digit
-                i -= 1;
-            } else {
+            if self.digits[i] != 0 {
                 break;
             }
+            if i == 0 {
+                // we wrapped the top digit, the number is back to zero
+                return true;
+            }
+            // we wrapped the current digit, move up a digit
+            i -= 1;
         }
-    }
-
-    fn to_digits(&self) -> Vec<usize> {
-        self.digits.clone()
+        false
     }
 }
 
@@ -39,19 +78,28 @@ impl ArbitraryRadixNumber {
 /// directories.
 #[derive(Debug, Clone)]
 pub struct UriProvider {
+    /// The base of every uri up to and including the bucket
     base: String,
-    bucket: String,
     obj_prefix: String,
     num_objs_per_prefix: usize,
     obj_cnt: usize,
     /// A number to let us build out an incrementing dir prefix where each digit is folder.
     radix_num: Option<ArbitraryRadixNumber>,
+    /// Keys to cycle through instead of generating them, `obj_cnt` indexing the next one
+    keys: Option<KeyList>,
+    /// Number of times every uri of the keyspace has been yielded
+    cycles: usize,
+    /// The index of the first uri yielded out of the whole keyspace, every `stride`th
+    /// one from there on being yielded
+    offset: usize,
+    stride: usize,
 }
 
 impl UriProvider {
     pub fn new(
-        uri_base: String,
-        bucket: String,
+        uri_base: &str,
+        bucket: &str,
+        addressing: Addressing,
         obj_prefix: String,
         depth: usize,
         num_objs: usize,
@@ -62,34 +110,169 @@ impl UriProvider {
         } else {
             None
         };
+        let base = match addressing {
+            Addressing::Path => format!("{uri_base}/{bucket}"),
+            Addressing::Virtual => {
+                let uri = uri_base.parse::<Uri>().expect("invalid uri base");
+                format!(
+                    "{}://{bucket}.{}",
+                    uri.scheme_str().unwrap_or("http"),
+                    uri.authority().expect("uri base has no authority")
+                )
+            }
+        };
         UriProvider {
-            base: uri_base,
-            bucket,
+            base,
             obj_prefix,
             num_objs_per_prefix: num_objs,
             obj_cnt: 0,
             radix_num,
+            keys: None,
+            cycles: 0,
+            offset: 0,
+            stride: 1,
+        }
+    }
+
+    /// Cycles through the given keys instead of generating them, starting from the key
+    /// at index `start` (wrapping around)
+    pub fn with_keys(mut self, keys: KeyList, start: usize) -> Self {
+        self.obj_cnt = start % keys.len();
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Only yields every `stride`th uri of the keyspace, starting from the one at index
+    /// `offset`, for connections sharing the keyspace to interleave their keys
+    ///
+    /// The uris the provider yields are then indexed among themselves, e.g. by
+    /// [`uri_at`](Self::uri_at).
+    pub fn with_stride(mut self, offset: usize, stride: usize) -> Self {
+        self.offset = offset % self.full_keyspace_size();
+        self.stride = stride.max(1);
+        self.obj_cnt = 0;
+        self
+    }
+
+    /// Whether the provider skips over some of the keyspace
+    fn is_strided(&self) -> bool {
+        self.offset != 0 || self.stride != 1
+    }
+
+    /// The number of distinct uris the provider yields before wrapping around
+    pub fn keyspace_size(&self) -> usize {
+        match self.full_keyspace_size() - self.offset {
+            0 => 0,
+            n => (n - 1) / self.stride + 1,
         }
     }
 
+    /// The number of distinct uris, including those the stride skips over
+    ///
+    /// Saturates at `usize::MAX` for folders too deep to count the keys of, a keyspace
+    /// no run gets through anyway.
+    fn full_keyspace_size(&self) -> usize {
+        if let Some(keys) = &self.keys {
+            return keys.len();
+        }
+        self.radix_num
+            .as_ref()
+            .map_or(Some(1), |n| {
+                u32::try_from(n.digits.len())
+                    .ok()
+                    .and_then(|depth| n.radix.checked_pow(depth))
+            })
+            .and_then(|num_dirs| num_dirs.checked_mul(self.num_objs_per_prefix))
+            .unwrap_or(usize::MAX)
+    }
+
+    /// The index within the whole keyspace of the uri at `index` in the order the
+    /// provider yields them
+    fn keyspace_index(&self, index: usize) -> usize {
+        self.offset + index % self.keyspace_size() * self.stride
+    }
+
+    /// The number of times the provider has gone through its whole keyspace, its uris
+    /// repeating from the start (in the same order) once it has
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
+    /// The directory prefix the next uri is in, e.g. `3/2/1/`, empty without folders
+    pub fn prefix(&self) -> String {
+        if self.is_strided() {
+            return self.prefix_at(self.obj_cnt);
+        }
+        self.radix_num
+            .as_ref()
+            .map_or(String::new(), |n| dir_prefix(&n.digits))
+    }
+
+    /// The uri at `index` in the order the provider yields them, regardless of where
+    /// the provider currently is
+    pub fn uri_at(&self, index: usize) -> Uri {
+        let index = self.keyspace_index(index);
+        if let Some(keys) = &self.keys {
+            return self.key_uri(&keys.0[index]);
+        }
+
+        format!(
+            "{}/{}-{}{}",
+            self.base,
+            self.obj_prefix,
+            self.keyspace_prefix(index),
+            index % self.num_objs_per_prefix
+        )
+        .parse::<Uri>()
+        .unwrap()
+    }
+
+    /// The directory prefix of the uri at `index`, which is the number of the prefix
+    /// the object is in, written in the radix of the number of branches with a digit
+    /// per folder
+    pub fn prefix_at(&self, index: usize) -> String {
+        self.keyspace_prefix(self.keyspace_index(index))
+    }
+
+    /// The directory prefix of the uri at `index` within the whole keyspace
+    fn keyspace_prefix(&self, index: usize) -> String {
+        self.radix_num.as_ref().map_or(String::new(), |n| {
+            let mut dir = index / self.num_objs_per_prefix;
+            let mut digits = vec![0; n.digits.len()];
+            for digit in digits.iter_mut().rev() {
+                *digit = dir % n.radix;
+                dir /= n.radix;
+            }
+            dir_prefix(&digits)
+        })
+    }
+
+    fn key_uri(&self, key: &str) -> Uri {
+        // Keys are encoded a segment at a time to keep their `/` delimiters
+        let key = key.split('/').map(util::percent_encode).collect::<Vec<_>>();
+        format!("{}/{}", self.base, key.join("/"))
+            .parse::<Uri>()
+            .unwrap()
+    }
+
     pub fn next(&mut self) -> Uri {
-        // Build the directory prefix according to the current radix number
-        // For instance, if we had the radix_num `321`, that would result in the
-        // directory prefix of "3/2/1/"
-        let dir_prefix = self.radix_num.as_mut().map_or(String::new(), |n| {
-            let mut s = String::new();
-            n.to_digits()
-                .iter()
-                .try_for_each(|i| write!(s, "{i}/"))
-                .unwrap();
-            s
-        });
+        if self.keys.is_some() || self.is_strided() {
+            let uri = self.uri_at(self.obj_cnt);
+            self.obj_cnt = (self.obj_cnt + 1) % self.keyspace_size();
+            if self.obj_cnt == 0 {
+                self.cycles += 1;
+            }
+            return uri;
+        }
 
         // Build the uri, leaving the object prefix at the very top to ensure that all
         // our folders are unique for the run
         let uri = format!(
-            "{}/{}/{}-{}{}",
-            self.base, self.bucket, self.obj_prefix, dir_prefix, self.obj_cnt
+            "{}/{}-{}{}",
+            self.base,
+            self.obj_prefix,
+            self.prefix(),
+            self.obj_cnt
         )
         .parse::<Uri>()
         .unwrap();
@@ -98,8 +281,12 @@ impl UriProvider {
 
         if self.obj_cnt == 0 {
             // we've written num_objs to the current prefix, increment to get to the next dir prefix.
-            if let Some(n) = self.radix_num.as_mut() {
-                n.increment();
+            let wrapped = self
+                .radix_num
+                .as_mut()
+                .map_or(true, ArbitraryRadixNumber::increment);
+            if wrapped {
+                self.cycles += 1;
             }
         }
 
@@ -107,17 +294,29 @@ impl UriProvider {
     }
 }
 
+/// Builds the directory prefix of a radix number, for instance the radix number `321`
+/// results in the directory prefix of "3/2/1/"
+fn dir_prefix(digits: &[usize]) -> String {
+    let mut s = String::new();
+    digits.iter().try_for_each(|i| write!(s, "{i}/")).unwrap();
+    s
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::cli::Addressing;
+    use crate::engine::s3::uri::KeyList;
     use crate::engine::s3::*;
     use hyper::Uri;
+    use std::collections::HashSet;
     use std::str::FromStr;
 
     #[test]
     fn no_depth_single_obj() {
         let mut s = UriProvider::new(
-            "http://10.0.1.24:9003".to_string(),
-            "bucket".to_string(),
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
             "my-dude".to_string(),
             0,
             1,
@@ -125,9 +324,9 @@ mod tests {
         );
 
         let expected = vec![
-            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0").unwrap(),
         ];
 
         let actual: Vec<Uri> = (0..3).map(|_| s.next()).collect();
@@ -137,8 +336,9 @@ mod tests {
     #[test]
     fn no_depth_multi_obj() {
         let mut s = UriProvider::new(
-            "http://10.0.1.24:9003".to_string(),
-            "bucket".to_string(),
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
             "my-dude".to_string(),
             0,
             2,
@@ -146,9 +346,9 @@ mod tests {
         );
 
         let expected = vec![
-            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude1").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-1").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0").unwrap(),
         ];
 
         let actual: Vec<Uri> = (0..3).map(|_| s.next()).collect();
@@ -158,8 +358,9 @@ mod tests {
     #[test]
     fn one_depth_single_obj() {
         let mut s = UriProvider::new(
-            "http://10.0.1.24:9003".to_string(),
-            "bucket".to_string(),
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
             "my-dude".to_string(),
             1,
             1,
@@ -167,9 +368,9 @@ mod tests {
         );
 
         let expected = vec![
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/my-dude0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0").unwrap(),
         ];
 
         let actual: Vec<Uri> = (0..3).map(|_| s.next()).collect();
@@ -179,8 +380,9 @@ mod tests {
     #[test]
     fn one_depth_multi_obj() {
         let mut s = UriProvider::new(
-            "http://10.0.1.24:9003".to_string(),
-            "bucket".to_string(),
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
             "my-dude".to_string(),
             1,
             2,
@@ -188,9 +390,9 @@ mod tests {
         );
 
         let expected = vec![
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/my-dude1").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/my-dude0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/1").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0").unwrap(),
         ];
 
         let actual: Vec<Uri> = (0..3).map(|_| s.next()).collect();
@@ -200,8 +402,9 @@ mod tests {
     #[test]
     fn multi_depth_single_obj() {
         let mut s = UriProvider::new(
-            "http://10.0.1.24:9003".to_string(),
-            "bucket".to_string(),
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
             "my-dude".to_string(),
             2,
             1,
@@ -209,11 +412,11 @@ mod tests {
         );
 
         let expected = vec![
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/0/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/1/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/1/0/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/1/1/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/0/my-dude0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/1/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-1/0/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-1/1/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0/0").unwrap(),
         ];
 
         let actual: Vec<Uri> = (0..5).map(|_| s.next()).collect();
@@ -223,8 +426,9 @@ mod tests {
     #[test]
     fn multi_depth_multi_obj() {
         let mut s = UriProvider::new(
-            "http://10.0.1.24:9003".to_string(),
-            "bucket".to_string(),
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
             "my-dude".to_string(),
             2,
             2,
@@ -232,19 +436,308 @@ mod tests {
         );
 
         let expected = vec![
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/0/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/0/my-dude1").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/1/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/1/my-dude1").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/1/0/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/1/0/my-dude1").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/1/1/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/1/1/my-dude1").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/0/my-dude0").unwrap(),
-            Uri::from_str("http://10.0.1.24:9003/bucket/0/0/my-dude1").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0/1").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/1/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/1/1").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-1/0/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-1/0/1").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-1/1/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-1/1/1").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0/0").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/my-dude-0/0/1").unwrap(),
         ];
 
         let actual: Vec<Uri> = (0..10).map(|_| s.next()).collect();
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn virtual_no_depth_multi_obj() {
+        let mut s = UriProvider::new(
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Virtual,
+            "my-dude".to_string(),
+            0,
+            2,
+            0,
+        );
+
+        let expected = vec![
+            Uri::from_str("http://bucket.10.0.1.24:9003/my-dude-0").unwrap(),
+            Uri::from_str("http://bucket.10.0.1.24:9003/my-dude-1").unwrap(),
+            Uri::from_str("http://bucket.10.0.1.24:9003/my-dude-0").unwrap(),
+        ];
+
+        let actual: Vec<Uri> = (0..3).map(|_| s.next()).collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn virtual_multi_depth_single_obj() {
+        let mut s = UriProvider::new(
+            "https://s3.example.com",
+            "bucket",
+            Addressing::Virtual,
+            "my-dude".to_string(),
+            2,
+            1,
+            2,
+        );
+
+        let expected = vec![
+            Uri::from_str("https://bucket.s3.example.com/my-dude-0/0/0").unwrap(),
+            Uri::from_str("https://bucket.s3.example.com/my-dude-0/1/0").unwrap(),
+            Uri::from_str("https://bucket.s3.example.com/my-dude-1/0/0").unwrap(),
+        ];
+
+        let actual: Vec<Uri> = (0..3).map(|_| s.next()).collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn keyspace_size_covers_all_uris() {
+        let mut s = UriProvider::new(
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
+            "my-dude".to_string(),
+            2,
+            3,
+            2,
+        );
+
+        assert_eq!(s.keyspace_size(), 12);
+        let first_pass: Vec<Uri> = (0..12).map(|_| s.next()).collect();
+        assert_eq!(first_pass.iter().collect::<HashSet<_>>().len(), 12);
+        assert_eq!(s.next(), first_pass[0]);
+    }
+
+    #[test]
+    fn saturates_deep_keyspaces() {
+        // 10^20 folders of 10000 objects don't fit in a `usize`
+        let uri = |s: &mut UriProvider| s.next().to_string();
+        let mut s = UriProvider::new(
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
+            "my-dude".to_string(),
+            20,
+            10000,
+            10,
+        );
+        assert_eq!(s.keyspace_size(), usize::MAX);
+        let zeros = "0/".repeat(20);
+        assert_eq!(
+            uri(&mut s),
+            format!("http://10.0.1.24:9003/bucket/my-dude-{zeros}0")
+        );
+        assert_eq!(
+            uri(&mut s),
+            format!("http://10.0.1.24:9003/bucket/my-dude-{zeros}1")
+        );
+
+        let mut s = s.with_stride(1, 2);
+        assert_eq!(s.keyspace_size(), usize::MAX / 2);
+        assert_eq!(
+            uri(&mut s),
+            format!("http://10.0.1.24:9003/bucket/my-dude-{zeros}1")
+        );
+        assert_eq!(
+            uri(&mut s),
+            format!("http://10.0.1.24:9003/bucket/my-dude-{zeros}3")
+        );
+    }
+
+    #[test]
+    fn counts_full_cycles() {
+        // branches^depth * objs distinct keys before repeating, in the same order
+        for (depth, objs, branches) in [(0, 3, 0), (1, 2, 3), (3, 2, 2)] {
+            let mut s = UriProvider::new(
+                "http://10.0.1.24:9003",
+                "bucket",
+                Addressing::Path,
+                "my-dude".to_string(),
+                depth,
+                objs,
+                branches,
+            );
+            let size = s.keyspace_size();
+            assert_eq!(size, branches.pow(u32::try_from(depth).unwrap()) * objs);
+
+            let first_pass: Vec<Uri> = (0..size - 1).map(|_| s.next()).collect();
+            assert_eq!(s.cycles(), 0);
+            let last = s.next();
+            assert_eq!(s.cycles(), 1);
+            assert!(!first_pass.contains(&last));
+            assert_eq!(first_pass.iter().collect::<HashSet<_>>().len(), size - 1);
+
+            let second_pass: Vec<Uri> = (0..size - 1).map(|_| s.next()).collect();
+            assert_eq!(second_pass, first_pass);
+            assert_eq!(s.next(), last);
+            assert_eq!(s.cycles(), 2);
+        }
+    }
+
+    #[test]
+    fn cycles_through_keys() {
+        let keys = KeyList::parse("logs/2023/a b.txt\r\n\nimages/é.png\nlast\n").unwrap();
+        let mut s = UriProvider::new(
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
+            "my-dude".to_string(),
+            0,
+            1,
+            0,
+        )
+        .with_keys(keys, 4);
+
+        let expected = vec![
+            Uri::from_str("http://10.0.1.24:9003/bucket/images/%C3%A9.png").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/last").unwrap(),
+            Uri::from_str("http://10.0.1.24:9003/bucket/logs/2023/a%20b.txt").unwrap(),
+        ];
+
+        assert_eq!(s.keyspace_size(), 3);
+        let actual: Vec<Uri> = (0..3).map(|_| s.next()).collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn interleaves_the_shared_keyspace() {
+        // 2 folders of 3 objects shared by 4 connections
+        let uris = (0..4)
+            .map(|i| {
+                let mut s = UriProvider::new(
+                    "http://10.0.1.24:9003",
+                    "bucket",
+                    Addressing::Path,
+                    "my-dude".to_string(),
+                    1,
+                    3,
+                    2,
+                )
+                .with_stride(i, 4);
+                (0..3)
+                    .map(|_| s.next().path().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            uris[0],
+            [
+                "/bucket/my-dude-0/0",
+                "/bucket/my-dude-1/1",
+                "/bucket/my-dude-0/0"
+            ]
+        );
+        assert_eq!(
+            uris[1],
+            [
+                "/bucket/my-dude-0/1",
+                "/bucket/my-dude-1/2",
+                "/bucket/my-dude-0/1"
+            ]
+        );
+        assert_eq!(
+            uris[2],
+            [
+                "/bucket/my-dude-0/2",
+                "/bucket/my-dude-0/2",
+                "/bucket/my-dude-0/2"
+            ]
+        );
+        assert_eq!(
+            uris[3],
+            [
+                "/bucket/my-dude-1/0",
+                "/bucket/my-dude-1/0",
+                "/bucket/my-dude-1/0"
+            ]
+        );
+
+        let s = UriProvider::new(
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
+            "my-dude".to_string(),
+            1,
+            3,
+            2,
+        )
+        .with_stride(1, 4);
+        assert_eq!(s.keyspace_size(), 2);
+        assert_eq!(s.prefix_at(1), "1/");
+        assert_eq!(s.uri_at(3), s.uri_at(1));
+    }
+
+    #[test]
+    fn interleaves_the_keys() {
+        let keys = KeyList::parse("a\nb\nc\nd\ne\n").unwrap();
+        let mut s = UriProvider::new(
+            "http://h",
+            "bucket",
+            Addressing::Path,
+            String::new(),
+            0,
+            1,
+            0,
+        )
+        .with_keys(keys, 0)
+        .with_stride(1, 2);
+
+        assert_eq!(s.keyspace_size(), 2);
+        let actual = (0..3)
+            .map(|_| s.next().path().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(actual, ["/bucket/b", "/bucket/d", "/bucket/b"]);
+        assert_eq!(s.cycles(), 1);
+    }
+
+    #[test]
+    fn empty_key_file() {
+        assert!(KeyList::parse("").is_err());
+        assert!(KeyList::parse("\n  \n").is_err());
+    }
+
+    #[test]
+    fn uri_at_matches_order() {
+        let mut s = UriProvider::new(
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
+            "my-dude".to_string(),
+            2,
+            3,
+            2,
+        );
+
+        let indexed: Vec<Uri> = (0..24).map(|i| s.uri_at(i)).collect();
+        let sequential: Vec<Uri> = (0..24).map(|_| s.next()).collect();
+        assert_eq!(indexed, sequential);
+    }
+
+    #[test]
+    fn reports_progress_through_keyspace() {
+        let mut s = UriProvider::new(
+            "http://10.0.1.24:9003",
+            "bucket",
+            Addressing::Path,
+            "my-dude".to_string(),
+            2,
+            3,
+            2,
+        );
+        assert_eq!(s.prefix(), "0/0/");
+
+        for _ in 0..7 {
+            s.next();
+        }
+        assert_eq!(s.prefix(), "1/0/");
+        assert_eq!(s.prefix_at(6), "1/0/");
+        assert_eq!(s.prefix_at(11), "1/1/");
+    }
 }
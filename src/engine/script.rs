@@ -0,0 +1,201 @@
+//! # Script Engine
+//!
+//! Replays a hand-authored sequence of requests, e.g. a create, read, update and
+//! delete flow, each connection going through the script in order.
+//!
+//! A script has a request per line, of the form `METHOD PATH [NAME=VALUE ...]`, any
+//! headers following the path. Blank lines and those starting with `#` are skipped.
+
+use crate::connection::body::ResponseBody;
+use crate::engine::Engine;
+use crate::stats::RequestLabel;
+use crate::util;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::header::{HeaderMap, HeaderName};
+use hyper::http::request::Builder;
+use hyper::http::uri::PathAndQuery;
+use hyper::{Method, Request, Response, Uri};
+use std::path::Path;
+
+/// A request of a script
+#[derive(Debug, Clone)]
+pub struct ScriptedRequest {
+    /// `<METHOD> <path>`, the stats of the request are broken down by
+    pub label: RequestLabel,
+    pub method: Method,
+    pub path: PathAndQuery,
+    /// Values of `env:<VAR>` are read from the environment, as with `--headers`
+    pub headers: HeaderMap,
+}
+
+/// Reads the requests of the script in the given file
+pub fn from_file(path: &Path) -> Result<Vec<ScriptedRequest>> {
+    let script = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the request script {}", path.display()))?;
+    parse(&script).with_context(|| format!("Invalid request script {}", path.display()))
+}
+
+fn parse(s: &str) -> Result<Vec<ScriptedRequest>> {
+    let requests = s
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| parse_line(line).with_context(|| format!("line {}", i + 1)))
+        .collect::<Result<Vec<_>>>()?;
+    if requests.is_empty() {
+        bail!("Script must contain at least one request");
+    }
+    Ok(requests)
+}
+
+fn parse_line(line: &str) -> Result<ScriptedRequest> {
+    let mut fields = line.split_whitespace();
+    let (Some(method), Some(path)) = (fields.next(), fields.next()) else {
+        bail!("expected `METHOD PATH`, found `{}`", line.trim());
+    };
+    let headers = fields
+        .map(|header| {
+            let (name, value) = header
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid header `{header}`: no `=` found"))?;
+            let value = util::parse_header_value(value).map_err(|e| anyhow!(e))?;
+            Ok((name.parse::<HeaderName>()?, value))
+        })
+        .collect::<Result<HeaderMap>>()?;
+    Ok(ScriptedRequest {
+        label: RequestLabel(format!("{method} {path}").into()),
+        method: util::parse_method(method).map_err(|e| anyhow!(e))?,
+        path: path.parse()?,
+        headers,
+    })
+}
+
+/// An engine issuing the requests of a script in order, either going through it over
+/// and over or only once
+pub struct ScriptEngine {
+    requests: Vec<ScriptedRequest>,
+    /// The index of the request to issue next
+    next: usize,
+    once: bool,
+}
+
+impl ScriptEngine {
+    pub fn new(requests: Vec<ScriptedRequest>, once: bool) -> Self {
+        ScriptEngine {
+            requests,
+            next: 0,
+            once,
+        }
+    }
+
+    fn next_request(&mut self) -> &ScriptedRequest {
+        let i = self.next % self.requests.len();
+        self.next += 1;
+        &self.requests[i]
+    }
+}
+
+#[async_trait]
+impl Engine<Empty<Bytes>> for ScriptEngine {
+    fn name<'a>(&self) -> &'a str {
+        "script"
+    }
+
+    async fn setup(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn request(&mut self, req: Builder) -> Result<(Request<Empty<Bytes>>, usize)> {
+        let scripted = self.next_request();
+        let mut parts = req.uri_ref().unwrap().clone().into_parts();
+        parts.path_and_query = Some(scripted.path.clone());
+        let mut req = req
+            .method(scripted.method.clone())
+            .uri(Uri::from_parts(parts)?)
+            .extension(scripted.label.clone());
+
+        // As with the simple engine, the headers of the script replace any set by the
+        // connection
+        if let Some(headers) = req.headers_mut() {
+            headers.extend(scripted.headers.clone());
+        }
+
+        Ok((req.body(Empty::new())?, 0))
+    }
+
+    async fn response(&mut self, resp: &mut Response<ResponseBody>) -> Result<usize> {
+        let mut read = 0;
+        while let Some(next) = resp.frame().await {
+            if let Some(d) = next?.data_ref() {
+                read += d.len();
+            }
+        }
+        Ok(read)
+    }
+
+    fn finished(&self) -> bool {
+        self.once && self.next >= self.requests.len()
+    }
+
+    async fn cleanup(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::script::{parse, ScriptEngine};
+    use crate::engine::Engine;
+    use hyper::{Method, Request};
+
+    const SCRIPT: &str = "
+        # Create, read and delete an item
+        POST /items Content-Type=application/json
+        GET /items/1?verbose=true Accept=application/json X-Trace=abc
+
+        DELETE /items/1
+    ";
+
+    #[test]
+    fn parse_script() {
+        let requests = parse(SCRIPT).unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].method, Method::GET);
+        assert_eq!(requests[1].path, "/items/1?verbose=true");
+        assert_eq!(requests[1].headers["accept"], "application/json");
+        assert_eq!(requests[1].headers["x-trace"], "abc");
+        assert_eq!(&*requests[2].label.0, "DELETE /items/1");
+
+        assert!(parse("# Nothing but comments\n\n").is_err());
+        assert!(parse("GET").is_err());
+        assert!(parse("FETCH /items").is_err());
+        let e = parse("GET /items\nGET /items Accept").unwrap_err();
+        assert!(format!("{e:#}").starts_with("line 2:"), "{e:#}");
+    }
+
+    #[test]
+    fn goes_through_the_script() {
+        for once in [false, true] {
+            let mut engine = ScriptEngine::new(parse(SCRIPT).unwrap(), once);
+            let mut issued = vec![];
+            while !engine.finished() && issued.len() < 5 {
+                let req = Request::builder().uri("http://localhost:9000/ignored");
+                let (req, _) = futures::executor::block_on(engine.request(req)).unwrap();
+                issued.push(format!("{} {}", req.method(), req.uri()));
+            }
+
+            let expected = [
+                "POST http://localhost:9000/items",
+                "GET http://localhost:9000/items/1?verbose=true",
+                "DELETE http://localhost:9000/items/1",
+                "POST http://localhost:9000/items",
+                "GET http://localhost:9000/items/1?verbose=true",
+            ];
+            let expected = if once { &expected[..3] } else { &expected[..] };
+            assert_eq!(issued, expected);
+        }
+    }
+}
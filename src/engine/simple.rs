@@ -1,25 +1,93 @@
 //! # Simple Engine
 //!
 
+pub mod mix;
+
+use crate::connection::body::ResponseBody;
+use crate::engine::s3::size::SizeSampler;
 use crate::engine::Engine;
+use crate::stats::RequestLabel;
+use crate::util;
 use anyhow::Result;
 use async_trait::async_trait;
 use bytes::Bytes;
 use http_body_util::{BodyExt, Either, Empty, Full};
-use hyper::body::Incoming;
 use hyper::http::request::Builder;
-use hyper::{Request, Response};
+use hyper::{Request, Response, Uri};
+use mix::RequestTemplate;
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::rngs::StdRng;
+use rand::RngCore;
 
 /// An simple engine to generate loads to any given server. This workload
 /// consists of a single type of request, specifying the HTTP method,
-/// HTTP headers and body (if any).
+/// HTTP headers and body (if any), or of a weighted mix of such requests.
 pub struct SimpleEngine {
-    pub method: String,
-    pub headers: Vec<(String, String)>,
-    pub body: Option<Bytes>,
+    templates: Vec<RequestTemplate>,
+    weights: WeightedIndex<u32>,
+    rng: StdRng,
+    /// Generates the bodies in place of those of the templates, when set
+    bodies: Option<BodyGenerator>,
+}
+
+/// Generates bodies of varying size, part random and part zeros
+struct BodyGenerator {
+    sizes: SizeSampler,
+    /// Fraction of each body that's random, between 0 and 1
+    entropy: f64,
+    rng: StdRng,
+}
+
+impl BodyGenerator {
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn next(&mut self) -> Bytes {
+        let len = self.sizes.next();
+        let mut body = vec![0; len];
+        let random = ((len as f64 * self.entropy).round() as usize).min(len);
+        self.rng.fill_bytes(&mut body[..random]);
+        Bytes::from(body)
+    }
+}
+
+impl SimpleEngine {
+    /// Builds an engine picking between the templates according to their
+    /// weights, the picks being derived from the seed
+    pub fn new(templates: Vec<RequestTemplate>, seed: &str) -> Result<Self> {
+        let weights = WeightedIndex::new(templates.iter().map(|t| t.weight))?;
+        Ok(SimpleEngine {
+            templates,
+            weights,
+            rng: util::seeded_rng(seed),
+            bodies: None,
+        })
+    }
+
+    /// Sends a body of a size drawn from `sizes` with every request, `entropy` being
+    /// the percentage of it that's random, the bytes being derived from the seed
+    pub fn with_generated_bodies(mut self, sizes: SizeSampler, entropy: f64, seed: &str) -> Self {
+        self.bodies = Some(BodyGenerator {
+            sizes,
+            entropy: entropy / 100.0,
+            rng: util::seeded_rng(&format!("{seed}-body")),
+        });
+        self
+    }
+
+    fn next_template(&mut self) -> &RequestTemplate {
+        if self.templates.len() == 1 {
+            &self.templates[0]
+        } else {
+            &self.templates[self.weights.sample(&mut self.rng)]
+        }
+    }
 }
 
-#[async_trait(? Send)]
+#[async_trait]
 impl Engine<Either<Full<Bytes>, Empty<Bytes>>> for SimpleEngine {
     fn name<'a>(&self) -> &'a str {
         "simple"
@@ -33,22 +101,45 @@ impl Engine<Either<Full<Bytes>, Empty<Bytes>>> for SimpleEngine {
         &mut self,
         req: Builder,
     ) -> Result<(Request<Either<Full<Bytes>, Empty<Bytes>>>, usize)> {
-        let mut req = req.method(self.method.as_str());
+        let body = self.bodies.as_mut().map(BodyGenerator::next);
+        let template = self.next_template();
+        let body = body.or_else(|| template.body.clone());
+        let mut req = req.method(template.method.clone());
 
-        for (k, v) in &self.headers {
-            req = req.header(k, v);
+        if let Some(path) = &template.path {
+            let mut parts = req.uri_ref().unwrap().clone().into_parts();
+            parts.path_and_query = Some(path.clone());
+            req = req.uri(Uri::from_parts(parts)?);
         }
 
-        let req = match &self.body {
+        // The first value of each header replaces any set by the connection, further
+        // values are appended to it
+        if let Some(headers) = req.headers_mut() {
+            headers.extend(template.headers.clone());
+        }
+
+        if let Some(name) = &template.name {
+            req = req.extension(RequestLabel(name.clone()));
+        }
+
+        let len = body.as_ref().map_or_else(|| 0_usize, Bytes::len);
+        let req = match body {
             None => req.body(Either::Right(Empty::new())),
-            Some(r) => req.body(Either::Left(Full::new(r.clone()))),
+            Some(body) => req.body(Either::Left(Full::new(body))),
         }
         .unwrap();
 
-        Ok((req, self.body.as_ref().map_or_else(|| 0_usize, Bytes::len)))
+        Ok((req, len))
     }
 
-    async fn response(&mut self, resp: &mut Response<Incoming>) -> Result<usize> {
+    async fn warmup_request(
+        &mut self,
+        req: Builder,
+    ) -> Result<Option<Request<Either<Full<Bytes>, Empty<Bytes>>>>> {
+        Ok(Some(req.method("HEAD").body(Either::Right(Empty::new()))?))
+    }
+
+    async fn response(&mut self, resp: &mut Response<ResponseBody>) -> Result<usize> {
         let mut read = 0;
         while let Some(next) = resp.frame().await {
             let frame = next.unwrap();
@@ -63,3 +154,34 @@ impl Engine<Either<Full<Bytes>, Empty<Bytes>>> for SimpleEngine {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::s3::size::{SizeDistribution, SizeSampler};
+    use crate::engine::simple::BodyGenerator;
+    use crate::util;
+
+    #[test]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn generates_bodies_within_range() {
+        let mut bodies = BodyGenerator {
+            sizes: SizeSampler::new(
+                SizeDistribution::Uniform { min: 100, max: 200 },
+                util::seeded_rng("seed"),
+            ),
+            entropy: 0.25,
+            rng: util::seeded_rng("seed"),
+        };
+        for _ in 0..100 {
+            let body = bodies.next();
+            assert!((100..=200).contains(&body.len()));
+            let random = (body.len() as f64 * 0.25).round() as usize;
+            assert!(body[random..].iter().all(|b| *b == 0));
+            assert!(body[..random].iter().any(|b| *b != 0));
+        }
+    }
+}
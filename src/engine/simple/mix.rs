@@ -0,0 +1,134 @@
+//! # Request Mix
+//!
+//! A mix describes several request templates, each with a weight, that the
+//! simple engine picks from for every request it issues, e.g. to model 70% of
+//! traffic going to `GET /items` and 30% to `POST /orders` in a single run.
+
+use crate::util;
+use anyhow::{anyhow, bail, Result};
+use bytes::Bytes;
+use hyper::header::{HeaderMap, HeaderName};
+use hyper::http::uri::PathAndQuery;
+use hyper::Method;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+struct Mix {
+    templates: Vec<TemplateSpec>,
+}
+
+/// A single request template of a mix as described in the file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateSpec {
+    /// Name of the template in the summary, defaults to `<METHOD> <path>`
+    name: Option<String>,
+    weight: u32,
+    method: String,
+    /// Path (and query) of the request, defaults to that of the url
+    path: Option<String>,
+    /// Values of `env:<VAR>` are read from the environment, as with `--headers`
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+}
+
+/// A request variant the simple engine can issue
+#[derive(Debug, Clone)]
+pub struct RequestTemplate {
+    /// The name the stats of the template are broken down by, only set for templates of a mix
+    pub name: Option<Arc<str>>,
+    pub weight: u32,
+    pub method: Method,
+    pub path: Option<PathAndQuery>,
+    /// A header with several values is sent as a field line per value
+    pub headers: HeaderMap,
+    pub body: Option<Bytes>,
+}
+
+/// Reads the request templates of a mix from the given JSON file
+pub fn from_file(path: &Path) -> Result<Vec<RequestTemplate>> {
+    parse(&std::fs::read_to_string(path)?)
+}
+
+fn parse(s: &str) -> Result<Vec<RequestTemplate>> {
+    let mix: Mix = serde_json::from_str(s)?;
+    if mix.templates.is_empty() {
+        bail!("Mix must contain at least one request template");
+    }
+    if mix.templates.iter().all(|t| t.weight == 0) {
+        bail!("At least one request template of the mix must have a non-zero weight");
+    }
+
+    mix.templates
+        .into_iter()
+        .map(|t| {
+            let name = t
+                .name
+                .unwrap_or_else(|| format!("{} {}", t.method, t.path.as_deref().unwrap_or("/")));
+            Ok(RequestTemplate {
+                name: Some(name.into()),
+                weight: t.weight,
+                method: util::parse_method(&t.method).map_err(|e| anyhow!(e))?,
+                path: t.path.map(|p| p.parse()).transpose()?,
+                headers: parse_headers(t.headers)?,
+                body: t.body.map(Bytes::from),
+            })
+        })
+        .collect()
+}
+
+fn parse_headers(headers: BTreeMap<String, String>) -> Result<HeaderMap> {
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            let value = util::parse_header_value(&value).map_err(|e| anyhow!(e))?;
+            Ok((name.parse::<HeaderName>()?, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::simple::mix::parse;
+
+    #[test]
+    fn parse_templates() {
+        let templates = parse(
+            r#"[
+                {"weight": 70, "method": "GET", "path": "/items?limit=10"},
+                {"name": "orders", "weight": 30, "method": "POST", "path": "/orders",
+                 "headers": {"Content-Type": "application/json"}, "body": "{}"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(templates.len(), 2);
+        assert_eq!(templates[0].name.as_deref(), Some("GET /items?limit=10"));
+        assert_eq!(templates[0].weight, 70);
+        assert_eq!(templates[0].path.as_ref().unwrap().path(), "/items");
+        assert!(templates[0].body.is_none());
+        assert_eq!(templates[1].name.as_deref(), Some("orders"));
+        assert_eq!(templates[1].method, "POST");
+        assert_eq!(templates[1].headers.len(), 1);
+        assert_eq!(
+            templates[1].headers[hyper::header::CONTENT_TYPE],
+            "application/json"
+        );
+        assert_eq!(templates[1].body.as_deref(), Some(b"{}".as_slice()));
+    }
+
+    #[test]
+    fn parse_empty_mix() {
+        assert!(parse("[]").is_err());
+    }
+
+    #[test]
+    fn parse_zero_weights() {
+        assert!(parse(r#"[{"weight": 0, "method": "GET"}]"#).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+//! # Interval log
+//!
+//! Appends the throughput of every interval of a run to a CSV file, timestamped
+//! so that spikes can be lined up with external events such as failovers.
+
+use crate::stats::InstantStats;
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+const HEADER: &str = "timestamp,requests_per_second,bytes_written_per_second,bytes_read_per_second";
+
+/// A CSV log with a line per interval of the run
+pub struct IntervalLog {
+    file: File,
+}
+
+impl IntervalLog {
+    /// Opens the log for appending, writing the header when the file is new or empty
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{HEADER}")?;
+        }
+        Ok(IntervalLog { file })
+    }
+
+    /// Appends the throughput of the interval that just ended
+    pub fn append(&mut self, interval: &InstantStats) -> Result<()> {
+        writeln!(self.file, "{}", line(Utc::now(), interval))?;
+        Ok(())
+    }
+}
+
+fn line(timestamp: DateTime<Utc>, interval: &InstantStats) -> String {
+    format!(
+        "{},{},{},{}",
+        timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+        interval.requests_issued,
+        interval.bytes_written,
+        interval.bytes_read
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interval_log::line;
+    use crate::stats::InstantStats;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn formats_line() {
+        let timestamp = Utc.with_ymd_and_hms(2023, 7, 1, 12, 30, 5).unwrap();
+        let interval = InstantStats {
+            requests_issued: 1000,
+            bytes_written: 2048,
+            bytes_read: 0,
+        };
+        assert_eq!(
+            line(timestamp, &interval),
+            "2023-07-01T12:30:05.000Z,1000,2048,0"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! # Test reports
+//!
+//! Renders the SLA results of a run as a `JUnit` XML report so CI systems can
+//! track performance gates alongside their regular test results.
+
+use crate::sla::SlaResult;
+use std::fmt::Write;
+
+/// The SLA results of a single run or scenario stage, reported as a test suite
+pub struct Suite {
+    pub name: String,
+    pub results: Vec<SlaResult>,
+}
+
+/// Renders the suites as a `JUnit` XML document with a testcase per SLA threshold
+pub fn render(suites: &[Suite]) -> String {
+    let tests = suites.iter().map(|s| s.results.len()).sum::<usize>();
+    let failures = suites
+        .iter()
+        .flat_map(|s| &s.results)
+        .filter(|r| !r.passed)
+        .count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        "<testsuites name=\"loaded\" tests=\"{tests}\" failures=\"{failures}\">"
+    )
+    .unwrap();
+    for suite in suites {
+        let name = escape(&suite.name);
+        writeln!(
+            xml,
+            "  <testsuite name=\"{name}\" tests=\"{}\" failures=\"{}\">",
+            suite.results.len(),
+            suite.results.iter().filter(|r| !r.passed).count()
+        )
+        .unwrap();
+        for result in &suite.results {
+            let message = escape(&format!(
+                "threshold {}, actual {}",
+                result.threshold, result.actual
+            ));
+            if result.passed {
+                writeln!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{name}\">\n      <system-out>{message}</system-out>\n    </testcase>",
+                    result.name
+                )
+                .unwrap();
+            } else {
+                writeln!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{name}\">\n      <failure message=\"{message}\"/>\n    </testcase>",
+                    result.name
+                )
+                .unwrap();
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::junit::{render, Suite};
+    use crate::sla::SlaResult;
+
+    #[test]
+    fn renders_passed_and_failed_results() {
+        let suites = vec![Suite {
+            name: "stage <1>".to_string(),
+            results: vec![
+                SlaResult {
+                    name: "max-p99",
+                    threshold: "100ms".to_string(),
+                    actual: "12ms".to_string(),
+                    passed: true,
+                },
+                SlaResult {
+                    name: "min-throughput",
+                    threshold: "1000 req/s".to_string(),
+                    actual: "900 req/s".to_string(),
+                    passed: false,
+                },
+            ],
+        }];
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="loaded" tests="2" failures="1">
+  <testsuite name="stage &lt;1&gt;" tests="2" failures="1">
+    <testcase name="max-p99" classname="stage &lt;1&gt;">
+      <system-out>threshold 100ms, actual 12ms</system-out>
+    </testcase>
+    <testcase name="min-throughput" classname="stage &lt;1&gt;">
+      <failure message="threshold 1000 req/s, actual 900 req/s"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+        assert_eq!(expected, render(&suites));
+    }
+}
@@ -0,0 +1,160 @@
+//! # Logging
+//!
+//! Sets up the logger, either as plain text or as a JSON object per line for log
+//! aggregators, tagging the records logged on behalf of a connection with the worker
+//! and connection they came from.
+
+use crate::cli::LogFormat;
+use chrono::{SecondsFormat, Utc};
+use hyper::Uri;
+use log::Record;
+use serde::Serialize;
+use std::future::Future;
+use std::io::Write;
+
+tokio::task_local! {
+    static CONNECTION: ConnectionContext;
+}
+
+/// What the records logged on behalf of a connection are tagged with
+#[derive(Debug, Clone)]
+pub struct ConnectionContext {
+    pub worker_id: usize,
+    pub connection_id: usize,
+    /// The url the connection issues its requests to
+    pub url: String,
+    /// The uri of the request being issued, while there's one
+    pub request_uri: Option<String>,
+}
+
+/// A log record as a JSON object
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+struct JsonRecord<'a> {
+    timestamp: String,
+    level: &'a str,
+    target: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    worker_id: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection_id: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_uri: Option<String>,
+    message: String,
+}
+
+impl<'a> JsonRecord<'a> {
+    fn new(record: &'a Record, thread: Option<&'a str>) -> Self {
+        let context = CONNECTION.try_with(Clone::clone).ok();
+        JsonRecord {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            level: record.level().as_str(),
+            target: record.target(),
+            thread,
+            worker_id: context.as_ref().map(|c| c.worker_id),
+            connection_id: context.as_ref().map(|c| c.connection_id),
+            url: context.as_ref().map(|c| c.url.clone()),
+            request_uri: context.and_then(|c| c.request_uri),
+            message: record.args().to_string(),
+        }
+    }
+}
+
+/// Initializes the logger, whose level is still set through `RUST_LOG`
+pub fn init(format: LogFormat) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let thread = std::thread::current();
+            let json = serde_json::to_string(&JsonRecord::new(record, thread.name()))
+                .map_err(std::io::Error::from)?;
+            writeln!(buf, "{json}")
+        });
+    }
+    builder.init();
+}
+
+/// Tags the records logged while running the future of a connection with its context
+pub async fn scope<F: Future>(context: ConnectionContext, fut: F) -> F::Output {
+    CONNECTION.scope(context, fut).await
+}
+
+/// Carries the context of the current connection, if there's any, over to a future
+/// that's spawned off of it
+///
+/// The context is taken when this is called rather than once the future is polled,
+/// which is on the task it's spawned as.
+pub fn inherit<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    within(CONNECTION.try_with(Clone::clone).ok(), fut)
+}
+
+/// Tags the records logged while running the future, on behalf of the current
+/// connection if there's any, with the uri of the request it issues
+pub fn request<F: Future>(uri: &Uri, fut: F) -> impl Future<Output = F::Output> {
+    let context = CONNECTION.try_with(Clone::clone).ok();
+    let context = context.map(|context| ConnectionContext {
+        request_uri: Some(uri.to_string()),
+        ..context
+    });
+    within(context, fut)
+}
+
+async fn within<F: Future>(context: Option<ConnectionContext>, fut: F) -> F::Output {
+    match context {
+        Some(context) => scope(context, fut).await,
+        None => fut.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logging::{request, scope, ConnectionContext, JsonRecord};
+    use log::{Level, Record};
+    use serde_json::json;
+
+    /// A record logged as the connection failed
+    fn failure_json() -> serde_json::Value {
+        // The arguments only live as long as the expression they're formatted in
+        serde_json::to_value(JsonRecord::new(
+            &Record::builder()
+                .args(format_args!("Connection failed"))
+                .level(Level::Error)
+                .target("loaded::connection")
+                .build(),
+            Some("Worker 1"),
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn tags_records_with_the_connection() {
+        let json = failure_json();
+        assert_eq!(json["level"], "ERROR");
+        assert_eq!(json["message"], "Connection failed");
+        assert_eq!(json.get("connection_id"), None);
+
+        let context = ConnectionContext {
+            worker_id: 1,
+            connection_id: 3,
+            url: "http://localhost:9000".to_string(),
+            request_uri: None,
+        };
+        let json = futures::executor::block_on(scope(context.clone(), async { failure_json() }));
+        assert_eq!(json["thread"], "Worker 1");
+        assert_eq!(json["worker_id"], 1);
+        assert_eq!(json["connection_id"], 3);
+        assert_eq!(json["url"], json!("http://localhost:9000"));
+        assert_eq!(json.get("request_uri"), None);
+
+        let uri = "http://localhost:9000/items/1".parse().unwrap();
+        let json = futures::executor::block_on(scope(context, async {
+            request(&uri, async { failure_json() }).await
+        }));
+        assert_eq!(json["request_uri"], json!("http://localhost:9000/items/1"));
+        assert_eq!(json["connection_id"], 3);
+    }
+}
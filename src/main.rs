@@ -6,36 +6,40 @@
     clippy::similar_names
 )]
 
+mod baseline;
 pub mod cli;
 mod cmd;
 pub(crate) mod connection;
 mod engine;
+mod interval_log;
+mod junit;
+mod logging;
+mod run_config;
+mod scenario;
+mod sla;
+mod slowest;
+mod stability;
 pub mod stats;
+mod statsd;
 mod stream;
 mod util;
+mod webhook;
 pub mod worker;
 
 use crate::cli::{Loaded, LoadedCmd};
-use anyhow::{bail, Result};
+use anyhow::Result;
 use clap::Parser;
 
 fn main() -> Result<()> {
-    env_logger::init();
     let args = Loaded::parse();
+    logging::init(args.log_format);
     match args.loaded {
         LoadedCmd::GenCompletions { shell, out_dir } => {
             cmd::gen_completions::generate_completions(shell, out_dir)?;
         }
-        LoadedCmd::Run(args) => {
-            if args.connections < args.threads {
-                bail!(
-                    "Connections ({}) cannot be less than the number of threads ({}).",
-                    args.connections,
-                    args.threads
-                )
-            }
-            cmd::run::run(&args)?;
-        }
+        LoadedCmd::Run(args) => cmd::run::run(&args)?,
+        LoadedCmd::Merge(args) => cmd::merge::merge(&args)?,
+        LoadedCmd::Engines(args) => cmd::engines::engines(&args)?,
     }
 
     Ok(())
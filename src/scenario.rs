@@ -0,0 +1,115 @@
+//! # Scenario
+//!
+//! A scenario scripts a run as a series of stages executed one after the other,
+//! e.g. ramping the rate up over time for capacity testing.
+
+use crate::cli::RunCmd;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+pub struct Scenario {
+    pub stages: Vec<Stage>,
+}
+
+/// A single stage of a scenario, any values not specified fall back to those
+/// provided on the command line
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stage {
+    /// How long the stage runs for in seconds
+    pub duration: u64,
+    pub rate_limit: Option<u32>,
+    pub connections: Option<usize>,
+}
+
+impl Scenario {
+    pub fn from_file(path: &Path, threads: usize) -> Result<Self> {
+        let scenario = Self::parse(&std::fs::read_to_string(path)?)?;
+        for (i, stage) in scenario.stages.iter().enumerate() {
+            if let Some(connections) = stage.connections {
+                if connections < threads {
+                    bail!(
+                        "Stage {}: connections ({connections}) cannot be less than the number of threads ({threads}).",
+                        i + 1
+                    )
+                }
+            }
+        }
+        Ok(scenario)
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        let scenario: Scenario = serde_json::from_str(s)?;
+        if scenario.stages.is_empty() {
+            bail!("Scenario must contain at least one stage");
+        }
+        Ok(scenario)
+    }
+}
+
+impl Stage {
+    /// Applies the stage on top of the args provided on the command line
+    pub fn apply(&self, args: &RunCmd) -> RunCmd {
+        let mut args = args.clone();
+        args.duration = Some(Duration::from_secs(self.duration));
+        args.num_requests = None;
+        args.total_bytes = None;
+        args.scenario = None;
+        if self.rate_limit.is_some() {
+            args.rate_limit = self.rate_limit;
+        }
+        if let Some(connections) = self.connections {
+            args.connections = connections;
+        }
+        args
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{}s", self.duration))?;
+        if let Some(rate) = self.rate_limit {
+            f.write_str(&format!(", {rate} req/s"))?;
+        }
+        if let Some(connections) = self.connections {
+            f.write_str(&format!(", {connections} connections"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scenario::Scenario;
+
+    #[test]
+    fn parse_stages() {
+        let scenario = Scenario::parse(
+            r#"[{"duration": 60, "rate_limit": 1000}, {"duration": 120, "connections": 8}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.stages.len(), 2);
+        assert_eq!(scenario.stages[0].duration, 60);
+        assert_eq!(scenario.stages[0].rate_limit, Some(1000));
+        assert_eq!(scenario.stages[0].connections, None);
+        assert_eq!(scenario.stages[1].duration, 120);
+        assert_eq!(scenario.stages[1].rate_limit, None);
+        assert_eq!(scenario.stages[1].connections, Some(8));
+    }
+
+    #[test]
+    fn parse_empty_scenario() {
+        assert!(Scenario::parse("[]").is_err());
+    }
+
+    #[test]
+    fn parse_unknown_field() {
+        assert!(Scenario::parse(r#"[{"duration": 60, "rate": 1000}]"#).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! # SLA
+//!
+//! Service level thresholds that the results of a run are checked against,
+//! allowing loaded to act as a pass/fail performance gate.
+
+use crate::cli::RunCmd;
+use crate::stats::{RunStats, SummaryStats};
+use crate::util::format_duration;
+use bigdecimal::ToPrimitive;
+use std::fmt::{Display, Formatter};
+
+/// The outcome of checking a single SLA threshold
+#[derive(Debug)]
+pub struct SlaResult {
+    pub name: &'static str,
+    pub threshold: String,
+    pub actual: String,
+    pub passed: bool,
+}
+
+impl Display for SlaResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "{} [{}]: threshold {}, actual {}",
+            self.name,
+            if self.passed { "PASSED" } else { "FAILED" },
+            self.threshold,
+            self.actual
+        ))
+    }
+}
+
+/// Checks the SLA thresholds specified on the command line against the results of the run
+pub fn evaluate(args: &RunCmd, stats: &RunStats, summary: &SummaryStats) -> Vec<SlaResult> {
+    let mut results = vec![];
+
+    if let Some(max_p99) = args.max_p99 {
+        let p99 = stats.ttlb_latency_hist.value_at_quantile(0.99);
+        results.push(SlaResult {
+            name: "max-p99",
+            threshold: format_duration(max_p99.as_nanos()),
+            actual: format_duration(p99.into()),
+            passed: u128::from(p99) <= max_p99.as_nanos(),
+        });
+    }
+
+    if let Some(max_error_rate) = args.max_error_rate {
+        let error_rate = summary.error_rate();
+        results.push(SlaResult {
+            name: "max-error-rate",
+            threshold: format!("{max_error_rate}%"),
+            actual: format!("{error_rate:.3}%"),
+            passed: error_rate <= max_error_rate,
+        });
+    }
+
+    if let Some(min_throughput) = args.min_throughput {
+        let throughput = summary.mean_reqs_per_second().to_f64().unwrap_or_default();
+        results.push(SlaResult {
+            name: "min-throughput",
+            threshold: format!("{min_throughput} req/s"),
+            actual: format!("{throughput:.3} req/s"),
+            passed: throughput >= min_throughput,
+        });
+    }
+
+    results
+}
@@ -0,0 +1,159 @@
+//! # Slowest requests
+//!
+//! Keeps the slowest requests of a run along with what they were, pointing at the
+//! keys or paths behind the tail latencies that the histograms only quantify.
+
+use crate::util;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::{Display, Formatter};
+
+/// A request that was among the slowest of the run
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SlowRequest {
+    /// Latency from issuing the request until the response headers were received
+    #[serde(rename = "round_trip_time_ns")]
+    pub rtt_ns: u64,
+    pub method: String,
+    pub uri: String,
+    pub status: u16,
+}
+
+impl Display for SlowRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} ({})",
+            util::format_duration(self.rtt_ns.into()),
+            self.method,
+            self.uri,
+            self.status
+        )
+    }
+}
+
+/// The `capacity` slowest requests seen so far, by round trip time
+///
+/// Held in a min-heap so the fastest of them is the one evicted by a slower request,
+/// never holding more than `capacity` requests.
+#[derive(Debug, Default)]
+pub struct SlowestRequests {
+    capacity: usize,
+    heap: BinaryHeap<Reverse<SlowRequest>>,
+}
+
+impl SlowestRequests {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        SlowestRequests {
+            capacity,
+            heap: BinaryHeap::with_capacity(capacity),
+        }
+    }
+
+    /// The number of requests kept at most
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether any requests are kept at all
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Whether a request that took `rtt_ns` would make it in, to avoid describing
+    /// requests that wouldn't
+    #[must_use]
+    pub fn admits(&self, rtt_ns: u64) -> bool {
+        if self.heap.len() < self.capacity {
+            return true;
+        }
+        self.heap
+            .peek()
+            .is_some_and(|Reverse(fastest)| rtt_ns > fastest.rtt_ns)
+    }
+
+    pub fn record(&mut self, request: SlowRequest) {
+        if !self.admits(request.rtt_ns) {
+            return;
+        }
+        if self.heap.len() == self.capacity {
+            self.heap.pop();
+        }
+        self.heap.push(Reverse(request));
+    }
+
+    /// Keeps the slowest of both, and the larger of their capacities
+    pub fn merge(&mut self, other: &SlowestRequests) {
+        self.capacity = self.capacity.max(other.capacity);
+        for Reverse(request) in &other.heap {
+            self.record(request.clone());
+        }
+    }
+
+    /// The requests kept, slowest first
+    #[must_use]
+    pub fn sorted(&self) -> Vec<SlowRequest> {
+        let mut requests = self
+            .heap
+            .iter()
+            .map(|Reverse(request)| request.clone())
+            .collect::<Vec<_>>();
+        requests.sort_by(|a, b| b.cmp(a));
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::slowest::{SlowRequest, SlowestRequests};
+
+    fn request(rtt_ns: u64) -> SlowRequest {
+        SlowRequest {
+            rtt_ns,
+            method: "GET".to_string(),
+            uri: format!("/{rtt_ns}"),
+            status: 200,
+        }
+    }
+
+    #[test]
+    fn keeps_the_slowest() {
+        let mut slowest = SlowestRequests::new(3);
+        for rtt in [5, 1, 9, 3, 7, 2] {
+            slowest.record(request(rtt));
+        }
+        let rtts = slowest
+            .sorted()
+            .iter()
+            .map(|r| r.rtt_ns)
+            .collect::<Vec<_>>();
+        assert_eq!(rtts, [9, 7, 5]);
+    }
+
+    #[test]
+    fn merges_the_slowest_of_both() {
+        let mut a = SlowestRequests::new(0);
+        let mut b = SlowestRequests::new(2);
+        b.record(request(4));
+        b.record(request(8));
+        let mut c = SlowestRequests::new(2);
+        c.record(request(6));
+        a.merge(&b);
+        a.merge(&c);
+        let rtts = a.sorted().iter().map(|r| r.rtt_ns).collect::<Vec<_>>();
+        assert_eq!(rtts, [8, 6]);
+    }
+
+    #[test]
+    fn disabled_keeps_nothing() {
+        let mut slowest = SlowestRequests::new(0);
+        slowest.record(request(1));
+        assert!(!slowest.is_enabled());
+        assert!(slowest.sorted().is_empty());
+    }
+}
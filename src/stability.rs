@@ -0,0 +1,98 @@
+//! # Stability
+//!
+//! Detects when the throughput of a run has plateaued, so capacity tests can stop
+//! once they've reached steady state rather than after a fixed duration.
+
+use std::collections::VecDeque;
+
+/// Watches the requests per second of each report interval for the coefficient of
+/// variation (the standard deviation relative to the mean) over a sliding window of
+/// them to stay below a threshold for a number of intervals in a row
+pub struct StabilityDetector {
+    /// The coefficient of variation, as a percentage, the throughput must stay below
+    threshold: f64,
+    window: usize,
+    intervals: usize,
+    rates: VecDeque<f64>,
+    /// Number of intervals in a row the window has been below the threshold
+    stable_for: usize,
+}
+
+impl StabilityDetector {
+    pub fn new(threshold: f64, window: usize, intervals: usize) -> Self {
+        StabilityDetector {
+            threshold,
+            window,
+            intervals,
+            rates: VecDeque::with_capacity(window),
+            stable_for: 0,
+        }
+    }
+
+    /// Records the throughput of the latest interval, returning the mean throughput
+    /// over the window once it's stable
+    pub fn observe(&mut self, rate: f64) -> Option<f64> {
+        if self.rates.len() == self.window {
+            self.rates.pop_front();
+        }
+        self.rates.push_back(rate);
+        match self.variation() {
+            Some(cv) if cv < self.threshold => self.stable_for += 1,
+            _ => self.stable_for = 0,
+        }
+        (self.stable_for >= self.intervals).then(|| self.mean())
+    }
+
+    /// The coefficient of variation of the window as a percentage, once it's full and
+    /// there's any throughput at all
+    #[allow(clippy::cast_precision_loss)]
+    fn variation(&self) -> Option<f64> {
+        let mean = self.mean();
+        if self.rates.len() < self.window || mean <= 0.0 {
+            return None;
+        }
+        let variance =
+            self.rates.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / self.rates.len() as f64;
+        Some(variance.sqrt() / mean * 100.0)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn mean(&self) -> f64 {
+        self.rates.iter().sum::<f64>() / self.rates.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stability::StabilityDetector;
+
+    #[test]
+    fn stable_once_variation_settles() {
+        let mut detector = StabilityDetector::new(5.0, 3, 2);
+
+        // Ramping up, the window varies too much
+        for rate in [10.0, 30.0, 60.0] {
+            assert_eq!(detector.observe(rate), None);
+        }
+        // The window needs to be below the threshold for two intervals in a row
+        assert_eq!(detector.observe(100.0), None);
+        assert_eq!(detector.observe(100.0), None);
+        assert_eq!(detector.observe(101.0), None);
+        assert_eq!(detector.observe(99.0), Some(100.0));
+    }
+
+    #[test]
+    fn spike_resets_the_count() {
+        let mut detector = StabilityDetector::new(5.0, 2, 2);
+        assert_eq!(detector.observe(100.0), None);
+        assert_eq!(detector.observe(100.0), None);
+        assert_eq!(detector.observe(200.0), None);
+        assert_eq!(detector.observe(200.0), None);
+        assert_eq!(detector.observe(200.0), Some(200.0));
+
+        // No throughput at all isn't a plateau
+        let mut idle = StabilityDetector::new(5.0, 2, 1);
+        assert_eq!(idle.observe(0.0), None);
+        assert_eq!(idle.observe(0.0), None);
+    }
+}
@@ -1,24 +1,113 @@
+use crate::run_config::RunConfig;
+use crate::slowest::{SlowRequest, SlowestRequests};
 use crate::util::{format_duration, format_duration_f64};
+use anyhow::Result;
 use bigdecimal::{BigDecimal, ToPrimitive};
 use bytesize::ByteSize;
+use hdrhistogram::serialization::{Deserializer, Serializer as _, V2DeflateSerializer};
 use hdrhistogram::Histogram;
 use hyper::StatusCode;
-use serde::Serialize;
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Debug, Serialize)]
+/// The version of the shape of the JSON summary, bumped by any change to it that
+/// breaks its consumers: a field renamed, removed or changing type. Fields may be
+/// added without bumping it.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The summary of a run, which is also the JSON output consumed by other tools
+///
+/// Times are in nanoseconds and sizes in bytes. The totals and means are decimals
+/// serialized as strings to keep their precision.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct SummaryStats {
+    /// See [`SCHEMA_VERSION`]
+    schema_version: u32,
     total_runtime_ns: BigDecimal,
     total_bytes_written: BigDecimal,
     total_bytes_read: BigDecimal,
     total_reqs: BigDecimal,
+    /// Bytes transferred including those of failed requests, unlike the totals
+    /// above which only account for successful requests
+    gross_bytes_written: BigDecimal,
+    gross_bytes_read: BigDecimal,
     mean_reqs_per_second: BigDecimal,
     mean_bytes_written_per_second: BigDecimal,
     mean_bytes_read_per_second: BigDecimal,
+    /// Serialized as an array of `{code, reason, count}` objects, by status code
+    #[serde(with = "error_counts")]
     errors: HashMap<u16, usize>,
+    /// The start of the body of an error response of each status code, serialized as
+    /// an array of `{code, sample}` objects
+    #[serde(default, with = "error_samples")]
+    error_samples: HashMap<u16, String>,
+    etag_mismatches: usize,
+    inconsistent_reads: usize,
+    /// Objects read whose checksum matched the one the server returned with them
+    #[serde(default)]
+    checksums_verified: usize,
+    #[serde(default)]
+    checksum_mismatches: usize,
     round_trip_time_latency: LatencyStats,
     time_to_first_byte_latency: LatencyStats,
+    time_to_last_byte_latency: LatencyStats,
+    connection_setup_latency: LatencyStats,
+    dns_resolution_latency: LatencyStats,
+    request_size: SizeStats,
+    response_size: SizeStats,
+    connections_established: usize,
+    ipv4_connections: usize,
+    ipv6_connections: usize,
+    reconnections: usize,
+    /// Attempts to connect that failed as the local ephemeral ports ran out, which were
+    /// retried after backing off
+    #[serde(default)]
+    port_exhaustions: usize,
+    hits: usize,
+    misses: usize,
+    already_exists: usize,
+    not_modified: usize,
+    #[serde(default)]
+    keys_written: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    labels: Vec<LabelSummary>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    workers: Vec<WorkerSummary>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    slowest_requests: Vec<SlowRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    requests_per_connection: Option<ConnectionBalance>,
+    /// The percentage of the requests the latencies and sizes were recorded for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_sample_rate: Option<f64>,
+    /// The number of workers of an interrupted run that didn't finish within the grace
+    /// period, the requests they completed last possibly missing from the summary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unfinished_workers: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compression: Option<CompressionStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stable_throughput: Option<StableThroughput>,
+    /// The highest rates of any report interval, each rate on its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peak_throughput: Option<IntervalThroughput>,
+    /// The lowest rates of any report interval, each rate on its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trough_throughput: Option<IntervalThroughput>,
+    /// How long it took for every connection to start when ramping them up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full_concurrency_after_ns: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_breakdown: Option<PhaseBreakdown>,
+    /// The parameters the run was launched with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config: Option<RunConfig>,
 }
 
 impl SummaryStats {
@@ -28,7 +117,8 @@ impl SummaryStats {
         total_bytes_written: BigDecimal,
         total_bytes_read: BigDecimal,
         total_reqs: BigDecimal,
-        stats: RunStats,
+        stats: &RunStats,
+        percentiles: &[f64],
     ) -> Self {
         let ns_to_sec_factor = BigDecimal::from(10_i32.pow(9));
         let mean_reqs_per_second = (&total_reqs / &total_runtime_ns * &ns_to_sec_factor).round(6);
@@ -38,18 +128,455 @@ impl SummaryStats {
         let mean_bytes_read_per_second =
             (&total_bytes_read / (&total_runtime_ns / &ns_to_sec_factor)).round(6);
 
+        let gross_bytes_written =
+            &total_bytes_written + BigDecimal::from(stats.failed_bytes_written as u64);
+        let gross_bytes_read = &total_bytes_read + BigDecimal::from(stats.failed_bytes_read as u64);
+
         SummaryStats {
+            schema_version: SCHEMA_VERSION,
             total_runtime_ns,
             total_bytes_written,
             total_bytes_read,
             total_reqs,
+            gross_bytes_written,
+            gross_bytes_read,
             mean_reqs_per_second,
             mean_bytes_written_per_second,
             mean_bytes_read_per_second,
-            errors: stats.errors,
-            round_trip_time_latency: stats.rtt_latency_hist.into(),
-            time_to_first_byte_latency: stats.ttfb_latency_hist.into(),
+            errors: stats.errors.clone(),
+            error_samples: stats.error_samples.clone(),
+            etag_mismatches: stats.etag_mismatches,
+            inconsistent_reads: stats.inconsistent_reads,
+            checksums_verified: stats.checksums_verified,
+            checksum_mismatches: stats.checksum_mismatches,
+            round_trip_time_latency: LatencyStats::new(&stats.rtt_latency_hist, percentiles),
+            time_to_first_byte_latency: LatencyStats::new(&stats.ttfb_latency_hist, percentiles),
+            time_to_last_byte_latency: LatencyStats::new(&stats.ttlb_latency_hist, percentiles),
+            connection_setup_latency: LatencyStats::new(
+                &stats.conn_setup_latency_hist,
+                percentiles,
+            ),
+            dns_resolution_latency: LatencyStats::new(
+                &stats.dns_resolution_latency_hist,
+                percentiles,
+            ),
+            request_size: SizeStats::new(&stats.request_size_hist, percentiles),
+            response_size: SizeStats::new(&stats.response_size_hist, percentiles),
+            connections_established: stats.connections_established,
+            ipv4_connections: stats.connections_established - stats.ipv6_connections,
+            ipv6_connections: stats.ipv6_connections,
+            reconnections: stats.reconnections,
+            port_exhaustions: stats.port_exhaustions,
+            hits: stats.hits,
+            misses: stats.misses,
+            already_exists: stats.already_exists,
+            not_modified: stats.not_modified,
+            keys_written: stats.keys_written,
+            labels: LabelSummary::from_stats(&stats.labels, percentiles),
+            workers: vec![],
+            slowest_requests: stats.slowest.sorted(),
+            requests_per_connection: None,
+            latency_sample_rate: None,
+            unfinished_workers: None,
+            compression: CompressionStats::new(
+                stats.compressed_bytes_read,
+                stats.decompressed_bytes_read,
+            ),
+            stable_throughput: None,
+            peak_throughput: None,
+            trough_throughput: None,
+            full_concurrency_after_ns: None,
+            time_breakdown: PhaseBreakdown::new(&stats.phases),
+            config: None,
+        }
+    }
+
+    /// Includes a breakdown of the stats of each individual worker in the summary
+    pub(crate) fn with_workers(mut self, workers: Vec<WorkerSummary>) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Includes how evenly the requests were spread over the connections, from the
+    /// number of requests each of them completed
+    pub(crate) fn with_connection_requests(mut self, requests: &[usize]) -> Self {
+        self.requests_per_connection = ConnectionBalance::new(requests);
+        self
+    }
+
+    /// Notes the latencies and sizes were only recorded for this percentage of the
+    /// requests, of no consequence when all of them were
+    pub(crate) fn with_sample_rate(mut self, rate: Option<f64>) -> Self {
+        self.latency_sample_rate = rate.filter(|rate| *rate < 100.0);
+        self
+    }
+
+    /// Notes the summary is partial when some workers didn't finish within the grace
+    /// period of an interrupted run
+    pub(crate) fn with_unfinished_workers(mut self, workers: usize) -> Self {
+        self.unfinished_workers = Some(workers).filter(|workers| *workers > 0);
+        self
+    }
+
+    /// Notes the run was stopped once its throughput stabilized, if it was
+    pub(crate) fn with_stable_throughput(mut self, stable: Option<StableThroughput>) -> Self {
+        self.stable_throughput = stable;
+        self
+    }
+
+    /// Includes the throughput of the busiest and quietest report intervals, if the run
+    /// lasted any full interval
+    pub(crate) fn with_throughput_range(mut self, range: Option<ThroughputRange>) -> Self {
+        self.peak_throughput = range.map(|r| r.peak);
+        self.trough_throughput = range.map(|r| r.trough);
+        self
+    }
+
+    /// Notes when every connection had started, once ramping them up
+    pub(crate) fn with_full_concurrency(mut self, after: Option<Duration>) -> Self {
+        self.full_concurrency_after_ns = after.map(|after| after.as_nanos());
+        self
+    }
+
+    /// Echoes the parameters of the run, so the summary describes what produced it
+    pub(crate) fn with_config(mut self, config: RunConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// The caveat on the latencies and sizes when they were sampled
+    fn sampling_caveat(&self) -> Option<String> {
+        self.latency_sample_rate.map(|rate| {
+            format!(
+                "Latencies and sizes were sampled from {rate}% of the requests: \
+                 their percentiles are estimates, the tail ones the least reliable"
+            )
+        })
+    }
+
+    /// The caveats on the summary, printed ahead of the latencies
+    fn caveats(&self) -> impl Iterator<Item = String> {
+        [self.partial_caveat(), self.sampling_caveat()]
+            .into_iter()
+            .flatten()
+    }
+
+    /// The caveat on the whole summary when some workers were given up on
+    fn partial_caveat(&self) -> Option<String> {
+        self.unfinished_workers.map(|workers| {
+            format!(
+                "Partial results: {workers} workers didn't finish within the grace period, \
+                 the requests they completed last may be missing"
+            )
+        })
+    }
+
+    pub(crate) fn mean_reqs_per_second(&self) -> &BigDecimal {
+        &self.mean_reqs_per_second
+    }
+
+    pub(crate) fn time_to_last_byte_latency(&self) -> &LatencyStats {
+        &self.time_to_last_byte_latency
+    }
+
+    pub(crate) fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    /// The rows of the throughput table that only apply to some runs
+    fn optional_rows_to_markdown(&self) -> String {
+        let mut md = String::new();
+        if let Some(balance) = &self.requests_per_connection {
+            md.push_str(&format!("| Requests/Connection | {balance} |\n"));
+        }
+        if let Some(compression) = &self.compression {
+            md.push_str(&format!("| Compression | {compression} |\n"));
+        }
+        if let Some(stable) = &self.stable_throughput {
+            md.push_str(&format!("| Stable Throughput | {stable} |\n"));
+        }
+        if let (Some(peak), Some(trough)) = (&self.peak_throughput, &self.trough_throughput) {
+            md.push_str(&format!("| Peak Throughput | {peak} |\n"));
+            md.push_str(&format!("| Trough Throughput | {trough} |\n"));
+        }
+        if let Some(after) = self.full_concurrency_after_ns {
+            md.push_str(&format!(
+                "| Full Concurrency After | {} |\n",
+                format_duration(after)
+            ));
+        }
+        if let Some(breakdown) = &self.time_breakdown {
+            md.push_str(&format!("| Time Breakdown | {breakdown} |\n"));
+        }
+        if let Some(ratio) = self.hit_ratio() {
+            md.push_str(&format!("| Hits | {} |\n", self.hits));
+            md.push_str(&format!("| Misses | {} |\n", self.misses));
+            md.push_str(&format!("| Hit Ratio | {ratio:.2}% |\n"));
+        }
+        if self.checksums_verified > 0 {
+            md.push_str(&format!(
+                "| Checksums Verified | {} |\n",
+                self.checksums_verified
+            ));
+        }
+        if self.already_exists > 0 {
+            md.push_str(&format!("| Already Existed | {} |\n", self.already_exists));
+        }
+        if self.not_modified > 0 {
+            md.push_str(&format!("| Not Modified | {} |\n", self.not_modified));
+        }
+        if self.keys_written > 0 {
+            md.push_str(&format!("| Distinct Keys PUT | {} |\n", self.keys_written));
+        }
+        md
+    }
+
+    /// Renders the summary as GitHub-flavored Markdown tables
+    pub(crate) fn to_markdown(&self) -> String {
+        let ns_to_sec_factor = BigDecimal::from(10_i32.pow(9));
+        let bytes = |b: &BigDecimal| ByteSize::b(b.to_u64().unwrap()).to_string_as(true);
+
+        let mut md = String::from("### Throughput\n\n| Metric | Value |\n| --- | --- |\n");
+        for (metric, value) in [
+            (
+                "Total Runtime",
+                format!("{:.3}s", &self.total_runtime_ns / &ns_to_sec_factor),
+            ),
+            ("Total Requests", self.total_reqs.to_string()),
+            ("Total Bytes Written", bytes(&self.total_bytes_written)),
+            ("Total Bytes Read", bytes(&self.total_bytes_read)),
+            ("Gross Bytes Written", bytes(&self.gross_bytes_written)),
+            ("Gross Bytes Read", bytes(&self.gross_bytes_read)),
+            (
+                "Mean Requests/s",
+                format!("{:.2}", self.mean_reqs_per_second),
+            ),
+            (
+                "Mean Bytes Written/s",
+                bytes(&self.mean_bytes_written_per_second),
+            ),
+            ("Mean Bytes Read/s", bytes(&self.mean_bytes_read_per_second)),
+            (
+                "Connections Established",
+                self.connections_established.to_string(),
+            ),
+            ("IPv4 Connections", self.ipv4_connections.to_string()),
+            ("IPv6 Connections", self.ipv6_connections.to_string()),
+            ("Reconnections", self.reconnections.to_string()),
+            ("Port Exhaustions", self.port_exhaustions.to_string()),
+        ] {
+            md.push_str(&format!("| {metric} | {value} |\n"));
+        }
+        md.push_str(&self.optional_rows_to_markdown());
+
+        for caveat in self.caveats() {
+            md.push_str(&format!("\n> {caveat}\n"));
+        }
+        for (title, latency) in [
+            (
+                "Round Trip Time (RTT) Latency",
+                &self.round_trip_time_latency,
+            ),
+            (
+                "Time to First Byte (TTFB) Latency",
+                &self.time_to_first_byte_latency,
+            ),
+            (
+                "Time to Last Byte (TTLB) Latency",
+                &self.time_to_last_byte_latency,
+            ),
+            ("Connection Setup Latency", &self.connection_setup_latency),
+            ("DNS Resolution Latency", &self.dns_resolution_latency),
+        ] {
+            md.push_str(&format!("\n### {title}\n\n{}", latency.to_markdown()));
+        }
+        for (title, size) in [
+            ("Request Size", &self.request_size),
+            ("Response Size", &self.response_size),
+        ] {
+            md.push_str(&format!("\n### {title}\n\n{}", size.to_markdown()));
+        }
+
+        md.push_str(
+            "\n### Errors\n\n| Status | Reason | Count | Example |\n| --- | --- | --- | --- |\n",
+        );
+        let mut errors = self.errors.iter().collect::<Vec<_>>();
+        errors.sort();
+        for (status, count) in errors {
+            let reason = StatusCode::from_u16(*status)
+                .ok()
+                .and_then(|s| s.canonical_reason())
+                .unwrap_or_default();
+            let example = self
+                .error_samples
+                .get(status)
+                .map_or(String::new(), |s| s.replace('|', "\\|"));
+            md.push_str(&format!("| {status} | {reason} | {count} | {example} |\n"));
+        }
+        if self.etag_mismatches > 0 {
+            md.push_str(&format!(
+                "| - | ETag Mismatch | {} | |\n",
+                self.etag_mismatches
+            ));
+        }
+        if self.inconsistent_reads > 0 {
+            md.push_str(&format!(
+                "| - | Inconsistent Read | {} | |\n",
+                self.inconsistent_reads
+            ));
         }
+        if self.checksum_mismatches > 0 {
+            md.push_str(&format!(
+                "| - | Checksum Mismatch | {} | |\n",
+                self.checksum_mismatches
+            ));
+        }
+
+        md.push_str(&self.breakdowns_to_markdown());
+
+        md
+    }
+
+    /// Renders the breakdowns of the stats by label and by worker as Markdown tables
+    fn breakdowns_to_markdown(&self) -> String {
+        let mut md = String::new();
+        if !self.labels.is_empty() {
+            md.push_str(
+                "\n### Requests by Label\n\n| Label | Requests | Errors | Bytes Written | Bytes Read | Mean RTT | Max RTT | Mean TTFB | Mean TTLB |\n",
+            );
+            md.push_str("| --- | --- | --- | --- | --- | --- | --- | --- | --- |\n");
+            for l in &self.labels {
+                md.push_str(&format!(
+                    "| {} | {} | {} | {} | {} | {} | {} | {} | {} |\n",
+                    l.label,
+                    l.total_reqs,
+                    l.errors,
+                    ByteSize::b(l.total_bytes_written as u64).to_string_as(true),
+                    ByteSize::b(l.total_bytes_read as u64).to_string_as(true),
+                    format_duration_f64(l.round_trip_time_latency.mean),
+                    format_duration(l.round_trip_time_latency.max.into()),
+                    format_duration_f64(l.time_to_first_byte_latency.mean),
+                    format_duration_f64(l.time_to_last_byte_latency.mean)
+                ));
+            }
+        }
+
+        if !self.workers.is_empty() {
+            md.push_str("\n### Workers\n\n| Worker | Requests | Bytes Written | Bytes Read |\n");
+            md.push_str("| --- | --- | --- | --- |\n");
+            for w in &self.workers {
+                md.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    w.worker_id,
+                    w.total_reqs,
+                    ByteSize::b(w.total_bytes_written as u64).to_string_as(true),
+                    ByteSize::b(w.total_bytes_read as u64).to_string_as(true)
+                ));
+            }
+        }
+
+        if !self.slowest_requests.is_empty() {
+            md.push_str("\n### Slowest Requests\n\n| RTT | Method | URI | Status |\n");
+            md.push_str("| --- | --- | --- | --- |\n");
+            for r in &self.slowest_requests {
+                md.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    format_duration(r.rtt_ns.into()),
+                    r.method,
+                    r.uri,
+                    r.status
+                ));
+            }
+        }
+
+        md
+    }
+
+    /// Percentage of reads that found the object, if hits and misses were counted
+    fn hit_ratio(&self) -> Option<f64> {
+        let reads = self.hits + self.misses;
+        #[allow(clippy::cast_precision_loss)]
+        (reads > 0).then(|| self.hits as f64 / reads as f64 * 100.0)
+    }
+
+    /// Percentage of all requests that resulted in an error
+    pub(crate) fn error_rate(&self) -> f64 {
+        let total_errors = self.errors.values().sum::<usize>();
+        let total = &self.total_reqs + BigDecimal::from(total_errors as u64);
+        if total == BigDecimal::from(0) {
+            return 0.0;
+        }
+        (BigDecimal::from(total_errors as u64) / total * BigDecimal::from(100))
+            .to_f64()
+            .unwrap_or_default()
+    }
+
+    /// Writes how the throughput varied over the run, beyond its mean
+    fn fmt_throughput(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(stable) = &self.stable_throughput {
+            f.write_str(&format!("Stable Throughput: {stable}\n"))?;
+        }
+        if let (Some(peak), Some(trough)) = (&self.peak_throughput, &self.trough_throughput) {
+            f.write_str(&format!(
+                "Peak Interval: {peak}\nTrough Interval: {trough}\n"
+            ))?;
+        }
+        if let Some(after) = self.full_concurrency_after_ns {
+            f.write_str(&format!(
+                "Full Concurrency: reached after {}\n",
+                format_duration(after)
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the lines of the summary that only apply to some runs, e.g. the compression
+    /// of the responses or the outcomes of requests that are neither successes nor errors
+    fn fmt_optional_lines(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(compression) = &self.compression {
+            f.write_str(&format!("Compressed Responses: {compression}\n"))?;
+        }
+        if self.etag_mismatches > 0 {
+            f.write_str(&format!("ETag Mismatches: {}\n", self.etag_mismatches))?;
+        }
+        if self.inconsistent_reads > 0 {
+            f.write_str(&format!(
+                "Inconsistent Reads: {} (missing or stale after the read-after-write delay)\n",
+                self.inconsistent_reads
+            ))?;
+        }
+        if self.checksums_verified + self.checksum_mismatches > 0 {
+            f.write_str(&format!(
+                "Checksums Verified: {}, Mismatched: {}\n",
+                self.checksums_verified, self.checksum_mismatches
+            ))?;
+        }
+
+        if let Some(ratio) = self.hit_ratio() {
+            f.write_str(&format!(
+                "Hits: {}, Misses: {} ({ratio:.2}% hit ratio)\n",
+                self.hits, self.misses
+            ))?;
+        }
+
+        if self.already_exists > 0 {
+            f.write_str(&format!(
+                "Conditional PUTs of Existing Objects: {}\n",
+                self.already_exists
+            ))?;
+        }
+
+        if self.not_modified > 0 {
+            f.write_str(&format!(
+                "Conditional GETs Not Modified: {}\n",
+                self.not_modified
+            ))?;
+        }
+
+        if self.keys_written > 0 {
+            f.write_str(&format!("Distinct Keys PUT: {}\n", self.keys_written))?;
+        }
+        Ok(())
     }
 }
 
@@ -58,6 +585,11 @@ impl Display for SummaryStats {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let ns_to_sec_factor = BigDecimal::from(10_i32.pow(9));
 
+        for worker in &self.workers {
+            f.write_str(&format!("{worker}"))?;
+            f.write_str("\r\n")?;
+        }
+
         f.write_str(&format!(
             "Total Runtime: {:.3}s\n",
             &self.total_runtime_ns / &ns_to_sec_factor
@@ -68,19 +600,25 @@ impl Display for SummaryStats {
             ByteSize::b((&self.total_bytes_written).to_u64().unwrap()).to_string_as(true),
             ByteSize::b((&self.total_bytes_read).to_u64().unwrap()).to_string_as(true)
         ))?;
+        f.write_str(&format!(
+            "Gross Bytes Written: {}, Gross Bytes Read: {} (including failed requests)\n",
+            ByteSize::b((&self.gross_bytes_written).to_u64().unwrap()).to_string_as(true),
+            ByteSize::b((&self.gross_bytes_read).to_u64().unwrap()).to_string_as(true)
+        ))?;
         f.write_str(&format!(
             "Mean Requests/s: {:.2}, Mean Bytes Written/s: {:.3}, Mean Bytes Read/s: {:.3}\n",
             ByteSize::b(self.mean_reqs_per_second.to_u64().unwrap()).to_string_as(true),
             ByteSize::b((&self.mean_bytes_written_per_second).to_u64().unwrap()).to_string_as(true),
             ByteSize::b((&self.mean_bytes_read_per_second).to_u64().unwrap()).to_string_as(true),
         ))?;
+        self.fmt_throughput(f)?;
 
         let total_errors = self.errors.iter().fold(0, |acc, (_, v)| acc + *v);
         f.write_str(&format!("Errors: {total_errors}\n"))?;
         if !self.errors.is_empty() {
             for (k, v) in &self.errors {
                 f.write_str(&format!(
-                    "\t{} ({}): {}\n",
+                    "\t{} ({}): {}",
                     k,
                     StatusCode::from_u16(*k)
                         .unwrap()
@@ -88,29 +626,458 @@ impl Display for SummaryStats {
                         .unwrap(),
                     v
                 ))?;
+                match self.error_samples.get(k) {
+                    Some(sample) => f.write_str(&format!(", e.g. \"{sample}\"\n"))?,
+                    None => f.write_str("\n")?,
+                }
             }
         }
 
+        self.fmt_optional_lines(f)?;
+
+        for label in &self.labels {
+            f.write_str(&format!("{label}"))?;
+            f.write_str("\r\n")?;
+        }
+
+        for caveat in self.caveats() {
+            f.write_str(&format!("{caveat}\n"))?;
+        }
+        f.write_str("Round Trip Time (RTT) Latency Statistics:\n")?;
+        f.write_str(&format!("{}", self.round_trip_time_latency))?;
+        f.write_str("\r\n")?;
         f.write_str("Time to First Byte (TTFB) Latency Statistics:\n")?;
         f.write_str(&format!("{}", self.time_to_first_byte_latency))?;
         f.write_str("\r\n")?;
+        f.write_str("Time to Last Byte (TTLB) Latency Statistics:\n")?;
+        f.write_str(&format!("{}", self.time_to_last_byte_latency))?;
+        f.write_str("\r\n")?;
+        if let Some(breakdown) = &self.time_breakdown {
+            f.write_str(&format!("Time Breakdown (mean per request): {breakdown}\n"))?;
+        }
+        f.write_str("Request Size Statistics:\n")?;
+        f.write_str(&format!("{}", self.request_size))?;
+        f.write_str("Response Size Statistics:\n")?;
+        f.write_str(&format!("{}", self.response_size))?;
+        f.write_str("\r\n")?;
+        f.write_str(&format!(
+            "Connections Established: {} (IPv4: {}, IPv6: {}), Reconnections: {}\n",
+            self.connections_established,
+            self.ipv4_connections,
+            self.ipv6_connections,
+            self.reconnections
+        ))?;
+        if self.port_exhaustions > 0 {
+            f.write_str(&format!(
+                "Ephemeral Port Exhaustions: {} (retried after backing off)\n",
+                self.port_exhaustions
+            ))?;
+        }
+        if let Some(balance) = &self.requests_per_connection {
+            f.write_str(&format!("Requests per Connection: {balance}\n"))?;
+        }
+        f.write_str("Connection Setup Latency Statistics:\n")?;
+        f.write_str(&format!("{}", self.connection_setup_latency))?;
+        f.write_str("DNS Resolution Latency Statistics:\n")?;
+        f.write_str(&format!("{}", self.dns_resolution_latency))?;
+
+        if !self.slowest_requests.is_empty() {
+            f.write_str("Slowest Requests:\n")?;
+            for request in &self.slowest_requests {
+                f.write_str(&format!("{request}\n"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How much the compressed response bodies came down to once decompressed
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CompressionStats {
+    compressed_bytes_read: usize,
+    decompressed_bytes_read: usize,
+    /// Decompressed bytes for every compressed byte
+    ratio: f64,
+}
+
+impl CompressionStats {
+    /// Only when some of the bodies were compressed
+    #[allow(clippy::cast_precision_loss)]
+    fn new(compressed: usize, decompressed: usize) -> Option<Self> {
+        (compressed > 0).then(|| CompressionStats {
+            compressed_bytes_read: compressed,
+            decompressed_bytes_read: decompressed,
+            ratio: decompressed as f64 / compressed as f64,
+        })
+    }
+}
+
+impl Display for CompressionStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} decompressed to {} ({:.2}x)",
+            ByteSize::b(self.compressed_bytes_read as u64).to_string_as(true),
+            ByteSize::b(self.decompressed_bytes_read as u64).to_string_as(true),
+            self.ratio
+        )
+    }
+}
+
+/// The throughput a run stabilized at and how long into it that was detected
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct StableThroughput {
+    /// The mean over the window it was stable for
+    reqs_per_second: f64,
+    reached_after_ns: u128,
+}
+
+impl StableThroughput {
+    pub(crate) fn new(reqs_per_second: f64, reached_after: Duration) -> Self {
+        StableThroughput {
+            reqs_per_second,
+            reached_after_ns: reached_after.as_nanos(),
+        }
+    }
+}
+
+impl Display for StableThroughput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} Req/s, reached after {}",
+            self.reqs_per_second,
+            format_duration(self.reached_after_ns)
+        )
+    }
+}
+
+/// The rates of a report interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct IntervalThroughput {
+    reqs_per_second: usize,
+    bytes_written_per_second: usize,
+    bytes_read_per_second: usize,
+}
+
+impl IntervalThroughput {
+    fn new(rates: &InstantStats) -> Self {
+        IntervalThroughput {
+            reqs_per_second: rates.requests_issued,
+            bytes_written_per_second: rates.bytes_written,
+            bytes_read_per_second: rates.bytes_read,
+        }
+    }
+}
+
+impl Display for IntervalThroughput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} Req/s, Write/s: {}, Read/s: {}",
+            self.reqs_per_second,
+            ByteSize::b(self.bytes_written_per_second as u64).to_string_as(true),
+            ByteSize::b(self.bytes_read_per_second as u64).to_string_as(true)
+        )
+    }
+}
+
+/// The highest and lowest rates over the report intervals of a run, revealing a
+/// throughput that oscillates (e.g. through autoscaling or GC pauses) which the mean
+/// evens out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThroughputRange {
+    peak: IntervalThroughput,
+    trough: IntervalThroughput,
+}
+
+impl ThroughputRange {
+    /// Starts off from the rates of the first interval
+    #[must_use]
+    pub fn new(rates: &InstantStats) -> Self {
+        let throughput = IntervalThroughput::new(rates);
+        ThroughputRange {
+            peak: throughput,
+            trough: throughput,
+        }
+    }
+
+    /// Accounts for the rates of another interval
+    pub fn observe(&mut self, rates: &InstantStats) {
+        let (peak, trough) = (&mut self.peak, &mut self.trough);
+        peak.reqs_per_second = peak.reqs_per_second.max(rates.requests_issued);
+        peak.bytes_written_per_second = peak.bytes_written_per_second.max(rates.bytes_written);
+        peak.bytes_read_per_second = peak.bytes_read_per_second.max(rates.bytes_read);
+        trough.reqs_per_second = trough.reqs_per_second.min(rates.requests_issued);
+        trough.bytes_written_per_second = trough.bytes_written_per_second.min(rates.bytes_written);
+        trough.bytes_read_per_second = trough.bytes_read_per_second.min(rates.bytes_read);
+    }
+}
+
+/// Where the time of the mean successful request went, connection setup being spread
+/// over the requests issued over the connections
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PhaseBreakdown {
+    connect: Phase,
+    send: Phase,
+    /// Waiting on the server for the response headers
+    wait: Phase,
+    body: Phase,
+}
+
+/// The mean time the requests spent in a phase and its share of their total time
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Phase {
+    mean_ns: u64,
+    percent: f64,
+}
+
+impl PhaseBreakdown {
+    /// Only when there were successful requests
+    #[allow(clippy::cast_precision_loss)]
+    fn new(totals: &PhaseTotals) -> Option<Self> {
+        if totals.requests == 0 {
+            return None;
+        }
+        let total = totals.connect_ns + totals.send_ns + totals.wait_ns + totals.body_ns;
+        let phase = |ns: u128| Phase {
+            mean_ns: u64::try_from(ns / u128::from(totals.requests)).unwrap_or(u64::MAX),
+            percent: if total == 0 {
+                0.0
+            } else {
+                ns as f64 / total as f64 * 100.0
+            },
+        };
+        Some(PhaseBreakdown {
+            connect: phase(totals.connect_ns),
+            send: phase(totals.send_ns),
+            wait: phase(totals.wait_ns),
+            body: phase(totals.body_ns),
+        })
+    }
+}
+
+impl Display for PhaseBreakdown {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Connect {}, Send {}, Wait {}, Body {}",
+            self.connect, self.send, self.wait, self.body
+        )
+    }
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({:.1}%)",
+            format_duration(self.mean_ns.into()),
+            self.percent
+        )
+    }
+}
+
+/// The spread of the number of requests completed by each connection, where a
+/// connection well below the mean points at one that starved, e.g. stalled on a
+/// slow endpoint
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ConnectionBalance {
+    connections: usize,
+    min: usize,
+    max: usize,
+    mean: f64,
+    stddev: f64,
+}
+
+impl ConnectionBalance {
+    /// Summarizes the request counts of the connections, if there were any
+    #[allow(clippy::cast_precision_loss)]
+    fn new(requests: &[usize]) -> Option<Self> {
+        let min = *requests.iter().min()?;
+        let max = *requests.iter().max()?;
+        let n = requests.len() as f64;
+        let mean = requests.iter().sum::<usize>() as f64 / n;
+        let variance = requests
+            .iter()
+            .map(|&r| (r as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        Some(ConnectionBalance {
+            connections: requests.len(),
+            min,
+            max,
+            mean,
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+impl Display for ConnectionBalance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Mean: {:.2}, Stddev: {:.2}, Min: {}, Max: {} ({} connections)",
+            self.mean, self.stddev, self.min, self.max, self.connections
+        )
+    }
+}
+
+/// The stats of a single worker
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct WorkerSummary {
+    worker_id: usize,
+    total_reqs: usize,
+    total_bytes_written: usize,
+    total_bytes_read: usize,
+    round_trip_time_latency: LatencyStats,
+    time_to_last_byte_latency: LatencyStats,
+}
+
+impl WorkerSummary {
+    pub(crate) fn new(worker_id: usize, stats: &WorkerStats, percentiles: &[f64]) -> Self {
+        WorkerSummary {
+            worker_id,
+            total_reqs: stats.instant_stats.requests_issued,
+            total_bytes_written: stats.instant_stats.bytes_written,
+            total_bytes_read: stats.instant_stats.bytes_read,
+            round_trip_time_latency: LatencyStats::new(
+                &stats.run_stats.rtt_latency_hist,
+                percentiles,
+            ),
+            time_to_last_byte_latency: LatencyStats::new(
+                &stats.run_stats.ttlb_latency_hist,
+                percentiles,
+            ),
+        }
+    }
+}
+
+impl Display for WorkerSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "Worker {}: Requests: {}, Bytes Written: {}, Bytes Read: {}\n",
+            self.worker_id,
+            self.total_reqs,
+            ByteSize::b(self.total_bytes_written as u64).to_string_as(true),
+            ByteSize::b(self.total_bytes_read as u64).to_string_as(true)
+        ))?;
         f.write_str("Round Trip Time (RTT) Latency Statistics:\n")?;
         f.write_str(&format!("{}", self.round_trip_time_latency))?;
+        f.write_str("Time to Last Byte (TTLB) Latency Statistics:\n")?;
+        f.write_str(&format!("{}", self.time_to_last_byte_latency))
+    }
+}
 
-        Ok(())
+/// The stats of the requests sharing a label
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct LabelSummary {
+    label: String,
+    total_reqs: usize,
+    errors: usize,
+    total_bytes_written: usize,
+    total_bytes_read: usize,
+    round_trip_time_latency: LatencyStats,
+    time_to_first_byte_latency: LatencyStats,
+    time_to_last_byte_latency: LatencyStats,
+}
+
+impl LabelSummary {
+    fn from_stats(labels: &HashMap<Arc<str>, LabelStats>, percentiles: &[f64]) -> Vec<Self> {
+        let mut summaries = labels
+            .iter()
+            .map(|(label, stats)| LabelSummary {
+                label: label.to_string(),
+                total_reqs: stats.requests,
+                errors: stats.errors,
+                total_bytes_written: stats.bytes_written,
+                total_bytes_read: stats.bytes_read,
+                round_trip_time_latency: LatencyStats::new(&stats.rtt_latency_hist, percentiles),
+                time_to_first_byte_latency: LatencyStats::new(
+                    &stats.ttfb_latency_hist,
+                    percentiles,
+                ),
+                time_to_last_byte_latency: LatencyStats::new(&stats.ttlb_latency_hist, percentiles),
+            })
+            .collect::<Vec<_>>();
+        summaries.sort_by(|a, b| a.label.cmp(&b.label));
+        summaries
     }
 }
 
-#[derive(Debug, Serialize)]
-struct LatencyStats {
+impl Display for LabelSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "{}: Requests: {}, Errors: {}, Bytes Written: {}, Bytes Read: {}\n",
+            self.label,
+            self.total_reqs,
+            self.errors,
+            ByteSize::b(self.total_bytes_written as u64).to_string_as(true),
+            ByteSize::b(self.total_bytes_read as u64).to_string_as(true)
+        ))?;
+        f.write_str("Round Trip Time (RTT) Latency Statistics:\n")?;
+        f.write_str(&format!("{}", self.round_trip_time_latency))?;
+        f.write_str("Time to First Byte (TTFB) Latency Statistics:\n")?;
+        f.write_str(&format!("{}", self.time_to_first_byte_latency))?;
+        f.write_str("Time to Last Byte (TTLB) Latency Statistics:\n")?;
+        f.write_str(&format!("{}", self.time_to_last_byte_latency))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct LatencyStats {
     mean: f64,
     min: u64,
     max: u64,
-    p50: u64,
-    p95: u64,
-    p99: u64,
-    p999: u64,
-    p9999: u64,
+    #[serde(flatten)]
+    percentiles: Percentiles,
+}
+
+impl LatencyStats {
+    pub(crate) fn new(hist: &Histogram<u64>, percentiles: &[f64]) -> Self {
+        LatencyStats {
+            mean: hist.mean(),
+            min: hist.min(),
+            max: hist.max(),
+            percentiles: Percentiles::new(hist, percentiles),
+        }
+    }
+
+    /// The value of the percentile with the given label, e.g. `p99`, if it was reported
+    pub(crate) fn percentile(&self, label: &str) -> Option<u64> {
+        self.percentiles
+            .0
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, value)| *value)
+    }
+}
+
+impl LatencyStats {
+    pub(crate) fn to_markdown(&self) -> String {
+        let mut md = String::from("| Statistic | Latency |\n| --- | --- |\n");
+        md.push_str(&format!("| Mean | {} |\n", format_duration_f64(self.mean)));
+        md.push_str(&format!("| Min | {} |\n", format_duration(self.min.into())));
+        md.push_str(&format!("| Max | {} |\n", format_duration(self.max.into())));
+        for (label, value) in &self.percentiles.0 {
+            md.push_str(&format!(
+                "| {} | {} |\n",
+                label,
+                format_duration((*value).into())
+            ));
+        }
+        md
+    }
 }
 
 impl Display for LatencyStats {
@@ -121,46 +1088,309 @@ impl Display for LatencyStats {
             format_duration(self.min.into()),
             format_duration(self.max.into())
         ))?;
-        f.write_str(&format!("p50: {}\n", format_duration(self.p50.into())))?;
-        f.write_str(&format!("p95: {}\n", format_duration(self.p95.into())))?;
-        f.write_str(&format!("p99: {}\n", format_duration(self.p99.into())))?;
-        f.write_str(&format!("p999: {}\n", format_duration(self.p999.into())))?;
-        f.write_str(&format!("p9999: {}\n", format_duration(self.p9999.into())))
+        for (label, value) in &self.percentiles.0 {
+            f.write_str(&format!(
+                "{}: {}\n",
+                label,
+                format_duration((*value).into())
+            ))?;
+        }
+        Ok(())
     }
 }
 
-impl From<Histogram<u64>> for LatencyStats {
-    fn from(value: Histogram<u64>) -> Self {
-        LatencyStats {
-            mean: value.mean(),
-            min: value.min(),
-            max: value.max(),
-            p50: value.value_at_quantile(0.50),
-            p95: value.value_at_quantile(0.95),
-            p99: value.value_at_quantile(0.99),
-            p999: value.value_at_quantile(0.999),
-            p9999: value.value_at_quantile(0.9999),
+/// The distribution of the sizes of requests or responses, in bytes
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct SizeStats {
+    mean: f64,
+    min: u64,
+    max: u64,
+    #[serde(flatten)]
+    percentiles: Percentiles,
+}
+
+impl SizeStats {
+    fn new(hist: &Histogram<u64>, percentiles: &[f64]) -> Self {
+        SizeStats {
+            mean: hist.mean(),
+            min: hist.min(),
+            max: hist.max(),
+            percentiles: Percentiles::new(hist, percentiles),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut md = String::from("| Statistic | Size |\n| --- | --- |\n");
+        md.push_str(&format!("| Mean | {} |\n", format_size_f64(self.mean)));
+        md.push_str(&format!("| Min | {} |\n", format_size(self.min)));
+        md.push_str(&format!("| Max | {} |\n", format_size(self.max)));
+        for (label, value) in &self.percentiles.0 {
+            md.push_str(&format!("| {} | {} |\n", label, format_size(*value)));
+        }
+        md
+    }
+}
+
+impl Display for SizeStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "Mean: {}, Min: {}, Max: {}\n",
+            format_size_f64(self.mean),
+            format_size(self.min),
+            format_size(self.max)
+        ))?;
+        for (label, value) in &self.percentiles.0 {
+            f.write_str(&format!("{}: {}\n", label, format_size(*value)))?;
         }
+        Ok(())
     }
 }
 
+fn format_size(bytes: u64) -> String {
+    ByteSize::b(bytes).to_string_as(true)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_size_f64(bytes: f64) -> String {
+    format_size(bytes.round() as u64)
+}
+
+/// Latency values keyed by the label of the percentile they were taken at (e.g.
+/// `p99`, `p99_9`), serialized as a map in the order they were requested
+#[derive(Debug)]
+struct Percentiles(Vec<(String, u64)>);
+
+impl Percentiles {
+    fn new(hist: &Histogram<u64>, percentiles: &[f64]) -> Self {
+        Percentiles(
+            percentiles
+                .iter()
+                .map(|p| (percentile_label(*p), hist.value_at_quantile(p / 100.0)))
+                .collect(),
+        )
+    }
+}
+
+impl Serialize for Percentiles {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (label, value) in &self.0 {
+            map.serialize_entry(label, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Percentiles {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PercentilesVisitor;
+
+        impl<'de> Visitor<'de> for PercentilesVisitor {
+            type Value = Percentiles;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a map of percentile labels to values")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut percentiles = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    percentiles.push(entry);
+                }
+                Ok(Percentiles(percentiles))
+            }
+        }
+
+        deserializer.deserialize_map(PercentilesVisitor)
+    }
+}
+
+/// (De)serializes the errors keyed by status code as an array of [`ErrorCount`]s,
+/// which is easier on consumers than a map keyed by numbers
+mod error_counts {
+    use super::ErrorCount;
+    use hyper::StatusCode;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        errors: &HashMap<u16, usize>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut counts = errors
+            .iter()
+            .map(|(&code, &count)| ErrorCount {
+                code,
+                reason: StatusCode::from_u16(code)
+                    .ok()
+                    .and_then(|status| status.canonical_reason())
+                    .map(str::to_string),
+                count,
+            })
+            .collect::<Vec<_>>();
+        counts.sort_by_key(|c| c.code);
+        counts.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<u16, usize>, D::Error> {
+        let counts = Vec::<ErrorCount>::deserialize(deserializer)?;
+        Ok(counts.into_iter().map(|c| (c.code, c.count)).collect())
+    }
+}
+
+/// (De)serializes the error samples keyed by status code as an array of
+/// [`ErrorSampleEntry`]s, like the error counts
+mod error_samples {
+    use super::ErrorSampleEntry;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        samples: &HashMap<u16, String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut entries = samples
+            .iter()
+            .map(|(&code, sample)| ErrorSampleEntry {
+                code,
+                sample: sample.clone(),
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|e| e.code);
+        entries.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<u16, String>, D::Error> {
+        let entries = Vec::<ErrorSampleEntry>::deserialize(deserializer)?;
+        Ok(entries.into_iter().map(|e| (e.code, e.sample)).collect())
+    }
+}
+
+/// The start of the body of a response that failed with a status code
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct ErrorSampleEntry {
+    code: u16,
+    sample: String,
+}
+
+/// The number of requests that failed with a status code
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct ErrorCount {
+    code: u16,
+    /// The canonical reason phrase of the code, if it's a known one
+    reason: Option<String>,
+    count: usize,
+}
+
+/// Renders every recorded value of the histogram as CSV `(value, quantile, count)`
+/// rows, the cumulative quantile of each row (up to 1.0) tracing out the full latency CDF
+#[must_use]
+pub fn latency_cdf(hist: &Histogram<u64>) -> String {
+    let mut csv = String::from("latency_ns,quantile,count\n");
+    for v in hist.iter_recorded() {
+        csv.push_str(&format!(
+            "{},{:.6},{}\n",
+            v.value_iterated_to(),
+            v.quantile(),
+            v.count_at_value()
+        ));
+    }
+    csv
+}
+
+/// Serializes the histogram in the compressed V2 HDR histogram format, so the
+/// distributions of several runs can be merged later on
+pub fn hdr_encode(hist: &Histogram<u64>) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    V2DeflateSerializer::new().serialize(hist, &mut buf)?;
+    Ok(buf)
+}
+
+/// Deserializes a histogram in any of the V2 HDR histogram formats
+pub fn hdr_decode(mut bytes: &[u8]) -> Result<Histogram<u64>> {
+    Ok(Deserializer::new().deserialize(&mut bytes)?)
+}
+
+/// Labels a percentile in the form of `p50`, `p99`, `p99_9` (99.9th), etc., the
+/// fractional part set apart so that e.g. the 99.9th and the 9.99th don't collide
+fn percentile_label(percentile: f64) -> String {
+    format!("p{}", percentile.to_string().replace('.', "_"))
+}
+
 #[derive(Debug)]
 pub struct WorkerStats {
     pub instant_stats: InstantStats,
     pub run_stats: RunStats,
+    /// The round trip times recorded since the report interval started, alongside
+    /// those of the whole run
+    pub interval_rtt_hist: Histogram<u64>,
 }
 
-impl Default for WorkerStats {
-    fn default() -> Self {
+impl WorkerStats {
+    /// Histograms are recorded with `sigfig` significant figures
+    ///
+    /// # Panics
+    ///
+    /// If `sigfig` is greater than 5
+    #[must_use]
+    pub fn new(sigfig: u8) -> Self {
         WorkerStats {
             instant_stats: InstantStats::default(),
-            run_stats: RunStats {
-                errors: HashMap::new(),
-                rtt_latency_hist: Histogram::new(3).unwrap(),
-                ttfb_latency_hist: Histogram::new(3).unwrap(),
-            },
+            run_stats: RunStats::new(sigfig),
+            interval_rtt_hist: Histogram::new(sigfig).unwrap(),
+        }
+    }
+
+    /// Keeps the `n` slowest requests of the worker
+    #[must_use]
+    pub fn with_slowest(mut self, n: usize) -> Self {
+        self.run_stats.slowest = SlowestRequests::new(n);
+        self
+    }
+
+    /// Empty stats that are recorded like these, to be merged into them
+    #[must_use]
+    pub fn emptied(&self) -> Self {
+        WorkerStats {
+            instant_stats: InstantStats::default(),
+            run_stats: self.run_stats.emptied(),
+            interval_rtt_hist: Histogram::new_from(&self.interval_rtt_hist),
         }
     }
+
+    /// Takes the round trip times recorded since the previous call, starting the next
+    /// report interval
+    pub fn take_interval_rtt(&mut self) -> Histogram<u64> {
+        let empty = Histogram::new_from(&self.interval_rtt_hist);
+        std::mem::replace(&mut self.interval_rtt_hist, empty)
+    }
+
+    /// Takes the stats recorded so far, leaving them empty
+    #[must_use]
+    pub fn take(&mut self) -> Self {
+        let empty = self.emptied();
+        std::mem::replace(self, empty)
+    }
+
+    /// Adds the stats of `other` to these
+    ///
+    /// # Errors
+    ///
+    /// If the histograms of `other` can't be added to these
+    pub fn merge(&mut self, other: &WorkerStats) -> Result<()> {
+        self.instant_stats.requests_issued += other.instant_stats.requests_issued;
+        self.instant_stats.bytes_written += other.instant_stats.bytes_written;
+        self.instant_stats.bytes_read += other.instant_stats.bytes_read;
+        self.interval_rtt_hist.add(&other.interval_rtt_hist)?;
+        self.run_stats.merge(&other.run_stats)
+    }
 }
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -171,6 +1401,7 @@ pub struct InstantStats {
 }
 
 impl InstantStats {
+    /// The stats accumulated since an earlier snapshot of the same counters
     #[must_use]
     pub fn changed(&self, since: &InstantStats) -> InstantStats {
         let requests_issued = changed(since.requests_issued, self.requests_issued);
@@ -183,30 +1414,541 @@ impl InstantStats {
             bytes_read,
         }
     }
+
+    /// Scales the stats of an interval of the given length to per second rates
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn per_second(&self, interval: Duration) -> InstantStats {
+        let secs = interval.as_secs_f64();
+        let rate = |n: usize| (n as f64 / secs).round() as usize;
+        InstantStats {
+            requests_issued: rate(self.requests_issued),
+            bytes_written: rate(self.bytes_written),
+            bytes_read: rate(self.bytes_read),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct RunStats {
     pub errors: HashMap<u16, usize>,
+    /// The start of the body of the first error response of each status code
+    pub error_samples: HashMap<u16, String>,
+    /// Latency from issuing the request until the response headers are received
     pub rtt_latency_hist: Histogram<u64>,
+    /// Latency from issuing the request until the first byte of the response body
     pub ttfb_latency_hist: Histogram<u64>,
+    /// Latency from issuing the request until the response body has been fully read
+    pub ttlb_latency_hist: Histogram<u64>,
+    /// Latency of establishing a connection to the server, spanning both the
+    /// tcp connect and the http handshake
+    pub conn_setup_latency_hist: Histogram<u64>,
+    /// Latency of resolving the address of the server, only recorded for lookups
+    /// that weren't served from the DNS cache
+    pub dns_resolution_latency_hist: Histogram<u64>,
+    /// Bytes sent for each successful request
+    pub request_size_hist: Histogram<u64>,
+    /// Bytes read from the response of each successful request
+    pub response_size_hist: Histogram<u64>,
+    /// Bytes sent for requests that failed
+    pub failed_bytes_written: usize,
+    /// Bytes read from responses of requests that failed
+    pub failed_bytes_read: usize,
+    /// Number of PUTs whose returned `ETag` didn't match the digest that was sent
+    pub etag_mismatches: usize,
+    /// Number of objects read back missing or stale after the read-after-write delay
+    pub inconsistent_reads: usize,
+    /// Number of objects read whose checksum matched the one the server returned
+    pub checksums_verified: usize,
+    /// Number of objects read whose checksum didn't match the one the server returned
+    pub checksum_mismatches: usize,
+    /// Bytes read from the compressed bodies of successful responses, when decompressing
+    pub compressed_bytes_read: usize,
+    /// Bytes the compressed bodies came down to once decompressed
+    pub decompressed_bytes_read: usize,
+    /// Number of connections established to the server, including reconnections
+    pub connections_established: usize,
+    /// Number of the established connections that were made over IPv6
+    pub ipv6_connections: usize,
+    /// Number of reads that found the object, when tolerating misses
+    pub hits: usize,
+    /// Number of reads that found no object, when tolerating misses
+    pub misses: usize,
+    /// Number of conditional writes rejected because the object already existed
+    pub already_exists: usize,
+    /// Number of conditional reads of objects unchanged since their `ETag` was captured
+    pub not_modified: usize,
+    /// Number of distinct keys PUT successfully, summed over the connections
+    pub keys_written: usize,
+    /// Number of connections established to replace a dropped keep-alive connection
+    pub reconnections: usize,
+    /// Number of attempts to connect that failed as the local ephemeral ports ran out
+    pub port_exhaustions: usize,
+    /// Stats of the requests that were labelled by the engine, keyed by label
+    pub labels: HashMap<Arc<str>, LabelStats>,
+    /// The slowest requests, only kept when tracing them
+    pub slowest: SlowestRequests,
+    /// Time spent in each phase of the successful requests
+    pub phases: PhaseTotals,
 }
 
-impl Default for RunStats {
-    fn default() -> Self {
+/// The time spent in each phase of the requests, summed in nanoseconds to break down
+/// where the time of a request goes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseTotals {
+    /// The successful requests that spent their time in the phases
+    pub requests: u64,
+    /// Establishing the connections, shared by the requests issued over them
+    pub connect_ns: u128,
+    /// From issuing the request until it had been sent in full
+    pub send_ns: u128,
+    /// From the request having been sent until the response headers were received
+    pub wait_ns: u128,
+    /// From the response headers being received until the body had been fully read
+    pub body_ns: u128,
+}
+
+impl PhaseTotals {
+    pub fn merge(&mut self, other: &PhaseTotals) {
+        self.requests += other.requests;
+        self.connect_ns += other.connect_ns;
+        self.send_ns += other.send_ns;
+        self.wait_ns += other.wait_ns;
+        self.body_ns += other.body_ns;
+    }
+}
+
+/// Attached as an extension to a request by engines that want the stats of
+/// their requests broken down, e.g. by the template of a mix they came from or
+/// by the S3 operation
+#[derive(Debug, Clone)]
+pub struct RequestLabel(pub Arc<str>);
+
+/// Attached as an extension to a response by engines reading objects from a
+/// keyspace that may not have been fully written, to count hits and misses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOutcome {
+    Hit,
+    /// The object didn't exist, which isn't counted as an error
+    Miss,
+    /// The object was read back missing after the read-after-write delay, which the
+    /// engine counts as an inconsistent read rather than an error
+    Inconsistent,
+    /// The object still had the `ETag` the read was conditional on (304), which is
+    /// counted as a success
+    NotModified,
+}
+
+/// Attached as an extension to a response whose body was decompressed, with the bytes
+/// it took on the wire and those it came down to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decompressed {
+    pub compressed: usize,
+    pub decompressed: usize,
+}
+
+/// Attached as an extension to an error response, with the start of its body to
+/// show what the server said
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorSample(pub String);
+
+/// Attached as an extension to a response by engines issuing conditional writes,
+/// to count those rejected as the object already existed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// The precondition failed (412), which isn't counted as an error
+    AlreadyExists,
+}
+
+/// The stats of all requests sharing a [`RequestLabel`]
+#[derive(Debug)]
+pub struct LabelStats {
+    pub requests: usize,
+    pub errors: usize,
+    pub bytes_written: usize,
+    pub bytes_read: usize,
+    pub rtt_latency_hist: Histogram<u64>,
+    pub ttfb_latency_hist: Histogram<u64>,
+    pub ttlb_latency_hist: Histogram<u64>,
+}
+
+impl LabelStats {
+    /// Histograms are recorded with `sigfig` significant figures
+    ///
+    /// # Panics
+    ///
+    /// If `sigfig` is greater than 5
+    #[must_use]
+    pub fn new(sigfig: u8) -> Self {
+        LabelStats {
+            requests: 0,
+            errors: 0,
+            bytes_written: 0,
+            bytes_read: 0,
+            rtt_latency_hist: Histogram::new(sigfig).unwrap(),
+            ttfb_latency_hist: Histogram::new(sigfig).unwrap(),
+            ttlb_latency_hist: Histogram::new(sigfig).unwrap(),
+        }
+    }
+}
+
+impl RunStats {
+    /// Histograms are recorded with `sigfig` significant figures, trading memory
+    /// for the resolution of the tail latencies
+    ///
+    /// # Panics
+    ///
+    /// If `sigfig` is greater than 5
+    #[must_use]
+    pub fn new(sigfig: u8) -> Self {
         RunStats {
             errors: HashMap::new(),
-            rtt_latency_hist: Histogram::new(3).unwrap(),
-            ttfb_latency_hist: Histogram::new(3).unwrap(),
+            error_samples: HashMap::new(),
+            rtt_latency_hist: Histogram::new(sigfig).unwrap(),
+            ttfb_latency_hist: Histogram::new(sigfig).unwrap(),
+            ttlb_latency_hist: Histogram::new(sigfig).unwrap(),
+            conn_setup_latency_hist: Histogram::new(sigfig).unwrap(),
+            dns_resolution_latency_hist: Histogram::new(sigfig).unwrap(),
+            request_size_hist: Histogram::new(sigfig).unwrap(),
+            response_size_hist: Histogram::new(sigfig).unwrap(),
+            failed_bytes_written: 0,
+            failed_bytes_read: 0,
+            etag_mismatches: 0,
+            inconsistent_reads: 0,
+            checksums_verified: 0,
+            checksum_mismatches: 0,
+            compressed_bytes_read: 0,
+            decompressed_bytes_read: 0,
+            connections_established: 0,
+            ipv6_connections: 0,
+            hits: 0,
+            misses: 0,
+            already_exists: 0,
+            not_modified: 0,
+            keys_written: 0,
+            reconnections: 0,
+            port_exhaustions: 0,
+            labels: HashMap::new(),
+            slowest: SlowestRequests::default(),
+            phases: PhaseTotals::default(),
+        }
+    }
+
+    /// Empty stats with the same precision, keeping as many of the slowest requests
+    #[must_use]
+    pub fn emptied(&self) -> Self {
+        RunStats {
+            slowest: SlowestRequests::new(self.slowest.capacity()),
+            ..RunStats::new(self.rtt_latency_hist.sigfig())
+        }
+    }
+
+    /// Adds the stats of `other` to these
+    ///
+    /// # Errors
+    ///
+    /// If the histograms of `other` can't be added to these
+    pub fn merge(&mut self, other: &RunStats) -> Result<()> {
+        self.rtt_latency_hist.add(&other.rtt_latency_hist)?;
+        self.ttfb_latency_hist.add(&other.ttfb_latency_hist)?;
+        self.ttlb_latency_hist.add(&other.ttlb_latency_hist)?;
+        self.conn_setup_latency_hist
+            .add(&other.conn_setup_latency_hist)?;
+        self.dns_resolution_latency_hist
+            .add(&other.dns_resolution_latency_hist)?;
+        self.request_size_hist.add(&other.request_size_hist)?;
+        self.response_size_hist.add(&other.response_size_hist)?;
+        self.failed_bytes_written += other.failed_bytes_written;
+        self.failed_bytes_read += other.failed_bytes_read;
+        self.etag_mismatches += other.etag_mismatches;
+        self.inconsistent_reads += other.inconsistent_reads;
+        self.checksums_verified += other.checksums_verified;
+        self.checksum_mismatches += other.checksum_mismatches;
+        self.compressed_bytes_read += other.compressed_bytes_read;
+        self.decompressed_bytes_read += other.decompressed_bytes_read;
+        self.connections_established += other.connections_established;
+        self.ipv6_connections += other.ipv6_connections;
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.already_exists += other.already_exists;
+        self.not_modified += other.not_modified;
+        self.keys_written += other.keys_written;
+        self.reconnections += other.reconnections;
+        self.port_exhaustions += other.port_exhaustions;
+        self.slowest.merge(&other.slowest);
+        self.phases.merge(&other.phases);
+        let sigfig = self.rtt_latency_hist.sigfig();
+        for (label, stats) in &other.labels {
+            let acc = self
+                .labels
+                .entry(label.clone())
+                .or_insert_with(|| LabelStats::new(sigfig));
+            acc.requests += stats.requests;
+            acc.errors += stats.errors;
+            acc.bytes_written += stats.bytes_written;
+            acc.bytes_read += stats.bytes_read;
+            acc.rtt_latency_hist.add(&stats.rtt_latency_hist)?;
+            acc.ttfb_latency_hist.add(&stats.ttfb_latency_hist)?;
+            acc.ttlb_latency_hist.add(&stats.ttlb_latency_hist)?;
+        }
+        for (status, count) in &other.errors {
+            *self.errors.entry(*status).or_insert(0) += count;
+        }
+        for (status, sample) in &other.error_samples {
+            self.error_samples
+                .entry(*status)
+                .or_insert_with(|| sample.clone());
         }
+        Ok(())
     }
 }
 
+/// The counters only ever grow within a run, only a reset could take them back, in
+/// which case nothing is taken to have changed
 fn changed(prev: usize, curr: usize) -> usize {
-    if curr >= prev {
-        curr - prev
-    } else {
-        // wrapped
-        (usize::MAX - prev) + curr
+    curr.saturating_sub(prev)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{
+        latency_cdf, percentile_label, ConnectionBalance, InstantStats, PhaseBreakdown,
+        PhaseTotals, RunStats, SummaryStats, ThroughputRange, WorkerStats, SCHEMA_VERSION,
+    };
+    use bigdecimal::BigDecimal;
+    use hdrhistogram::Histogram;
+    use serde_json::json;
+
+    #[test]
+    fn changed_between_snapshots() {
+        let prev = InstantStats {
+            requests_issued: 10,
+            bytes_written: 1000,
+            bytes_read: 500,
+        };
+        let curr = InstantStats {
+            requests_issued: 15,
+            bytes_written: 1600,
+            bytes_read: 500,
+        };
+        let changed = curr.changed(&prev);
+        assert_eq!(changed.requests_issued, 5);
+        assert_eq!(changed.bytes_written, 600);
+        assert_eq!(changed.bytes_read, 0);
+
+        // A reset counter doesn't report a spike
+        let reset = InstantStats::default().changed(&curr);
+        assert_eq!(reset.requests_issued, 0);
+        assert_eq!(reset.bytes_written, 0);
+    }
+
+    #[test]
+    fn take_and_merge() {
+        let mut shared = WorkerStats::new(3).with_slowest(5);
+        let mut local = shared.emptied();
+        assert_eq!(local.run_stats.slowest.capacity(), 5);
+
+        local.instant_stats.requests_issued += 2;
+        local.run_stats.rtt_latency_hist.record(1_000).unwrap();
+        local.run_stats.errors.insert(503, 1);
+        shared.merge(&local.take()).unwrap();
+        assert_eq!(local.instant_stats.requests_issued, 0);
+        assert!(local.run_stats.rtt_latency_hist.is_empty());
+
+        local.run_stats.rtt_latency_hist.record(3_000).unwrap();
+        local.run_stats.errors.insert(503, 2);
+        shared.merge(&local.take()).unwrap();
+        assert_eq!(shared.instant_stats.requests_issued, 2);
+        assert_eq!(shared.run_stats.rtt_latency_hist.len(), 2);
+        assert_eq!(shared.run_stats.errors[&503], 3);
+    }
+
+    #[test]
+    fn summary_round_trips_through_json() {
+        let mut stats = RunStats::new(3);
+        for rtt in [1_000, 2_000, 9_000] {
+            stats.rtt_latency_hist.record(rtt).unwrap();
+        }
+        stats.errors.insert(503, 2);
+        stats.errors.insert(404, 1);
+        stats.errors.insert(599, 1);
+        let summary = SummaryStats::new(
+            BigDecimal::from(1_000_000_000),
+            BigDecimal::from(0),
+            BigDecimal::from(300),
+            BigDecimal::from(3),
+            &stats,
+            &[50.0, 99.9],
+        );
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["schema_version"], json!(SCHEMA_VERSION));
+        assert_eq!(
+            json["errors"],
+            json!([
+                {"code": 404, "reason": "Not Found", "count": 1},
+                {"code": 503, "reason": "Service Unavailable", "count": 2},
+                {"code": 599, "reason": null, "count": 1},
+            ])
+        );
+        assert_eq!(
+            json["round_trip_time_latency"]["p99_9"],
+            json!(stats.rtt_latency_hist.value_at_quantile(0.999))
+        );
+
+        let parsed: SummaryStats = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(parsed.errors, stats.errors);
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn traces_out_the_latency_cdf() {
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+        for (latency, count) in [(1_000, 5), (2_000, 3), (50_000, 2)] {
+            hist.record_n(latency, count).unwrap();
+        }
+        let csv = latency_cdf(&hist);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("latency_ns,quantile,count"));
+        let rows = lines
+            .map(|line| {
+                let fields = line.split(',').collect::<Vec<_>>();
+                (
+                    fields[0].parse::<u64>().unwrap(),
+                    fields[1].parse::<f64>().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+        assert!(rows.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        assert!(csv.contains("\n1000,0.500000,5\n"), "{csv}");
+        assert!(csv.ends_with("\n50015,1.000000,2\n"), "{csv}");
+    }
+
+    #[test]
+    fn summarizes_the_balance_of_connections() {
+        let balance = ConnectionBalance::new(&[2, 4, 4, 4, 5, 5, 7, 9]).unwrap();
+        assert_eq!(
+            balance.to_string(),
+            "Mean: 5.00, Stddev: 2.00, Min: 2, Max: 9 (8 connections)"
+        );
+        assert!(ConnectionBalance::new(&[]).is_none());
+    }
+
+    #[test]
+    fn labels_percentiles_apart() {
+        let labels = [50.0, 99.0, 99.9, 9.99, 99.99].map(percentile_label);
+        assert_eq!(labels, ["p50", "p99", "p99_9", "p9_99", "p99_99"]);
+    }
+
+    #[test]
+    fn keeps_the_first_error_sample() {
+        let mut stats = RunStats::new(3);
+        let mut other = stats.emptied();
+        stats.errors.insert(503, 1);
+        stats
+            .error_samples
+            .insert(503, "<Error><Code>SlowDown</Code></Error>".to_string());
+        other.errors.insert(503, 1);
+        other.errors.insert(500, 1);
+        other.error_samples.insert(503, "Later".to_string());
+        other
+            .error_samples
+            .insert(500, "Internal | Error".to_string());
+        stats.merge(&other).unwrap();
+        assert_eq!(
+            stats.error_samples[&503],
+            "<Error><Code>SlowDown</Code></Error>"
+        );
+
+        let summary = SummaryStats::new(
+            BigDecimal::from(1_000_000_000),
+            BigDecimal::from(0),
+            BigDecimal::from(0),
+            BigDecimal::from(0),
+            &stats,
+            &[50.0],
+        );
+        assert!(summary.to_string().contains(
+            "503 (Service Unavailable): 2, e.g. \"<Error><Code>SlowDown</Code></Error>\""
+        ));
+        assert!(summary
+            .to_markdown()
+            .contains("| 500 | Internal Server Error | 1 | Internal \\| Error |"));
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(
+            json["error_samples"],
+            json!([
+                {"code": 500, "sample": "Internal | Error"},
+                {"code": 503, "sample": "<Error><Code>SlowDown</Code></Error>"},
+            ])
+        );
+        let parsed: SummaryStats = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.error_samples, stats.error_samples);
+    }
+
+    #[test]
+    fn phases_break_down_the_mean_request() {
+        assert!(PhaseBreakdown::new(&PhaseTotals::default()).is_none());
+
+        // A connection whose setup is spread over the 4 requests issued over it
+        let mut totals = PhaseTotals {
+            requests: 4,
+            connect_ns: 4_000,
+            send_ns: 2_000,
+            wait_ns: 12_000,
+            body_ns: 2_000,
+        };
+        totals.merge(&PhaseTotals::default());
+        let breakdown = PhaseBreakdown::new(&totals).unwrap();
+        assert_eq!(breakdown.connect.mean_ns, 1_000);
+        assert_eq!(breakdown.wait.mean_ns, 3_000);
+        assert!((breakdown.wait.percent - 60.0).abs() < f64::EPSILON);
+        assert_eq!(
+            breakdown.to_string(),
+            "Connect 1.0us (20.0%), Send 500ns (10.0%), Wait 3.0us (60.0%), Body 500ns (10.0%)"
+        );
+    }
+
+    #[test]
+    fn interval_rtt_starts_over_once_taken() {
+        let mut worker = WorkerStats::new(3);
+        let mut connection = worker.emptied();
+        for rtt in [1_000, 2_000, 100_000] {
+            connection.interval_rtt_hist.record(rtt).unwrap();
+            connection.run_stats.rtt_latency_hist.record(rtt).unwrap();
+        }
+        worker.merge(&connection).unwrap();
+
+        let interval = worker.take_interval_rtt();
+        assert_eq!(interval.len(), 3);
+        assert_eq!(interval.value_at_quantile(0.5), 2_000);
+        assert!(worker.interval_rtt_hist.is_empty());
+        // The whole run keeps them
+        assert_eq!(worker.run_stats.rtt_latency_hist.len(), 3);
+    }
+
+    #[test]
+    fn peak_and_trough_of_the_intervals() {
+        let rates = |requests_issued, bytes_written, bytes_read| InstantStats {
+            requests_issued,
+            bytes_written,
+            bytes_read,
+        };
+        let mut range = ThroughputRange::new(&rates(100, 1024, 0));
+        range.observe(&rates(40, 4096, 0));
+        range.observe(&rates(70, 2048, 8192));
+        assert_eq!(
+            range.peak.to_string(),
+            "100 Req/s, Write/s: 4.0 kiB, Read/s: 8.0 kiB"
+        );
+        assert_eq!(
+            range.trough.to_string(),
+            "40 Req/s, Write/s: 1.0 kiB, Read/s: 0 B"
+        );
     }
 }
@@ -0,0 +1,205 @@
+//! # Statsd
+//!
+//! Pushes the interval metrics of a run to a statsd (or `DogStatsD`) server
+//! over UDP, as counters for the requests and bytes and as timers sampled
+//! from the latency distribution of the interval.
+
+use crate::stats::{InstantStats, WorkerStats};
+use anyhow::{anyhow, Result};
+use hdrhistogram::Histogram;
+use log::debug;
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// The prefix of every metric name
+const PREFIX: &str = "loaded";
+
+/// The number of timer samples sent per latency histogram each interval,
+/// bounding the packet volume regardless of the throughput
+const TIMER_SAMPLES: u64 = 50;
+
+/// Keeps datagrams under the typical MTU so they aren't fragmented
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+/// Pushes the metrics of each interval to a statsd server
+pub struct StatsdReporter {
+    socket: UdpSocket,
+    errors: usize,
+    sigfig: u8,
+    rtt: Histogram<u64>,
+    ttfb: Histogram<u64>,
+    ttlb: Histogram<u64>,
+}
+
+impl StatsdReporter {
+    /// Binds a local socket that sends to the statsd server at `addr` (`host:port`),
+    /// the latency histograms having `sigfig` significant figures
+    ///
+    /// The socket is bound to the IP version of the first address the server resolves to.
+    pub fn connect(addr: &str, sigfig: u8) -> Result<Self> {
+        let server = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("{addr} resolved to no address"))?;
+        let local = match server {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind((local, 0))?;
+        socket.connect(server)?;
+        Ok(StatsdReporter {
+            socket,
+            errors: 0,
+            sigfig,
+            rtt: Histogram::new(sigfig)?,
+            ttfb: Histogram::new(sigfig)?,
+            ttlb: Histogram::new(sigfig)?,
+        })
+    }
+
+    /// Sends the counters of the interval along with timers sampled from the
+    /// latency recorded by the workers since the previous push
+    pub fn push(
+        &mut self,
+        interval: &InstantStats,
+        stats: &[Arc<RwLock<WorkerStats>>],
+    ) -> Result<()> {
+        let mut rtt = Histogram::new(self.sigfig)?;
+        let mut ttfb = Histogram::new(self.sigfig)?;
+        let mut ttlb = Histogram::new(self.sigfig)?;
+        let mut errors = 0;
+        for s in stats {
+            let guard = s.blocking_read();
+            rtt.add(&guard.run_stats.rtt_latency_hist)?;
+            ttfb.add(&guard.run_stats.ttfb_latency_hist)?;
+            ttlb.add(&guard.run_stats.ttlb_latency_hist)?;
+            errors += guard.run_stats.errors.values().sum::<usize>();
+        }
+
+        let mut lines = vec![
+            counter("requests", interval.requests_issued),
+            counter("errors", errors.saturating_sub(self.errors)),
+            counter("bytes_written", interval.bytes_written),
+            counter("bytes_read", interval.bytes_read),
+        ];
+        self.errors = errors;
+        for (name, current, previous) in [
+            ("latency.rtt", rtt, &mut self.rtt),
+            ("latency.ttfb", ttfb, &mut self.ttfb),
+            ("latency.ttlb", ttlb, &mut self.ttlb),
+        ] {
+            let mut delta = current.clone();
+            delta.subtract(&*previous)?;
+            lines.extend(timers(name, &delta));
+            *previous = current;
+        }
+
+        for datagram in datagrams(&lines) {
+            // statsd is best effort, a server that isn't listening shouldn't fail the run
+            if let Err(e) = self.socket.send(datagram.as_bytes()) {
+                debug!("Failed to push metrics to statsd: {e}");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn counter(name: &str, value: usize) -> String {
+    format!("{PREFIX}.{name}:{value}|c")
+}
+
+/// Samples evenly spaced quantiles of the histogram as timers in milliseconds,
+/// with a sample rate so the server scales their count back up to the number
+/// of requests of the interval
+#[allow(clippy::cast_precision_loss)]
+fn timers(name: &str, hist: &Histogram<u64>) -> Vec<String> {
+    if hist.is_empty() {
+        return vec![];
+    }
+    let samples = TIMER_SAMPLES.min(hist.len());
+    let rate = samples as f64 / hist.len() as f64;
+    (0..samples)
+        .map(|i| {
+            let quantile = (i as f64 + 0.5) / samples as f64;
+            let millis = hist.value_at_quantile(quantile) as f64 / 1_000_000.0;
+            if samples == hist.len() {
+                format!("{PREFIX}.{name}:{millis:.3}|ms")
+            } else {
+                format!("{PREFIX}.{name}:{millis:.3}|ms|@{rate:.6}")
+            }
+        })
+        .collect()
+}
+
+/// Packs the metric lines into newline separated datagrams
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams = vec![];
+    let mut current = String::new();
+    for line in lines {
+        if !current.is_empty() && current.len() + 1 + line.len() > MAX_DATAGRAM_SIZE {
+            datagrams.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        write!(current, "{line}").unwrap();
+    }
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+    datagrams
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::statsd::{counter, datagrams, timers, StatsdReporter, MAX_DATAGRAM_SIZE};
+    use hdrhistogram::Histogram;
+    use std::net::UdpSocket;
+
+    #[test]
+    fn formats_counters() {
+        assert_eq!(counter("requests", 42), "loaded.requests:42|c");
+    }
+
+    #[test]
+    fn samples_timers() {
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+        hist.record_n(1_000_000, 3).unwrap();
+        assert_eq!(
+            timers("latency.rtt", &hist),
+            vec!["loaded.latency.rtt:1.000|ms"; 3]
+        );
+
+        hist.record_n(1_000_000, 997).unwrap();
+        let sampled = timers("latency.rtt", &hist);
+        assert_eq!(sampled.len(), 50);
+        assert!(sampled
+            .iter()
+            .all(|l| l == "loaded.latency.rtt:1.000|ms|@0.050000"));
+
+        assert!(timers("latency.rtt", &Histogram::new(3).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn packs_datagrams() {
+        let lines = vec!["a".repeat(1000), "b".repeat(400), "c".repeat(100)];
+        let packed = datagrams(&lines);
+        assert_eq!(packed.len(), 2);
+        assert_eq!(packed[0], format!("{}\n{}", lines[0], lines[1]));
+        assert!(packed.iter().all(|d| d.len() <= MAX_DATAGRAM_SIZE));
+        assert_eq!(packed[1], lines[2]);
+    }
+
+    #[test]
+    fn sends_to_ipv6_servers() {
+        let server = UdpSocket::bind("[::1]:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let reporter = StatsdReporter::connect(&addr.to_string(), 3).unwrap();
+        reporter.socket.send(b"loaded.requests:1|c").unwrap();
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"loaded.requests:1|c");
+    }
+}
@@ -1,16 +1,37 @@
+pub mod aws_chunked;
 pub mod checksum;
+pub mod payload_dir;
 pub mod perpetual_stream;
+pub mod unique_stream;
 
 use crate::stream::checksum::Checksum;
 use async_trait::async_trait;
 use futures::Stream;
 
-#[async_trait(?Send)]
+#[async_trait]
 pub trait StreamProvider<S>: Send
 where
     S: Stream,
 {
-    fn new_stream(&mut self) -> S;
-    async fn new_stream_with_checksum(&mut self, checksum: &Checksum) -> (S, String);
+    /// Picks the payload of the next stream when the provider has payloads of its own,
+    /// returning its length, which the next stream must then be requested with
+    fn next_len(&mut self) -> Option<usize> {
+        None
+    }
+    fn new_stream(&mut self, len: usize) -> S;
+    async fn new_stream_with_checksum(
+        &mut self,
+        checksum: &Checksum,
+        len: usize,
+    ) -> (S, StreamChecksum);
     fn empty(&mut self) -> S;
 }
+
+/// How the checksum of a stream is sent along with it
+pub enum StreamChecksum {
+    /// Calculated up front and sent as a header
+    Header(String),
+    /// Calculated as the stream is read and sent in the trailer of its `aws-chunked`
+    /// encoding, the stream taking care of the encoding and being this long once encoded
+    Trailer { encoded_len: usize },
+}
@@ -0,0 +1,99 @@
+use crate::stream::checksum::{Checksum, ChecksumHasher};
+use bytes::{Bytes, BytesMut};
+
+/// Frames the chunks of a stream in the `aws-chunked` encoding, calculating their
+/// checksum along the way to send it in the trailer once the stream ends
+///
+/// The trailer carries the base64 encoding of the raw checksum, as S3 expects it, which
+/// is of the same length whatever the data.
+pub struct ChunkedTrailer {
+    checksum: Checksum,
+    hasher: ChecksumHasher,
+}
+
+impl ChunkedTrailer {
+    pub fn new(checksum: Checksum) -> Self {
+        ChunkedTrailer {
+            checksum,
+            hasher: checksum.hasher(),
+        }
+    }
+
+    /// Leaves room for the chunk header in front of `len` bytes of data to be
+    /// filled in, returning where the data starts
+    pub fn begin_chunk(buf: &mut BytesMut, len: usize) -> usize {
+        buf.extend_from_slice(format!("{len:x}\r\n").as_bytes());
+        buf.len()
+    }
+
+    /// Adds the data that follows `start` to the checksum and ends the chunk
+    pub fn end_chunk(&mut self, mut buf: BytesMut, start: usize) -> Bytes {
+        self.hasher.update(&buf[start..]);
+        buf.extend_from_slice(b"\r\n");
+        buf.freeze()
+    }
+
+    /// Frames a chunk of data
+    pub fn encode(&mut self, data: &[u8]) -> Bytes {
+        let mut buf = BytesMut::with_capacity(data.len() + 16);
+        let start = Self::begin_chunk(&mut buf, data.len());
+        buf.extend_from_slice(data);
+        self.end_chunk(buf, start)
+    }
+
+    /// The empty last chunk followed by the trailer carrying the checksum
+    pub fn finish(self) -> Bytes {
+        Self::last_chunk(self.checksum, &self.hasher.finalize_raw())
+    }
+
+    /// The length of `len` bytes of data once encoded in chunks of `chunk_size`, the last
+    /// of which may be shorter, along with the last chunk and the trailer
+    ///
+    /// Sent as the `Content-Length`, the streamed body being of a known length.
+    pub fn encoded_len(&self, len: usize, chunk_size: usize) -> usize {
+        let chunk = |n: usize| format!("{n:x}\r\n").len() + n + 2;
+        let rest = len % chunk_size;
+        let last = Self::last_chunk(self.checksum, &self.checksum.hasher().finalize_raw());
+        len / chunk_size * chunk(chunk_size) + if rest > 0 { chunk(rest) } else { 0 } + last.len()
+    }
+
+    fn last_chunk(checksum: Checksum, raw: &[u8]) -> Bytes {
+        Bytes::from(format!(
+            "0\r\n{}:{}\r\n\r\n",
+            checksum.header_name(),
+            base64::encode(raw)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::aws_chunked::ChunkedTrailer;
+    use crate::stream::checksum::Checksum;
+
+    #[test]
+    fn frames_chunks_and_trailer() {
+        let mut trailer = ChunkedTrailer::new(Checksum::Sha2);
+        let len = trailer.encoded_len(12, 6);
+        let mut encoded = trailer.encode(b"hello ").to_vec();
+        encoded.extend_from_slice(&trailer.encode(b"world!"));
+        encoded.extend_from_slice(&trailer.finish());
+
+        let checksum = "dQnlvaDHYtK6x/kNdYtbImP6Acy8VCq1498WO+CObKk=";
+        assert_eq!(
+            String::from_utf8(encoded.clone()).unwrap(),
+            format!("6\r\nhello \r\n6\r\nworld!\r\n0\r\nx-amz-checksum-sha256:{checksum}\r\n\r\n")
+        );
+        assert_eq!(len, encoded.len());
+
+        // The last chunk is shorter
+        let trailer = ChunkedTrailer::new(Checksum::Crc32c);
+        assert_eq!(
+            trailer.encoded_len(40_000, 16_384),
+            "4000\r\n\r\n".len() * 2
+                + "1c40\r\n\r\n".len()
+                + 40_000
+                + "0\r\nx-amz-checksum-crc32c:AAAAAA==\r\n\r\n".len()
+        );
+    }
+}
@@ -33,110 +33,163 @@ impl FromStr for Checksum {
     }
 }
 
-const CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_CKSUM);
+static CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
 
-#[async_trait(?Send)]
-pub trait StreamedChecksum {
-    async fn apply<S: Stream<Item = I>, I: AsRef<[u8]>>(&self, stream: S) -> String;
-}
-
-#[async_trait(?Send)]
-impl StreamedChecksum for Checksum {
-    async fn apply<S: Stream<Item = I>, I: AsRef<[u8]>>(&self, stream: S) -> String {
+impl Checksum {
+    /// The header the checksum is sent in, or the trailer when it's streamed
+    pub fn header_name(self) -> &'static str {
         match self {
-            Checksum::Md5 => {
-                let mut hasher = Md5::new();
-
-                let fut = stream.for_each(|f| {
-                    hasher.update(&f);
-                    future::ready(())
-                });
-                fut.await;
+            Checksum::Md5 => "Content-MD5",
+            Checksum::Crc32 => "x-amz-checksum-crc32",
+            Checksum::Crc32c => "x-amz-checksum-crc32c",
+            Checksum::Sha1 => "x-amz-checksum-sha1",
+            Checksum::Sha2 => "x-amz-checksum-sha256",
+        }
+    }
 
-                format!("{:x}", hasher.finalize())
+    /// Whether the checksum found in the header of a response is the one calculated over
+    /// the data, either as the hex digest loaded sends or the base64 encoding of the raw
+    /// checksum that servers send
+    pub fn matches(self, calculated: &str, advertised: &str) -> bool {
+        let advertised = advertised.trim();
+        if advertised.eq_ignore_ascii_case(calculated) {
+            return true;
+        }
+        let Ok(raw) = base64::decode(advertised) else {
+            return false;
+        };
+        let hex = match self {
+            // Formatted as numbers, like those calculated
+            Checksum::Crc32 | Checksum::Crc32c => match <[u8; 4]>::try_from(raw.as_slice()) {
+                Ok(bytes) => format!("{:x}", u32::from_be_bytes(bytes)),
+                Err(_) => return false,
+            },
+            Checksum::Md5 | Checksum::Sha1 | Checksum::Sha2 => {
+                raw.iter().map(|b| format!("{b:02x}")).collect()
             }
-            Checksum::Crc32 => {
-                let mut digest = CRC32.digest();
-
-                let fut = stream.for_each(|f| {
-                    digest.update(&f.as_ref());
-                    future::ready(())
-                });
-                fut.await;
+        };
+        hex == calculated
+    }
 
-                format!("{:x}", digest.finalize())
-            }
-            Checksum::Crc32c => {
-                let mut crc = 0;
+    /// Starts calculating the checksum incrementally, for data that's only seen once
+    pub fn hasher(self) -> ChecksumHasher {
+        match self {
+            Checksum::Md5 => ChecksumHasher::Md5(Md5::new()),
+            Checksum::Crc32 => ChecksumHasher::Crc32(CRC32.digest()),
+            Checksum::Crc32c => ChecksumHasher::Crc32c(0),
+            Checksum::Sha1 => ChecksumHasher::Sha1(Sha1::new()),
+            Checksum::Sha2 => ChecksumHasher::Sha2(Sha256::new()),
+        }
+    }
+}
 
-                let fut = stream.for_each(|f| {
-                    crc = crc32c_hw::update(crc, &f);
-                    future::ready(())
-                });
-                fut.await;
+/// A checksum being calculated over data as it's produced
+pub enum ChecksumHasher {
+    Md5(Md5),
+    Crc32(crc::Digest<'static, u32>),
+    Crc32c(u32),
+    Sha1(Sha1),
+    Sha2(Sha256),
+}
 
-                format!("{crc:x}")
-            }
-            Checksum::Sha1 => {
-                let mut hasher = Sha1::new();
+impl ChecksumHasher {
+    pub fn update(&mut self, buf: &[u8]) {
+        match self {
+            ChecksumHasher::Md5(hasher) => hasher.update(buf),
+            ChecksumHasher::Crc32(digest) => digest.update(buf),
+            ChecksumHasher::Crc32c(crc) => *crc = crc32c_hw::update(*crc, buf),
+            ChecksumHasher::Sha1(hasher) => hasher.update(buf),
+            ChecksumHasher::Sha2(hasher) => hasher.update(buf),
+        }
+    }
 
-                let fut = stream.for_each(|f| {
-                    hasher.update(&f);
-                    future::ready(())
-                });
-                fut.await;
+    /// The checksum of all the data so far, formatted like those calculated up front
+    pub fn finalize(self) -> String {
+        let crc = matches!(self, ChecksumHasher::Crc32(_) | ChecksumHasher::Crc32c(_));
+        let raw = self.finalize_raw();
+        match <[u8; 4]>::try_from(raw.as_slice()) {
+            // Formatted as numbers
+            Ok(bytes) if crc => format!("{:x}", u32::from_be_bytes(bytes)),
+            _ => raw.iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
 
-                format!("{:x}", hasher.finalize())
-            }
-            Checksum::Sha2 => {
-                let mut hasher = Sha256::new();
+    /// The raw bytes of the checksum of all the data so far, those of a CRC being
+    /// big-endian
+    pub fn finalize_raw(self) -> Vec<u8> {
+        match self {
+            ChecksumHasher::Md5(hasher) => hasher.finalize().to_vec(),
+            ChecksumHasher::Crc32(digest) => digest.finalize().to_be_bytes().to_vec(),
+            ChecksumHasher::Crc32c(crc) => crc.to_be_bytes().to_vec(),
+            ChecksumHasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            ChecksumHasher::Sha2(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
 
-                let fut = stream.for_each(|f| {
-                    hasher.update(&f);
-                    future::ready(())
-                });
-                fut.await;
+#[async_trait]
+pub trait StreamedChecksum {
+    async fn apply<S: Stream<Item = I> + Send, I: AsRef<[u8]> + Send>(&self, stream: S) -> String;
+}
 
-                format!("{:x}", hasher.finalize())
-            }
-        }
+#[async_trait]
+impl StreamedChecksum for Checksum {
+    async fn apply<S: Stream<Item = I> + Send, I: AsRef<[u8]> + Send>(&self, stream: S) -> String {
+        let mut hasher = self.hasher();
+        stream
+            .for_each(|f| {
+                hasher.update(f.as_ref());
+                future::ready(())
+            })
+            .await;
+        hasher.finalize()
     }
 }
 
-#[async_trait(?Send)]
+#[async_trait]
 pub trait FullChecksum {
-    async fn apply<B: AsRef<[u8]>>(&self, buf: B) -> String;
+    async fn apply<B: AsRef<[u8]> + Send>(&self, buf: B) -> String;
 }
 
-#[async_trait(?Send)]
+#[async_trait]
 impl FullChecksum for Checksum {
-    async fn apply<B: AsRef<[u8]>>(&self, buf: B) -> String {
-        match self {
-            Checksum::Md5 => {
-                let mut hasher = Md5::new();
-                hasher.update(&buf);
-                format!("{:x}", hasher.finalize())
-            }
-            Checksum::Crc32 => {
-                let mut digest = CRC32.digest();
-                digest.update(&buf.as_ref());
-                format!("{:x}", digest.finalize())
-            }
-            Checksum::Crc32c => {
-                let mut crc = 0;
-                crc = crc32c_hw::update(crc, &buf);
-                format!("{crc:x}")
-            }
-            Checksum::Sha1 => {
-                let mut hasher = Sha1::new();
-                hasher.update(&buf);
-                format!("{:x}", hasher.finalize())
-            }
-            Checksum::Sha2 => {
-                let mut hasher = Sha256::new();
-                hasher.update(&buf);
-                format!("{:x}", hasher.finalize())
-            }
-        }
+    async fn apply<B: AsRef<[u8]> + Send>(&self, buf: B) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(buf.as_ref());
+        hasher.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::checksum::Checksum;
+
+    fn checksum(algo: Checksum, data: &[u8]) -> String {
+        let mut hasher = algo.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    #[test]
+    fn matches_either_encoding() {
+        let sha = checksum(Checksum::Sha2, b"hello");
+        assert!(Checksum::Sha2.matches(&sha, &sha));
+        assert!(Checksum::Sha2.matches(&sha, "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ="));
+        assert!(!Checksum::Sha2.matches(&sha, "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCA="));
+
+        // The number a CRC comes down to is encoded big-endian
+        let crc = checksum(Checksum::Crc32c, b"hello");
+        let raw = u32::from_str_radix(&crc, 16).unwrap().to_be_bytes();
+        assert!(Checksum::Crc32c.matches(&crc, &base64::encode(raw)));
+        assert!(!Checksum::Crc32c.matches(&crc, &base64::encode([0; 8])));
+        assert!(!Checksum::Md5.matches(&crc, "not a checksum"));
+    }
+
+    #[test]
+    fn crc32_is_the_one_s3_uses() {
+        // CRC-32/ISO-HDLC, as returned by S3 in `x-amz-checksum-crc32`
+        let crc = checksum(Checksum::Crc32, b"hello");
+        assert_eq!(crc, "3610a686");
+        assert!(Checksum::Crc32.matches(&crc, "NhCmhg=="));
     }
 }
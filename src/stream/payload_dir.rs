@@ -0,0 +1,198 @@
+use crate::stream::checksum::{Checksum, FullChecksum};
+use crate::stream::perpetual_stream::PerpetualByteStream;
+use crate::stream::{StreamChecksum, StreamProvider};
+use async_trait::async_trait;
+use bytes::Bytes;
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The files of a directory, loaded in memory to be uploaded as they are
+#[derive(Debug, Clone)]
+pub struct PayloadDir(Arc<[Bytes]>);
+
+impl PayloadDir {
+    /// Loads every regular file in the directory, in the order of their names
+    fn load(dir: &Path) -> Result<Self, String> {
+        let mut paths = std::fs::read_dir(dir)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|e| e.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|e| format!("{}: {e}", dir.display()))?;
+        paths.retain(|path| path.is_file());
+        paths.sort();
+        if paths.is_empty() {
+            return Err(format!("{} contains no files", dir.display()));
+        }
+        let payloads = paths
+            .iter()
+            .map(|path| {
+                std::fs::read(path)
+                    .map(Bytes::from)
+                    .map_err(|e| format!("{}: {e}", path.display()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PayloadDir(payloads.into()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The sizes of the smallest and largest of the payloads
+    pub fn size_range(&self) -> (usize, usize) {
+        let sizes = self.0.iter().map(Bytes::len);
+        (sizes.clone().min().unwrap(), sizes.max().unwrap())
+    }
+}
+
+impl FromStr for PayloadDir {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::load(Path::new(s))
+    }
+}
+
+/// The order the payloads are uploaded in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PayloadOrder {
+    /// Cycles through the payloads in the order of their file names
+    RoundRobin,
+    /// Picks a payload at random for every upload
+    Random,
+}
+
+/// A supplier of streams of the payloads of a directory, each stream carrying one
+/// of the payloads whole
+///
+/// The payloads dictate the length of the streams, which are picked by
+/// [`StreamProvider::next_len`]. Their checksums are calculated the first time they're
+/// uploaded, unless they're sent in trailers.
+pub struct PayloadSupplier {
+    payloads: PayloadDir,
+    order: PayloadOrder,
+    rng: StdRng,
+    /// The payload of the next stream
+    next: usize,
+    checksums: HashMap<(Checksum, usize), String>,
+    trailing_checksums: bool,
+}
+
+impl PayloadSupplier {
+    pub fn new(payloads: PayloadDir, order: PayloadOrder, mut rng: StdRng) -> Self {
+        // Connections start off at different payloads
+        let next = rng.gen_range(0..payloads.len());
+        PayloadSupplier {
+            payloads,
+            order,
+            rng,
+            next,
+            checksums: HashMap::default(),
+            trailing_checksums: false,
+        }
+    }
+
+    /// Sends the checksums of the streams in their trailers rather than up front
+    pub fn with_trailing_checksums(mut self) -> Self {
+        self.trailing_checksums = true;
+        self
+    }
+
+    fn current(&self) -> Bytes {
+        self.payloads.0[self.next].clone()
+    }
+}
+
+#[async_trait]
+impl StreamProvider<PerpetualByteStream> for PayloadSupplier {
+    fn next_len(&mut self) -> Option<usize> {
+        self.next = match self.order {
+            PayloadOrder::RoundRobin => (self.next + 1) % self.payloads.len(),
+            PayloadOrder::Random => self.rng.gen_range(0..self.payloads.len()),
+        };
+        Some(self.payloads.0[self.next].len())
+    }
+
+    fn new_stream(&mut self, len: usize) -> PerpetualByteStream {
+        let payload = self.current();
+        debug_assert_eq!(payload.len(), len);
+        PerpetualByteStream::new(payload, 0, len)
+    }
+
+    async fn new_stream_with_checksum(
+        &mut self,
+        checksum: &Checksum,
+        len: usize,
+    ) -> (PerpetualByteStream, StreamChecksum) {
+        let payload = self.current();
+        debug_assert_eq!(payload.len(), len);
+        if self.trailing_checksums {
+            let stream = PerpetualByteStream::with_trailer(payload, 0, len, *checksum);
+            let encoded_len = stream.encoded_len();
+            return (stream, StreamChecksum::Trailer { encoded_len });
+        }
+
+        let key = (*checksum, self.next);
+        let digest = if let Some(digest) = self.checksums.get(&key) {
+            digest.clone()
+        } else {
+            let digest = FullChecksum::apply(checksum, payload.as_ref()).await;
+            self.checksums.insert(key, digest.clone());
+            digest
+        };
+        let stream = PerpetualByteStream::new(payload, 0, len);
+        (stream, StreamChecksum::Header(digest))
+    }
+
+    fn empty(&mut self) -> PerpetualByteStream {
+        PerpetualByteStream::empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::payload_dir::{PayloadDir, PayloadOrder, PayloadSupplier};
+    use crate::stream::StreamProvider;
+    use crate::util;
+    use bytes::Bytes;
+    use futures::executor::block_on_stream;
+
+    #[test]
+    fn uploads_every_payload_whole() {
+        let payloads =
+            PayloadDir(vec![Bytes::from("a"), Bytes::from("bb"), Bytes::from("ccc")].into());
+        let mut supplier =
+            PayloadSupplier::new(payloads, PayloadOrder::RoundRobin, util::seeded_rng("seed"));
+
+        let mut uploads = (0..6)
+            .map(|_| {
+                let len = supplier.next_len().unwrap();
+                let data = block_on_stream(supplier.new_stream(len))
+                    .flat_map(|frame| frame.unwrap().into_data().unwrap())
+                    .collect::<Vec<_>>();
+                assert_eq!(data.len(), len);
+                String::from_utf8(data).unwrap()
+            })
+            .collect::<Vec<_>>();
+        uploads.sort();
+        assert_eq!(uploads, ["a", "a", "bb", "bb", "ccc", "ccc"]);
+    }
+
+    #[test]
+    fn empty_dir() {
+        let dir = std::env::temp_dir().join(format!("loaded-payloads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(dir.to_str().unwrap().parse::<PayloadDir>().is_err());
+        std::fs::write(dir.join("object"), "data").unwrap();
+        let payloads = dir.to_str().unwrap().parse::<PayloadDir>().unwrap();
+        assert_eq!(payloads.size_range(), (4, 4));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
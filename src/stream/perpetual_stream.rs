@@ -1,16 +1,19 @@
+use crate::stream::aws_chunked::ChunkedTrailer;
 use crate::stream::checksum::{Checksum, StreamedChecksum};
-use crate::stream::StreamProvider;
+use crate::stream::{StreamChecksum, StreamProvider};
 use async_trait::async_trait;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures::stream::Stream;
 use futures_util::StreamExt;
 use hyper::body::Frame;
+use log::info;
 use std::cmp::min;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::OnceLock;
 use std::task::{Context, Poll};
+use std::time::Instant;
 
 #[cfg(target_os = "linux")]
 use std::fs;
@@ -20,12 +23,15 @@ const CHUNK_SIZE: usize = 16384;
 /// A byte stream that operates on an underlying buffer of fixed size.
 ///
 /// It produces `num_to_read` bytes by reading from an inner buffer and wrapping
-/// back on itself when it reaches the end.
+/// back on itself when it reaches the end. When it carries a checksum, the stream
+/// is `aws-chunked` encoded with the checksum sent in the trailer.
 pub struct PerpetualByteStream {
     inner: Bytes,
     idx: usize,
     num_to_read: usize,
     num_read: usize,
+    /// Taken once the trailer has been sent
+    trailer: Option<ChunkedTrailer>,
 }
 
 impl PerpetualByteStream {
@@ -35,6 +41,27 @@ impl PerpetualByteStream {
             idx: starting_offset,
             num_to_read: len,
             num_read: 0,
+            trailer: None,
+        }
+    }
+
+    pub fn with_trailer(
+        inner: Bytes,
+        starting_offset: usize,
+        len: usize,
+        checksum: Checksum,
+    ) -> Self {
+        PerpetualByteStream {
+            trailer: Some(ChunkedTrailer::new(checksum)),
+            ..PerpetualByteStream::new(inner, starting_offset, len)
+        }
+    }
+
+    /// The length of the stream as it's sent, once encoded when it carries a checksum
+    pub fn encoded_len(&self) -> usize {
+        match &self.trailer {
+            Some(trailer) => trailer.encoded_len(self.num_to_read, CHUNK_SIZE),
+            None => self.num_to_read,
         }
     }
 
@@ -44,10 +71,23 @@ impl PerpetualByteStream {
             idx: 0,
             num_to_read: 0,
             num_read: 0,
+            trailer: None,
         }
     }
 }
 
+/// Copies `len` bytes out of the buffer starting at `idx`, wrapping back around its start
+/// as many times as it takes, returning them along with the index that follows
+fn copy_wrapping(buf: &[u8], mut idx: usize, len: usize) -> (Bytes, usize) {
+    let mut copy = BytesMut::with_capacity(len);
+    while copy.len() < len {
+        let n = min(len - copy.len(), buf.len() - idx);
+        copy.extend_from_slice(&buf[idx..idx + n]);
+        idx = (idx + n) % buf.len();
+    }
+    (copy.freeze(), idx)
+}
+
 impl Stream for PerpetualByteStream {
     type Item = std::io::Result<Frame<Bytes>>;
     fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
@@ -67,6 +107,12 @@ impl Stream for PerpetualByteStream {
                 let s = pinned.inner.slice_ref(s);
                 pinned.idx = idx + chunk_size;
                 s
+            } else if pinned.trailer.is_some() {
+                // The chunks of an encoded stream are all of the same size, so that its
+                // length is known up front
+                let (s, next) = copy_wrapping(as_slice, idx, chunk_size);
+                pinned.idx = next;
+                s
             } else {
                 let s = &as_slice[idx..];
                 let s = pinned.inner.slice_ref(s);
@@ -76,7 +122,13 @@ impl Stream for PerpetualByteStream {
 
             self.num_read += subslice.len();
 
-            Poll::Ready(Some(Ok(Frame::data(subslice))))
+            let chunk = match &mut self.trailer {
+                Some(trailer) => trailer.encode(&subslice),
+                None => subslice,
+            };
+            Poll::Ready(Some(Ok(Frame::data(chunk))))
+        } else if let Some(trailer) = self.trailer.take() {
+            Poll::Ready(Some(Ok(Frame::data(trailer.finish()))))
         } else {
             Poll::Ready(None)
         }
@@ -92,37 +144,68 @@ impl Stream for PerpetualByteStream {
 ///
 /// As `PerpetualByteStream`'s are requested, increments the starting offset by
 /// a fixed amount to simulate pseudo-random data. Internally stores a cache
-/// of precalculated checksums to not bog down loaded during runtime
+/// of precalculated checksums to not bog down loaded during runtime, unless the
+/// checksums are sent in trailers and calculated as the streams are read
 pub struct PerpetualByteStreamSupplier {
     buf: Bytes,
     offset: usize,
-    len: usize,
     checksum_cache: HashMap<StreamCacheKey, String>,
+    /// Bounds the checksums cached, as streams of varying lengths could otherwise grow
+    /// the cache without limit
+    max_cached_checksums: usize,
+    trailing_checksums: bool,
 }
 
+/// The number of checksums cached unless configured otherwise
+pub const DEFAULT_CACHED_CHECKSUMS: usize = 64 * 1024;
+
 impl PerpetualByteStreamSupplier {
-    pub fn new(buf: Bytes, offset: usize, len: usize) -> Self {
+    pub fn new(buf: Bytes, offset: usize) -> Self {
         PerpetualByteStreamSupplier {
             buf,
             offset,
-            len,
             checksum_cache: HashMap::default(),
+            max_cached_checksums: DEFAULT_CACHED_CHECKSUMS,
+            trailing_checksums: false,
         }
     }
 
+    /// Caps the checksums cached as they're calculated
+    pub fn with_max_cached_checksums(mut self, max: usize) -> Self {
+        self.max_cached_checksums = max;
+        self
+    }
+
+    /// Sends the checksums of the streams in their trailers rather than up front
+    pub fn with_trailing_checksums(mut self) -> Self {
+        self.trailing_checksums = true;
+        self
+    }
+
+    /// Precalculates the checksums of the streams of `len` bytes at every offset, up to
+    /// `max_cached_checksums` of them, past which they're calculated as the streams
+    /// are requested
     pub async fn with_checksums(
         buf: Bytes,
         offset: usize,
         len: usize,
         checksum: &[Checksum],
+        max_cached_checksums: usize,
     ) -> Self {
-        let cache = warm_cache(checksum, &buf, offset, len).await;
+        let start = Instant::now();
+        let cache = warm_cache(checksum, &buf, offset, len, max_cached_checksums).await;
+        info!(
+            "Precalculated {} checksums in {:?}",
+            cache.len(),
+            start.elapsed()
+        );
 
         PerpetualByteStreamSupplier {
             buf,
             offset,
-            len,
             checksum_cache: cache,
+            max_cached_checksums,
+            trailing_checksums: false,
         }
     }
 }
@@ -132,11 +215,12 @@ async fn warm_cache(
     buf: &Bytes,
     offset: usize,
     len: usize,
+    max_entries: usize,
 ) -> HashMap<StreamCacheKey, String> {
     let mut md5_cache: HashMap<StreamCacheKey, String> = HashMap::default();
     let mut offset = offset;
     for checksum in checksums {
-        loop {
+        while md5_cache.len() < max_entries {
             offset = (offset + cache_line_size()) % (buf.len());
             let key = StreamCacheKey {
                 checksum: *checksum,
@@ -184,10 +268,10 @@ fn cache_line_size() -> usize {
     })
 }
 
-#[async_trait(?Send)]
+#[async_trait]
 impl StreamProvider<PerpetualByteStream> for PerpetualByteStreamSupplier {
-    fn new_stream(&mut self) -> PerpetualByteStream {
-        let stream = PerpetualByteStream::new(self.buf.clone(), self.offset, self.len);
+    fn new_stream(&mut self, len: usize) -> PerpetualByteStream {
+        let stream = PerpetualByteStream::new(self.buf.clone(), self.offset, len);
         self.offset = (self.offset + cache_line_size()) % (self.buf.len());
         stream
     }
@@ -195,31 +279,89 @@ impl StreamProvider<PerpetualByteStream> for PerpetualByteStreamSupplier {
     async fn new_stream_with_checksum(
         &mut self,
         checksum: &Checksum,
-    ) -> (PerpetualByteStream, String) {
+        len: usize,
+    ) -> (PerpetualByteStream, StreamChecksum) {
+        if self.trailing_checksums {
+            let stream =
+                PerpetualByteStream::with_trailer(self.buf.clone(), self.offset, len, *checksum);
+            self.offset = (self.offset + cache_line_size()) % (self.buf.len());
+            let encoded_len = stream.encoded_len();
+            return (stream, StreamChecksum::Trailer { encoded_len });
+        }
+
         let key = StreamCacheKey {
             checksum: *checksum,
             offset: self.offset,
-            len: self.len,
+            len,
         };
 
-        let checksum = match self.checksum_cache.entry(key) {
-            Entry::Occupied(e) => e.get().clone(),
-            Entry::Vacant(v) => {
-                let stream = PerpetualByteStream::new(self.buf.clone(), self.offset, self.len);
-                let checksum = checksum
-                    .apply(stream.map(|i| i.unwrap().into_data().unwrap()))
-                    .await;
-                v.insert(checksum.clone());
-                checksum
+        let checksum = if let Some(cached) = self.checksum_cache.get(&key) {
+            cached.clone()
+        } else {
+            let stream = PerpetualByteStream::new(self.buf.clone(), self.offset, len);
+            let checksum = checksum
+                .apply(stream.map(|i| i.unwrap().into_data().unwrap()))
+                .await;
+            if self.checksum_cache.len() < self.max_cached_checksums {
+                self.checksum_cache.insert(key, checksum.clone());
             }
+            checksum
         };
 
-        let stream = PerpetualByteStream::new(self.buf.clone(), self.offset, self.len);
+        let stream = PerpetualByteStream::new(self.buf.clone(), self.offset, len);
         self.offset = (self.offset + cache_line_size()) % (self.buf.len());
-        (stream, checksum)
+        (stream, StreamChecksum::Header(checksum))
     }
 
     fn empty(&mut self) -> PerpetualByteStream {
         PerpetualByteStream::empty()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::checksum::Checksum;
+    use crate::stream::perpetual_stream::{PerpetualByteStream, PerpetualByteStreamSupplier};
+    use crate::stream::{StreamChecksum, StreamProvider};
+    use bytes::Bytes;
+    use futures::executor::{block_on, block_on_stream};
+
+    #[test]
+    fn warm_up_is_bounded() {
+        let buf = Bytes::from(vec![7; 64 * 1024]);
+        let mut supplier = block_on(PerpetualByteStreamSupplier::with_checksums(
+            buf,
+            0,
+            1024,
+            &[Checksum::Md5],
+            3,
+        ));
+        assert_eq!(supplier.checksum_cache.len(), 3);
+
+        // Past the cap the checksums are still calculated, just not cached
+        for _ in 0..10 {
+            let (_, checksum) = block_on(supplier.new_stream_with_checksum(&Checksum::Md5, 1024));
+            assert!(matches!(checksum, StreamChecksum::Header(_)));
+        }
+        assert_eq!(supplier.checksum_cache.len(), 3);
+    }
+
+    #[test]
+    fn encoded_chunks_wrap_around_the_buffer() {
+        let buf = Bytes::from((0..=255).cycle().take(20_000).collect::<Vec<u8>>());
+        let stream = PerpetualByteStream::with_trailer(buf.clone(), 19_000, 50_000, Checksum::Sha2);
+        let encoded_len = stream.encoded_len();
+        let frames = block_on_stream(stream)
+            .map(|frame| frame.unwrap().into_data().unwrap())
+            .collect::<Vec<_>>();
+
+        // Four chunks, the last of them shorter, and the trailer
+        let lens = frames.iter().map(Bytes::len).collect::<Vec<_>>();
+        assert_eq!(lens[..3], [16_384 + 8; 3]);
+        assert_eq!(lens.len(), 5);
+        assert_eq!(lens.iter().sum::<usize>(), encoded_len);
+        // The first chunk wraps around from the end of the buffer
+        assert_eq!(frames[0][6..1006], buf[19_000..]);
+        assert_eq!(frames[0][1006..1016], buf[..10]);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::stream::aws_chunked::ChunkedTrailer;
+use crate::stream::checksum::Checksum;
+use crate::stream::{StreamChecksum, StreamProvider};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::stream::Stream;
+use hyper::body::Frame;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::cmp::min;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+const CHUNK_SIZE: usize = 16384;
+
+/// A byte stream of pseudo-random data generated as it's read, so that no two
+/// streams share their contents.
+///
+/// When it carries a checksum, the stream is `aws-chunked` encoded with the checksum
+/// calculated over the data as it goes and sent in the trailer.
+pub struct UniqueByteStream {
+    rng: StdRng,
+    remaining: usize,
+    /// Taken once the trailer has been sent
+    trailer: Option<ChunkedTrailer>,
+}
+
+impl UniqueByteStream {
+    pub fn new(seed: u64, len: usize) -> Self {
+        UniqueByteStream {
+            rng: StdRng::seed_from_u64(seed),
+            remaining: len,
+            trailer: None,
+        }
+    }
+
+    pub fn with_trailer(seed: u64, len: usize, checksum: Checksum) -> Self {
+        UniqueByteStream {
+            trailer: Some(ChunkedTrailer::new(checksum)),
+            ..UniqueByteStream::new(seed, len)
+        }
+    }
+
+    /// The length of the stream as it's sent, once encoded when it carries a checksum
+    pub fn encoded_len(&self) -> usize {
+        match &self.trailer {
+            Some(trailer) => trailer.encoded_len(self.remaining, CHUNK_SIZE),
+            None => self.remaining,
+        }
+    }
+
+    fn next_chunk(&mut self) -> Bytes {
+        let len = min(self.remaining, CHUNK_SIZE);
+        self.remaining -= len;
+
+        let mut buf = BytesMut::with_capacity(len + 16);
+        let start = match self.trailer {
+            Some(_) => ChunkedTrailer::begin_chunk(&mut buf, len),
+            None => 0,
+        };
+        buf.resize(start + len, 0);
+        self.rng.fill_bytes(&mut buf[start..]);
+        match &mut self.trailer {
+            Some(trailer) => trailer.end_chunk(buf, start),
+            None => buf.freeze(),
+        }
+    }
+}
+
+impl Stream for UniqueByteStream {
+    type Item = std::io::Result<Frame<Bytes>>;
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.remaining > 0 {
+            let chunk = self.next_chunk();
+            Poll::Ready(Some(Ok(Frame::data(chunk))))
+        } else if let Some(trailer) = self.trailer.take() {
+            Poll::Ready(Some(Ok(Frame::data(trailer.finish()))))
+        } else {
+            Poll::Ready(None)
+        }
+    }
+
+    /// The exact number of frames left, a frame per chunk followed by the trailer
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let frames =
+            (self.remaining + CHUNK_SIZE - 1) / CHUNK_SIZE + usize::from(self.trailer.is_some());
+        (frames, Some(frames))
+    }
+}
+
+/// A supplier of `UniqueByteStream`s
+///
+/// Each stream is seeded from a counter so every PUT uploads distinct bytes, which
+/// defeats deduplication and compression on the server at the cost of generating
+/// the data, and calculating its checksum, as it's sent.
+pub struct UniqueByteStreamSupplier {
+    seed: u64,
+    count: u64,
+}
+
+impl UniqueByteStreamSupplier {
+    pub fn new(seed: u64) -> Self {
+        UniqueByteStreamSupplier { seed, count: 0 }
+    }
+
+    fn next_seed(&mut self) -> u64 {
+        self.count += 1;
+        self.seed ^ self.count
+    }
+}
+
+#[async_trait]
+impl StreamProvider<UniqueByteStream> for UniqueByteStreamSupplier {
+    fn new_stream(&mut self, len: usize) -> UniqueByteStream {
+        UniqueByteStream::new(self.next_seed(), len)
+    }
+
+    async fn new_stream_with_checksum(
+        &mut self,
+        checksum: &Checksum,
+        len: usize,
+    ) -> (UniqueByteStream, StreamChecksum) {
+        let stream = UniqueByteStream::with_trailer(self.next_seed(), len, *checksum);
+        let encoded_len = stream.encoded_len();
+        (stream, StreamChecksum::Trailer { encoded_len })
+    }
+
+    fn empty(&mut self) -> UniqueByteStream {
+        UniqueByteStream::new(0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::checksum::{Checksum, FullChecksum};
+    use crate::stream::unique_stream::{UniqueByteStream, UniqueByteStreamSupplier};
+    use crate::stream::StreamProvider;
+    use futures::executor::{block_on, block_on_stream};
+    use futures::Stream;
+
+    fn read(stream: UniqueByteStream) -> Vec<u8> {
+        block_on_stream(stream)
+            .flat_map(|frame| frame.unwrap().into_data().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn streams_are_distinct() {
+        let mut supplier = UniqueByteStreamSupplier::new(42);
+        let stream = supplier.new_stream(40_000);
+        assert_eq!(stream.size_hint(), (3, Some(3)));
+        let a = read(stream);
+        let b = read(supplier.new_stream(40_000));
+        assert_eq!(a.len(), 40_000);
+        assert_eq!(b.len(), 40_000);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn trailer_carries_checksum_of_data() {
+        let len = 20_000;
+        let data = read(UniqueByteStream::new(7, len));
+        let stream = UniqueByteStream::with_trailer(7, len, Checksum::Crc32c);
+        // Two chunks and the trailer
+        assert_eq!(stream.size_hint(), (3, Some(3)));
+        let encoded_len = stream.encoded_len();
+        let encoded = read(stream);
+        assert_eq!(encoded.len(), encoded_len);
+
+        let checksum = block_on(FullChecksum::apply(&Checksum::Crc32c, &data));
+        let checksum = base64::encode(u32::from_str_radix(&checksum, 16).unwrap().to_be_bytes());
+        let mut expected = b"4000\r\n".to_vec();
+        expected.extend_from_slice(&data[..16384]);
+        expected.extend_from_slice(b"\r\ne20\r\n");
+        expected.extend_from_slice(&data[16384..]);
+        expected.extend_from_slice(
+            format!("\r\n0\r\nx-amz-checksum-crc32c:{checksum}\r\n\r\n").as_bytes(),
+        );
+        assert_eq!(encoded, expected);
+    }
+}
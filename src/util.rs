@@ -1,5 +1,11 @@
 use bigdecimal::num_traits::Pow;
+use hyper::header::HeaderValue;
+use hyper::Method;
 use once_cell::sync::OnceCell;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use sysinfo::{System, SystemExt};
 
 const MICROSECOND: u128 = 1000;
@@ -66,6 +72,22 @@ pub fn user_agent<'a>() -> &'a str {
     })
 }
 
+/// Interleaves the indices of the items, each repeated as many times as its count,
+/// e.g. `[3, 1]` gives `0, 1, 0, 0`, so any run of them takes from every item in turn
+pub fn interleave(counts: &[usize]) -> Vec<usize> {
+    let mut remaining = counts.to_vec();
+    let mut indices = Vec::with_capacity(counts.iter().sum());
+    while indices.len() < indices.capacity() {
+        for (i, count) in remaining.iter_mut().enumerate() {
+            if *count > 0 {
+                *count -= 1;
+                indices.push(i);
+            }
+        }
+    }
+    indices
+}
+
 // Divvys up the `to_divvy` value across `num_items` yielding an iterator of equivalent len
 pub fn divvy(to_divvy: usize, num_items: usize) -> impl Iterator<Item = usize> {
     let num_per_item = to_divvy / num_items;
@@ -80,6 +102,65 @@ pub fn divvy(to_divvy: usize, num_items: usize) -> impl Iterator<Item = usize> {
     })
 }
 
+/// Builds a random number generator deterministically derived from the seed
+pub fn seeded_rng(seed: &str) -> StdRng {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    StdRng::seed_from_u64(hasher.finish())
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986
+pub fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The methods requests can be issued with
+const METHODS: [Method; 9] = [
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::CONNECT,
+    Method::OPTIONS,
+    Method::TRACE,
+    Method::PATCH,
+];
+
+/// Parses one of the standard HTTP methods regardless of case, rejecting anything
+/// else as a likely typo
+pub fn parse_method(s: &str) -> Result<Method, String> {
+    METHODS
+        .into_iter()
+        .find(|m| m.as_str().eq_ignore_ascii_case(s))
+        .ok_or_else(|| {
+            let methods = METHODS.map(|m| m.to_string()).join(", ");
+            format!("unknown HTTP method `{s}`, expected one of {methods}")
+        })
+}
+
+/// Parses the value of a header, read from the environment variable it names with
+/// the `env:` prefix (e.g. `env:API_TOKEN`) to keep secrets off the command line
+///
+/// Values read from the environment are marked sensitive.
+pub fn parse_header_value(s: &str) -> Result<HeaderValue, String> {
+    let Some(var) = s.strip_prefix("env:") else {
+        return HeaderValue::from_str(s).map_err(|e| format!("invalid header value `{s}`: {e}"));
+    };
+    let value = std::env::var(var).map_err(|e| format!("environment variable `{var}`: {e}"))?;
+    let mut value = HeaderValue::from_str(&value)
+        .map_err(|e| format!("invalid header value in environment variable `{var}`: {e}"))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,4 +179,32 @@ mod tests {
         let expected = [6, 6, 6, 6, 5].into_iter();
         assert!(actual.eq(expected));
     }
+
+    #[test]
+    fn test_interleave() {
+        assert_eq!(interleave(&[3, 1]), [0, 1, 0, 0]);
+        assert_eq!(interleave(&[2, 0, 2]), [0, 2, 0, 2]);
+        assert!(interleave(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_method() {
+        assert_eq!(parse_method("GET"), Ok(Method::GET));
+        assert_eq!(parse_method("patch"), Ok(Method::PATCH));
+        assert!(parse_method("GTE").is_err());
+    }
+
+    #[test]
+    fn test_parse_header_value() {
+        assert_eq!(parse_header_value("text/plain").unwrap(), "text/plain");
+        assert!(!parse_header_value("text/plain").unwrap().is_sensitive());
+
+        std::env::set_var("LOADED_TEST_HEADER_TOKEN", "Bearer secret");
+        let value = parse_header_value("env:LOADED_TEST_HEADER_TOKEN").unwrap();
+        assert_eq!(value, "Bearer secret");
+        assert!(value.is_sensitive());
+
+        assert!(parse_header_value("env:LOADED_TEST_HEADER_UNSET").is_err());
+        assert!(parse_header_value("line\nbreak").is_err());
+    }
 }
@@ -0,0 +1,62 @@
+//! # Webhook
+//!
+//! Delivers the JSON summary of a run to a URL once it completes, e.g. to notify
+//! a chat channel or feed a results-collection service.
+
+use crate::stats::SummaryStats;
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{header, Method, Request, Uri};
+use hyper_util::rt::TokioIo;
+use log::debug;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// How long to wait for the webhook to be delivered before giving up on it
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// POSTs the summary as JSON to the webhook, failing unless it responds with a success
+pub fn post(url: &Uri, summary: &SummaryStats) -> Result<()> {
+    let body = serde_json::to_vec(summary)?;
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            tokio::time::timeout(TIMEOUT, send(url, body))
+                .await
+                .unwrap_or_else(|_| bail!("timed out after {TIMEOUT:?}"))
+        })
+}
+
+async fn send(url: &Uri, body: Vec<u8>) -> Result<()> {
+    let authority = url.authority().expect("webhook url has an authority");
+    let port = url.port_u16().unwrap_or(80);
+    // IPv6 addresses are bracketed in the url, not when resolved
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let stream = TcpStream::connect((host, port)).await?;
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            debug!("Webhook connection failed: {e}");
+        }
+    });
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(url.path_and_query().map_or("/", |p| p.as_str()))
+        .header(header::HOST, authority.as_str())
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(Full::new(Bytes::from(body)))?;
+    let resp = sender.send_request(req).await?;
+    let status = resp.status();
+    resp.into_body().collect().await?;
+    if !status.is_success() {
+        bail!("responded with {status}");
+    }
+    Ok(())
+}
@@ -1,25 +1,46 @@
-use crate::cli::{Engine, S3Args, SimpleArgs};
-use crate::connection::completion::{DurationCompletionCondition, RequestCompletionCondition};
+use crate::cli::{
+    ChecksumMode, Engine, KeySharding, S3Args, ScriptArgs, SimpleArgs, TrafficPattern,
+};
+use crate::connection::completion::{
+    BytesCompletionCondition, DurationCompletionCondition, RequestCompletionCondition,
+};
+use crate::connection::concurrency::ConcurrencyLimit;
+use crate::connection::dns::DnsCache;
+use crate::connection::latency::LatencyInjector;
 use crate::connection::lifecycle::ConnectionHttpLifecycle;
+use crate::connection::ramp::{ConnectionRamp, RampSchedule};
 use crate::connection::rate_limit::RateLimit;
+use crate::connection::sampling::Sampler;
 use crate::connection::stats::StatsCollector;
-use crate::connection::{Connection, ConnectionRunInfo, RunFlag};
+use crate::connection::{Connection, ConnectionOptions, ConnectionRunInfo, RunFlag};
+use crate::engine::bench::BenchEngine;
+use crate::engine::s3::popularity::KeySampler;
+use crate::engine::s3::ranges::RandomRanges;
+use crate::engine::s3::size::{SizeDistribution, SizeSampler};
+use crate::engine::s3::traffic::{self, SplitRole};
 use crate::engine::s3::uri::UriProvider;
 use crate::engine::s3::S3Engine;
+use crate::engine::script::{self, ScriptEngine};
+use crate::engine::simple::mix::{self, RequestTemplate};
 use crate::engine::simple::SimpleEngine;
+use crate::logging::{self, ConnectionContext};
 use crate::stats::WorkerStats;
+use crate::stream::payload_dir::PayloadSupplier;
 use crate::stream::perpetual_stream::PerpetualByteStreamSupplier;
+use crate::stream::unique_stream::UniqueByteStreamSupplier;
+use crate::stream::StreamProvider;
 use crate::util;
 use anyhow::Result;
 use bytes::{Bytes, BytesMut};
+use futures::Stream;
 use governor::clock::DefaultClock;
 use governor::state::{InMemoryState, NotKeyed};
 use governor::RateLimiter;
 use hyper::Uri;
 use log::debug;
+use rand::Rng;
 use std::iter;
-use std::rc::Rc;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::fs::File;
@@ -31,6 +52,14 @@ pub struct Worker {
     pub run_flag: Arc<AtomicBool>,
     pub stats: Arc<RwLock<WorkerStats>>,
     pub rate_limit: Option<Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>,
+    pub connection_options: ConnectionOptions,
+    pub num_workers: usize,
+    /// The number of connections across all workers of the run
+    pub num_connections: usize,
+    /// The number of connections allowed to be active across the run, when adapting concurrency
+    pub concurrency_limit: Option<Arc<AtomicUsize>>,
+    /// The schedule the connections of the run start on, when ramping them up
+    pub connection_ramp: Option<Arc<RampSchedule>>,
 }
 
 pub struct WorkerInfo {
@@ -39,14 +68,15 @@ pub struct WorkerInfo {
 }
 
 impl Worker {
+    /// Runs a connection to each of the `urls`, which may repeat
     pub async fn run(
         &mut self,
         engine: Engine,
-        url: String,
-        num_connections: usize,
+        urls: Vec<String>,
         seed: String,
         completion_condition: Option<CompletionCondition>,
     ) -> Result<WorkerInfo> {
+        let num_connections = urls.len();
         debug!(
             "Running worker {} with {num_connections} connections",
             self.worker_id
@@ -56,66 +86,90 @@ impl Worker {
         // Setup barrier to sync up all connections to not proceed until all have
         // completed their setup step
         let setup_barrier = Arc::new(Barrier::new(num_connections));
+        // Cleared once the run cools down, from then on responses are no longer recorded
+        let recording = Arc::new(AtomicBool::new(true));
+        // Each connection records its stats on its own before they're merged into these
+        let connection_stats = self.stats.read().await.emptied();
 
         // Build the completions conditions that correspond to our connections
-        let completion_conditions: Vec<Option<CompletionCondition>> = match completion_condition {
-            None => iter::repeat(None).take(num_connections).collect(),
-            Some(c) => {
-                if let CompletionCondition::NumRequests(r) = c {
-                    // Divvy up the requests across the connections so they're distributed evenly
-                    util::divvy(r, num_connections)
-                        .map(|num_requests| Some(CompletionCondition::NumRequests(num_requests)))
-                        .collect()
-                } else {
-                    iter::repeat(Some(c)).take(num_connections).collect()
-                }
-            }
-        };
+        let completion_conditions =
+            CompletionCondition::divvy(completion_condition, num_connections);
+
+        let dns_cache = Arc::new(DnsCache::new(
+            self.connection_options.dns_cache_ttl,
+            self.connection_options.socket.ip_version,
+        ));
+
+        let split_roles = split_roles(&engine, num_connections);
 
-        for (i, completion_condition) in iter::zip(0..num_connections, completion_conditions) {
+        for (i, (url, completion_condition)) in iter::zip(urls, completion_conditions).enumerate() {
             let url = url.parse::<Uri>()?;
-            let stats = self.stats.clone();
             let run = self.run_flag.clone();
             let barrier = setup_barrier.clone();
-            let limit = self.rate_limit.clone();
             let engine = engine.clone();
             let seed = seed.clone();
+            let options = self.connection_options.clone();
             let parent_worker_id = self.worker_id;
+            let (num_workers, run_connections) = (self.num_workers, self.num_connections);
+            let stats = self.stats.clone();
+            let dns_cache = dns_cache.clone();
+            let split_role = split_roles.as_ref().map(|roles| roles[i]);
+            let local_run = Arc::new(AtomicBool::new(true));
+            let lifecycle_listeners = self.create_lifecycle_listeners(
+                i,
+                &format!("{seed}-{}-{i}", self.worker_id),
+                &local_run,
+                &recording,
+                &connection_stats,
+                completion_condition,
+            );
 
-            let handle = tokio::task::spawn_local(async move {
-                let local_run = Rc::new(AtomicBool::new(true));
-                let lifecycle_listeners = Self::create_lifecycle_listeners(
-                    i,
-                    stats,
-                    &run,
-                    &local_run,
-                    limit,
-                    completion_condition,
-                );
-
-                let connection = Connection {
+            let context = ConnectionContext {
+                worker_id: self.worker_id,
+                connection_id: i,
+                url: url.to_string(),
+                request_uri: None,
+            };
+            let handle = tokio::spawn(logging::scope(context, async move {
+                // Readers replay the keys of the writer they're assigned to
+                let owner = match split_role {
+                    Some(SplitRole::Reader { writer }) => writer,
+                    Some(SplitRole::Writer) | None => i,
+                };
+                let keys = Keys {
+                    seed: format!("{seed}-{parent_worker_id}-{owner}"),
+                    run_seed: seed.clone(),
+                    // Interleaved across workers like the connections are divvied up
+                    index: owner * num_workers + parent_worker_id,
+                    num_connections: run_connections,
+                };
+                let seed = format!("{seed}-{parent_worker_id}-{i}");
+                let mut connection = Connection {
                     parent_worker_id,
                     run_flag: RunFlag::new(run, local_run),
                     setup_barrier: barrier,
                     id: i,
                     lifecycle_listeners,
+                    seed: seed.clone(),
+                    backoff: options.backoff(&seed),
+                    options,
+                    dns_cache,
                 };
 
                 match engine {
                     Engine::Simple(simple_args) => {
-                        Self::run_simple_engine(connection, &url, simple_args).await?
+                        Self::run_simple_engine(connection, &url, seed, simple_args).await?
                     }
                     Engine::S3(s3_args) => {
                         Self::run_s3_engine(
-                            connection,
-                            &url,
-                            format!("{seed}-{parent_worker_id}-{i}"),
-                            s3_args,
+                            connection, &url, seed, keys, s3_args, stats, split_role,
                         )
                         .await?
                     }
+                    Engine::Bench(_) => connection.run(&mut BenchEngine, &url).await,
+                    Engine::Script(args) => Self::run_script_engine(connection, &url, args).await?,
                 }
-            });
+            }));
             handles.push(handle);
         }
 
@@ -133,17 +187,49 @@ impl Worker {
     }
 
     fn create_lifecycle_listeners(
+        &self,
         id: usize,
-        stats: Arc<RwLock<WorkerStats>>,
-        global_run: &Arc<AtomicBool>,
-        local_run: &Rc<AtomicBool>,
-        limit: Option<Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>,
+        seed: &str,
+        local_run: &Arc<AtomicBool>,
+        recording: &Arc<AtomicBool>,
+        connection_stats: &WorkerStats,
         completion_condition: Option<CompletionCondition>,
     ) -> Vec<ConnectionHttpLifecycle> {
-        let mut lifecycle_listeners =
-            vec![ConnectionHttpLifecycle::Stats(StatsCollector::new(stats))];
-        if let Some(l) = limit {
-            lifecycle_listeners.push(ConnectionHttpLifecycle::RateLimit(RateLimit::new(l)));
+        let stats = StatsCollector::new(
+            self.stats.clone(),
+            connection_stats.emptied(),
+            recording.clone(),
+        );
+        let stats = if connection_stats.run_stats.slowest.is_enabled() {
+            stats.with_request_tracing()
+        } else {
+            stats
+        };
+        let stats = match self.connection_options.sample_rate {
+            Some(rate) => stats.with_sampling(Sampler::new(
+                rate,
+                util::seeded_rng(&format!("{seed}-sampling")).gen(),
+            )),
+            None => stats,
+        };
+        let mut lifecycle_listeners = vec![ConnectionHttpLifecycle::Stats(stats)];
+        if let Some(active) = &self.concurrency_limit {
+            // Interleave ids across workers so parked connections are spread evenly
+            lifecycle_listeners.push(ConnectionHttpLifecycle::Concurrency(ConcurrencyLimit::new(
+                id * self.num_workers + self.worker_id,
+                active.clone(),
+            )));
+        }
+        if let Some(distribution) = &self.connection_options.inject_latency {
+            lifecycle_listeners.push(ConnectionHttpLifecycle::Latency(LatencyInjector::new(
+                distribution.clone(),
+                util::seeded_rng(&format!("{seed}-latency")),
+            )));
+        }
+        if let Some(l) = &self.rate_limit {
+            lifecycle_listeners.push(ConnectionHttpLifecycle::RateLimit(RateLimit::new(
+                l.clone(),
+            )));
         }
         if let Some(cond) = completion_condition {
             match cond {
@@ -152,13 +238,19 @@ impl Worker {
                         RequestCompletionCondition::new(local_run.clone(), num_requests),
                     ));
                 }
-                CompletionCondition::Duration(duration) => {
+                CompletionCondition::TotalBytes(bytes) => {
+                    lifecycle_listeners.push(ConnectionHttpLifecycle::BytesCompletion(
+                        BytesCompletionCondition::new(local_run.clone(), bytes),
+                    ));
+                }
+                CompletionCondition::Duration { duration, cooldown } => {
                     if id == 0 {
                         // only run one of these
                         lifecycle_listeners.push(ConnectionHttpLifecycle::DurationCompletion(
                             DurationCompletionCondition {
-                                run: global_run.clone(),
+                                run: self.run_flag.clone(),
                                 duration_cond: duration,
+                                cooldown: cooldown.map(|cooldown| (cooldown, recording.clone())),
                                 handle: None,
                             },
                         ));
@@ -166,12 +258,32 @@ impl Worker {
                 }
             }
         }
+        if let Some(schedule) = &self.connection_ramp {
+            // Last so the others are set up by the time it holds the connection back, e.g.
+            // the duration of the run counting down from its start
+            lifecycle_listeners.push(ConnectionHttpLifecycle::Ramp(ConnectionRamp::new(
+                id * self.num_workers + self.worker_id,
+                schedule.clone(),
+                self.run_flag.clone(),
+            )));
+        }
         lifecycle_listeners
     }
 
+    async fn run_script_engine(
+        mut connection: Connection,
+        url: &Uri,
+        script_args: ScriptArgs,
+    ) -> Result<Result<ConnectionRunInfo>> {
+        let requests = script::from_file(&script_args.request_script)?;
+        let mut engine = ScriptEngine::new(requests, script_args.once);
+        Ok(connection.run(&mut engine, url).await)
+    }
+
     async fn run_simple_engine(
         mut connection: Connection,
         url: &Uri,
+        seed: String,
         simple_args: SimpleArgs,
     ) -> Result<Result<ConnectionRunInfo>> {
         let body = if simple_args.body_from_file.is_some() {
@@ -185,10 +297,25 @@ impl Worker {
             None
         };
 
-        let mut engine = SimpleEngine {
-            method: simple_args.method,
-            headers: simple_args.headers,
-            body,
+        let templates = match simple_args.mix {
+            Some(path) => mix::from_file(&path)?,
+            None => vec![RequestTemplate {
+                name: None,
+                weight: 1,
+                method: simple_args.method,
+                path: None,
+                headers: simple_args.headers.into_iter().collect(),
+                body,
+            }],
+        };
+        let engine = SimpleEngine::new(templates, &seed)?;
+        let mut engine = match simple_args.body_size_range {
+            Some(range) => engine.with_generated_bodies(
+                SizeSampler::new(range, util::seeded_rng(&format!("{seed}-body-sizes"))),
+                simple_args.body_entropy,
+                &seed,
+            ),
+            None => engine,
         };
 
         Ok(connection.run(&mut engine, url).await)
@@ -198,60 +325,280 @@ impl Worker {
         mut connection: Connection,
         url: &Uri,
         seed: String,
-        s3_args: S3Args,
+        keys: Keys,
+        mut s3_args: S3Args,
+        stats: Arc<RwLock<WorkerStats>>,
+        split_role: Option<SplitRole>,
     ) -> Result<Result<ConnectionRunInfo>> {
+        let distribution = size_distribution(&mut s3_args);
+        let sizes = SizeSampler::new(distribution, util::seeded_rng(&seed));
+        let uri_supplier = uri_provider(url, &mut s3_args, &keys);
+
+        let traffic_pattern =
+            split_role.map_or(s3_args.traffic_pattern.clone(), SplitRole::pattern);
+        // Seeded by the connection rather than the keys so readers sharing a writer
+        // don't GET the same keys in lockstep
+        let key_sampler = KeySampler::new(
+            s3_args.key_distribution,
+            s3_args.zipf_exponent,
+            uri_supplier.keyspace_size(),
+            util::seeded_rng(&format!("{seed}-keys")),
+        );
+
+        if let Some(payloads) = s3_args.payload_dir.take() {
+            let supp = PayloadSupplier::new(
+                payloads,
+                s3_args.payload_order,
+                util::seeded_rng(&format!("{seed}-payloads")),
+            );
+            let engine = S3Engine::new(
+                match s3_args.checksum_mode {
+                    ChecksumMode::Trailer => supp.with_trailing_checksums(),
+                    ChecksumMode::Header => supp,
+                },
+                uri_supplier,
+                sizes,
+                s3_args.checksum_algorithm,
+                s3_args.expect_continue,
+                traffic_pattern,
+                key_sampler,
+            );
+            let mut engine = Self::configure_s3_engine(engine, &s3_args, &seed, stats, split_role)?;
+            return Ok(connection.run(&mut engine, url).await);
+        }
+
+        if s3_args.unique_payloads {
+            let supp =
+                UniqueByteStreamSupplier::new(util::seeded_rng(&format!("{seed}-payloads")).gen());
+            let engine = S3Engine::new(
+                supp,
+                uri_supplier,
+                sizes,
+                s3_args.checksum_algorithm,
+                s3_args.expect_continue,
+                traffic_pattern,
+                key_sampler,
+            );
+            let mut engine = Self::configure_s3_engine(engine, &s3_args, &seed, stats, split_role)?;
+            return Ok(connection.run(&mut engine, url).await);
+        }
+
         let mut file = File::open("/dev/urandom").await?;
         let mut bytes = BytesMut::zeroed(1024 * 128);
         file.read_exact(&mut bytes).await?;
 
         let bytes = bytes.freeze();
 
-        let base = format!(
-            "{}://{}:{}",
-            &url.scheme().unwrap(),
-            &url.host().unwrap(),
-            &url.port().unwrap()
-        );
-
-        let uri_supplier = UriProvider::new(
-            base,
-            s3_args.bucket,
-            seed,
-            s3_args.prefix_folder_depth,
-            s3_args.num_objs_per_prefix_folder,
-            s3_args.num_branches_per_folder_depth,
+        let supp = match (s3_args.checksum_algorithm, sizes.fixed()) {
+            _ if s3_args.checksum_mode == ChecksumMode::Trailer => {
+                PerpetualByteStreamSupplier::new(bytes, 0).with_trailing_checksums()
+            }
+            // Precalculate the checksums up front when all objects have the same size,
+            // otherwise they're calculated as the sizes are drawn
+            (Some(c), Some(size)) => {
+                PerpetualByteStreamSupplier::with_checksums(
+                    bytes,
+                    0,
+                    size,
+                    &[c],
+                    s3_args.checksum_cache_size,
+                )
+                .await
+            }
+            _ => PerpetualByteStreamSupplier::new(bytes, 0)
+                .with_max_cached_checksums(s3_args.checksum_cache_size),
+        };
+        let engine = S3Engine::new(
+            supp,
+            uri_supplier,
+            sizes,
+            s3_args.checksum_algorithm,
+            s3_args.expect_continue,
+            traffic_pattern,
+            key_sampler,
         );
+        let mut engine = Self::configure_s3_engine(engine, &s3_args, &seed, stats, split_role)?;
 
-        let mut engine = if let Some(c) = s3_args.checksum_algorithm {
-            let supp =
-                PerpetualByteStreamSupplier::with_checksums(bytes, 0, s3_args.object_size, &[c])
-                    .await;
+        Ok(connection.run(&mut engine, url).await)
+    }
 
-            S3Engine::new(
-                supp,
-                uri_supplier,
-                s3_args.object_size,
-                Some(c),
-                s3_args.traffic_pattern,
-            )
+    /// Applies the options of the S3 engine that don't depend on where its data comes from
+    fn configure_s3_engine<P, S>(
+        engine: S3Engine<P, S>,
+        s3_args: &S3Args,
+        seed: &str,
+        stats: Arc<RwLock<WorkerStats>>,
+        split_role: Option<SplitRole>,
+    ) -> Result<S3Engine<P, S>>
+    where
+        P: StreamProvider<S>,
+        S: Stream,
+    {
+        let engine = if split_role.is_some() {
+            engine.with_op_labels()
         } else {
-            let supp = PerpetualByteStreamSupplier::new(bytes, 0, s3_args.object_size);
-
-            S3Engine::new(
-                supp,
-                uri_supplier,
-                s3_args.object_size,
-                None,
-                s3_args.traffic_pattern,
-            )
+            engine
+        };
+        let engine = engine.with_key_count(stats.clone());
+        let engine = if s3_args.verify {
+            engine.with_verification(stats.clone())
+        } else {
+            engine
+        };
+        let engine = if s3_args.verify_checksum {
+            engine.with_checksum_verification(stats.clone())
+        } else {
+            engine
+        };
+        let engine = match s3_args.read_after_write_delay {
+            Some(delay) => engine.with_read_after_write(delay, stats),
+            None => engine,
+        };
+        let engine = match s3_args.random_read_size {
+            Some(size) => engine.with_random_ranges(RandomRanges::new(
+                size,
+                s3_args.random_read_count.get(),
+                util::seeded_rng(&format!("{seed}-ranges")),
+            )),
+            None => engine,
+        };
+        let engine = if s3_args.allow_misses {
+            engine.with_misses_allowed()
+        } else {
+            engine
+        };
+        let engine = if s3_args.chunked {
+            engine.with_chunked_uploads()
+        } else {
+            engine
+        };
+        let engine = if s3_args.if_none_match {
+            engine.with_if_none_match()
+        } else {
+            engine
+        };
+        let engine = if s3_args.cleanup {
+            engine.with_cleanup()
+        } else {
+            engine
+        };
+        let engine = match s3_args.sse {
+            Some(sse) => engine.with_encryption(sse, s3_args.sse_kms_key_id.as_deref())?,
+            None => engine,
+        };
+        let engine = engine.with_content_type(s3_args.content_type.clone());
+        let engine = match &s3_args.storage_class {
+            Some(storage_class) => engine.with_storage_class(storage_class)?,
+            None => engine,
+        };
+        let engine = if s3_args.tagging.is_empty() {
+            engine
+        } else {
+            engine.with_tagging(&s3_args.tagging)?
         };
 
-        Ok(connection.run(&mut engine, url).await)
+        Ok(engine)
+    }
+}
+
+/// The keys a connection PUTs and GETs, which are those of another connection for the
+/// readers of split traffic
+struct Keys {
+    /// The seed of the connection owning the keys, which they're named after
+    seed: String,
+    /// The seed of the run, which the keyspace shared by all connections is named after
+    run_seed: String,
+    /// The index of the connection owning the keys, across all connections of the run
+    index: usize,
+    num_connections: usize,
+}
+
+/// The provider of the keys of the objects, either generated or cycling through the
+/// key file taken out of the args
+fn uri_provider(url: &Uri, s3_args: &mut S3Args, keys: &Keys) -> UriProvider {
+    let base = format!("{}://{}", &url.scheme().unwrap(), &url.authority().unwrap());
+    let prefix = match s3_args.key_sharding {
+        KeySharding::Contiguous => &keys.seed,
+        KeySharding::Interleaved => &keys.run_seed,
+    };
+    let uri_supplier = UriProvider::new(
+        &base,
+        &s3_args.bucket,
+        s3_args.addressing,
+        prefix.clone(),
+        s3_args.prefix_folder_depth,
+        s3_args.num_objs_per_prefix_folder,
+        s3_args.num_branches_per_folder_depth,
+    );
+    let uri_supplier = match s3_args.key_file.take() {
+        // Interleaved connections take keys of their own, there's no need to stagger
+        // their starts
+        Some(key_list) if s3_args.key_sharding == KeySharding::Interleaved => {
+            uri_supplier.with_keys(key_list, 0)
+        }
+        Some(key_list) => {
+            let start = util::seeded_rng(&keys.seed).gen_range(0..key_list.len());
+            uri_supplier.with_keys(key_list, start)
+        }
+        None => uri_supplier,
+    };
+    match s3_args.key_sharding {
+        KeySharding::Contiguous => uri_supplier,
+        KeySharding::Interleaved => uri_supplier.with_stride(keys.index, keys.num_connections),
+    }
+}
+
+/// The role of each connection when the traffic is split, having each of them either
+/// only GET or only PUT
+fn split_roles(engine: &Engine, num_connections: usize) -> Option<Vec<SplitRole>> {
+    match engine {
+        Engine::S3(s3_args) if matches!(s3_args.traffic_pattern, TrafficPattern::Split) => Some(
+            traffic::split_roles(num_connections, s3_args.reader_fraction),
+        ),
+        _ => None,
+    }
+}
+
+/// The distribution the sizes of the objects are drawn from, taking it out of the args
+///
+/// The sizes of the files uploaded from a directory are only drawn on for those of the
+/// objects to GET.
+fn size_distribution(s3_args: &mut S3Args) -> SizeDistribution {
+    match (s3_args.object_size_dist.take(), &s3_args.payload_dir) {
+        (Some(distribution), _) => distribution,
+        (None, Some(payloads)) => match payloads.size_range() {
+            (min, max) if min == max => SizeDistribution::Fixed(min),
+            (min, max) => SizeDistribution::Uniform { min, max },
+        },
+        (None, None) => SizeDistribution::Fixed(s3_args.object_size.unwrap()),
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum CompletionCondition {
     NumRequests(usize),
-    Duration(Duration),
+    /// Bytes to transfer, written and read alike
+    TotalBytes(usize),
+    /// Runs for the duration, no longer recording the stats for the cooldown at its end
+    Duration {
+        duration: Duration,
+        cooldown: Option<Duration>,
+    },
+}
+
+impl CompletionCondition {
+    /// Splits the condition of a worker up into those of its connections, divvying up the
+    /// requests and bytes across them so they're distributed evenly
+    fn divvy(condition: Option<Self>, num_connections: usize) -> Vec<Option<Self>> {
+        match condition {
+            None => iter::repeat(None).take(num_connections).collect(),
+            Some(CompletionCondition::NumRequests(r)) => util::divvy(r, num_connections)
+                .map(|num_requests| Some(CompletionCondition::NumRequests(num_requests)))
+                .collect(),
+            Some(CompletionCondition::TotalBytes(b)) => util::divvy(b, num_connections)
+                .map(|bytes| Some(CompletionCondition::TotalBytes(bytes)))
+                .collect(),
+            Some(c) => iter::repeat(Some(c)).take(num_connections).collect(),
+        }
+    }
 }